@@ -104,7 +104,7 @@ pub enum Commands {
         /// Path to analyze (default: /)
         path: Option<String>,
 
-        /// Maximum depth for directory analysis
+        /// Depth of the directory tree to display (the whole tree is always scanned)
         #[arg(long, default_value = "3")]
         depth: usize,
 
@@ -112,15 +112,45 @@ pub enum Commands {
         #[arg(long)]
         old: Option<u64>,
 
-        /// Find files larger than SIZE (e.g., 100M, 1G)
+        /// Find files larger than SIZE (e.g., 100M, 1G); minimum file size with --duplicates
         #[arg(long)]
         large: Option<String>,
 
         /// Include hidden files
         #[arg(long)]
         hidden: bool,
+
+        /// Find duplicate files and the space they waste
+        #[arg(long)]
+        duplicates: bool,
+
+        /// Stay on the filesystem of PATH (like du -x)
+        #[arg(short = 'x', long)]
+        one_file_system: bool,
+
+        /// Skip entries matching a glob (repeatable; patterns with '/' match the full path)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+
+        /// Record a usage sample and a snapshot of the scanned tree for growth tracking
+        #[arg(long)]
+        record: bool,
+
+        /// Where usage history and scan snapshots are kept [default: $RUSTWHY_STATE_DIR,
+        /// then $XDG_STATE_HOME/rustwhy]
+        #[arg(long, value_name = "DIR")]
+        state_dir: Option<String>,
     },
 
+    /// Check drive health (SMART attributes, NVMe health log)
+    Smart {
+        /// Only check this device (e.g., sda, /dev/nvme0)
+        device: Option<String>,
+    },
+
+    /// Check software RAID (md) arrays, dm-crypt and multipath devices
+    Raid,
+
     /// Explain high disk I/O
     Io {
         /// Continuous monitoring mode
@@ -161,8 +191,52 @@ pub enum Commands {
         /// Show interface statistics
         #[arg(long)]
         interfaces: bool,
+
+        /// URL probed for HTTP(S) timings and certificate checks (with --full)
+        #[arg(long, default_value = "https://www.google.com/")]
+        url: String,
+
+        /// URL expected to answer 204 with an empty body, for captive portal detection (with --full)
+        #[arg(long, default_value = "http://connectivitycheck.gstatic.com/generate_204")]
+        portal_url: String,
+
+        /// Attribute traffic to processes over a sampling interval
+        #[arg(long)]
+        bandwidth: bool,
+
+        /// Number of top processes to show (with --bandwidth)
+        #[arg(long, default_value = "10")]
+        top: usize,
+
+        /// Sampling interval in seconds (with --bandwidth)
+        #[arg(long, default_value = "2")]
+        interval: u64,
+    },
+
+    /// Show which process owns a port (or unix socket) and list listening sockets
+    Port {
+        /// Port number or unix socket path; omit to list all listeners
+        target: Option<String>,
     },
 
+    /// Inspect firewall rules and conntrack usage; with --port, tell whether a connection gets in
+    Firewall {
+        /// Destination port of the connection to check
+        #[arg(long)]
+        port: Option<u16>,
+
+        /// Protocol of the connection to check (tcp, udp, icmp...)
+        #[arg(long, default_value = "tcp")]
+        proto: String,
+
+        /// Interface the connection arrives on
+        #[arg(long)]
+        iface: Option<String>,
+    },
+
+    /// Check clock synchronisation (NTP daemon, offset, stratum, RTC)
+    Time,
+
     /// Explain fan activity
     Fan {
         /// Continuous monitoring mode
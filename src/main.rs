@@ -66,7 +66,7 @@ fn command_to_module_config(cli: &Cli) -> anyhow::Result<(String, ModuleConfig)>
             ModuleConfig { watch: *watch, top_n: *top, interval: *interval, ..config },
         ),
         Commands::Mem { top, .. } => ("mem".into(), ModuleConfig { top_n: *top, ..config }),
-        Commands::Disk { path, depth, old, large, hidden, .. } => {
+        Commands::Disk { path, depth, old, large, hidden, duplicates, one_file_system, exclude, record, state_dir } => {
             extra.insert("path".into(), path.clone().unwrap_or_else(|| "/".into()));
             extra.insert("depth".into(), depth.to_string());
             if let Some(o) = old {
@@ -76,8 +76,24 @@ fn command_to_module_config(cli: &Cli) -> anyhow::Result<(String, ModuleConfig)>
                 extra.insert("large".into(), l.clone());
             }
             extra.insert("hidden".into(), hidden.to_string());
+            extra.insert("duplicates".into(), duplicates.to_string());
+            extra.insert("one_file_system".into(), one_file_system.to_string());
+            if !exclude.is_empty() {
+                extra.insert("exclude".into(), exclude.join("\n"));
+            }
+            extra.insert("record".into(), record.to_string());
+            if let Some(ref d) = state_dir {
+                extra.insert("state_dir".into(), d.clone());
+            }
             ("disk".into(), ModuleConfig { extra_args: extra, ..config })
         }
+        Commands::Smart { device } => {
+            if let Some(ref d) = device {
+                extra.insert("device".into(), d.clone());
+            }
+            ("smart".into(), ModuleConfig { extra_args: extra, ..config })
+        }
+        Commands::Raid => ("raid".into(), config),
         Commands::Io { watch, top, interval, device, .. } => {
             if let Some(ref d) = device {
                 extra.insert("device".into(), d.clone());
@@ -87,10 +103,37 @@ fn command_to_module_config(cli: &Cli) -> anyhow::Result<(String, ModuleConfig)>
                 ModuleConfig { watch: *watch, top_n: *top, interval: *interval, extra_args: extra, ..config },
             )
         },
-        Commands::Net { host, .. } => {
+        Commands::Net { host, full, dns_only, count, interfaces, url, portal_url, bandwidth, top, interval } => {
             extra.insert("host".into(), host.clone());
-            ("net".into(), ModuleConfig { extra_args: extra, ..config })
+            extra.insert("full".into(), full.to_string());
+            extra.insert("url".into(), url.clone());
+            extra.insert("portal_url".into(), portal_url.clone());
+            extra.insert("bandwidth".into(), bandwidth.to_string());
+            extra.insert("interfaces".into(), interfaces.to_string());
+            extra.insert("dns_only".into(), dns_only.to_string());
+            extra.insert("count".into(), count.to_string());
+            (
+                "net".into(),
+                ModuleConfig { top_n: *top, interval: *interval, extra_args: extra, ..config },
+            )
+        }
+        Commands::Port { target } => {
+            if let Some(ref t) = target {
+                extra.insert("port".into(), t.clone());
+            }
+            ("port".into(), ModuleConfig { extra_args: extra, ..config })
+        }
+        Commands::Firewall { port, proto, iface } => {
+            if let Some(p) = port {
+                extra.insert("port".into(), p.to_string());
+            }
+            if let Some(i) = iface {
+                extra.insert("iface".into(), i.clone());
+            }
+            extra.insert("proto".into(), proto.clone());
+            ("firewall".into(), ModuleConfig { extra_args: extra, ..config })
         }
+        Commands::Time => ("time".into(), config),
         Commands::Fan { watch, interval, threshold, .. } => {
             if let Some(t) = threshold {
                 extra.insert("threshold".into(), t.to_string());
@@ -0,0 +1,198 @@
+//! Per-filesystem free space and inode checks via statvfs.
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation, Threshold};
+use crate::core::severity::Severity;
+use crate::utils::{format_bytes, read_mounts, shell_quote, MountEntry};
+use nix::sys::statvfs::statvfs;
+use std::collections::HashSet;
+
+/// Space/inode usage (percent) that triggers a warning.
+const USAGE_WARNING_PCT: f64 = 90.0;
+/// Space/inode usage (percent) that triggers a critical finding.
+const USAGE_CRITICAL_PCT: f64 = 97.0;
+
+/// Capacity of one mounted filesystem.
+#[derive(Debug, Clone)]
+pub struct FsUsage {
+    pub mount: MountEntry,
+    pub total: u64,
+    pub used: u64,
+    /// Free space available to unprivileged users.
+    pub avail: u64,
+    /// Blocks reserved for root (free minus available).
+    pub reserved: u64,
+    pub inodes_total: u64,
+    pub inodes_free: u64,
+}
+
+impl FsUsage {
+    /// Used space as `df` reports it: reserved blocks are not counted as available.
+    pub fn used_pct(&self) -> f64 {
+        let denom = self.used + self.avail;
+        if denom == 0 {
+            0.0
+        } else {
+            self.used as f64 / denom as f64 * 100.0
+        }
+    }
+
+    /// True for writable filesystems at or above the warning threshold (space or inodes).
+    pub fn is_nearly_full(&self) -> bool {
+        !self.mount.is_read_only()
+            && (self.used_pct() >= USAGE_WARNING_PCT
+                || self.inode_pct().is_some_and(|p| p >= USAGE_WARNING_PCT))
+    }
+
+    /// Used inodes in percent; `None` when the filesystem has no fixed inode table (btrfs).
+    pub fn inode_pct(&self) -> Option<f64> {
+        if self.inodes_total == 0 {
+            return None;
+        }
+        let used = self.inodes_total.saturating_sub(self.inodes_free);
+        Some(used as f64 / self.inodes_total as f64 * 100.0)
+    }
+}
+
+/// statvfs every real (non-pseudo) mount, skipping duplicate bind mounts.
+pub fn real_filesystems() -> Vec<FsUsage> {
+    let mut out = Vec::new();
+    let mut seen = HashSet::new();
+    for mount in read_mounts().unwrap_or_default() {
+        if mount.is_pseudo() {
+            continue;
+        }
+        let st = match statvfs(&mount.mountpoint) {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+        if st.blocks() == 0 || !seen.insert(st.filesystem_id()) {
+            continue;
+        }
+        let frsize = st.fragment_size() as u64;
+        let total = st.blocks() as u64 * frsize;
+        let free = st.blocks_free() as u64 * frsize;
+        let avail = st.blocks_available() as u64 * frsize;
+        out.push(FsUsage {
+            mount,
+            total,
+            used: total.saturating_sub(free),
+            avail,
+            reserved: free.saturating_sub(avail),
+            inodes_total: st.files() as u64,
+            inodes_free: st.files_free() as u64,
+        });
+    }
+    out
+}
+
+/// Add capacity metrics, findings and recommendations for each filesystem.
+pub fn analyze(report: &mut DiagnosticReport, filesystems: &[FsUsage]) {
+    for fs in filesystems {
+        let mp = fs.mount.mountpoint.display().to_string();
+        let used_pct = fs.used_pct();
+        report.add_metric(Metric {
+            name: format!("{} used", mp),
+            value: MetricValue::Float(used_pct),
+            unit: Some("%".into()),
+            threshold: Some(Threshold {
+                warning: USAGE_WARNING_PCT,
+                critical: USAGE_CRITICAL_PCT,
+            }),
+        });
+        report.add_metric(Metric {
+            name: format!("{} free", mp),
+            value: MetricValue::Text(format!(
+                "{} of {} ({} reserved for root)",
+                format_bytes(fs.avail),
+                format_bytes(fs.total),
+                format_bytes(fs.reserved)
+            )),
+            unit: None,
+            threshold: None,
+        });
+        let inode_pct = fs.inode_pct();
+        if let Some(pct) = inode_pct {
+            report.add_metric(Metric {
+                name: format!("{} inodes used", mp),
+                value: MetricValue::Float(pct),
+                unit: Some("%".into()),
+                threshold: Some(Threshold {
+                    warning: USAGE_WARNING_PCT,
+                    critical: USAGE_CRITICAL_PCT,
+                }),
+            });
+        }
+
+        // A full read-only filesystem (images, recovery partitions) is not a problem.
+        if fs.mount.is_read_only() {
+            continue;
+        }
+
+        if used_pct >= USAGE_WARNING_PCT {
+            let severity = if used_pct >= USAGE_CRITICAL_PCT {
+                Severity::Critical
+            } else {
+                Severity::Warning
+            };
+            report.add_finding(Finding {
+                severity,
+                category: "filesystem".into(),
+                message: format!("{} is {:.0}% full ({} free)", mp, used_pct, format_bytes(fs.avail)),
+                details: Some(format!(
+                    "{} on {} ({}); {} more is reserved for root and not usable by normal users.",
+                    format_bytes(fs.used),
+                    fs.mount.device,
+                    fs.mount.fstype,
+                    format_bytes(fs.reserved)
+                )),
+            });
+            report.add_recommendation(Recommendation {
+                priority: if severity == Severity::Critical { 1 } else { 2 },
+                action: format!("Find what fills {}", mp),
+                command: Some(format!("rustwhy disk {} --depth 2", shell_quote(&mp))),
+                explanation: "Writes fail with 'No space left on device' once the filesystem is full."
+                    .into(),
+            });
+        }
+
+        if let Some(pct) = inode_pct {
+            if fs.inodes_free == 0 && used_pct < USAGE_WARNING_PCT {
+                report.add_finding(Finding {
+                    severity: Severity::Critical,
+                    category: "inodes".into(),
+                    message: format!(
+                        "{} has 0 free inodes but {} of free space",
+                        mp,
+                        format_bytes(fs.avail)
+                    ),
+                    details: Some(
+                        "Every file needs an inode; millions of tiny files (caches, mail queues, \
+                         session files) exhaust them and new files fail with 'No space left on \
+                         device' even though df shows free space."
+                            .into(),
+                    ),
+                });
+            } else if pct >= USAGE_WARNING_PCT {
+                report.add_finding(Finding {
+                    severity: if pct >= USAGE_CRITICAL_PCT {
+                        Severity::Critical
+                    } else {
+                        Severity::Warning
+                    },
+                    category: "inodes".into(),
+                    message: format!("{} has used {:.0}% of its inodes", mp, pct),
+                    details: Some(format!("{} inodes left", fs.inodes_free)),
+                });
+            } else {
+                continue;
+            }
+            report.add_recommendation(Recommendation {
+                priority: 1,
+                action: format!("Find directories with the most files on {}", mp),
+                command: Some(format!("du --inodes -x {} 2>/dev/null | sort -rn | head -20", shell_quote(&mp))),
+                explanation: "Deleting or archiving directories with huge file counts frees inodes."
+                    .into(),
+            });
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! Classifier for well-known reclaimable locations (logs, caches, images, build output).
+
+use super::tree::dir_size;
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use crate::utils::{command_exists, format_bytes, list_dir, read_first_line, shell_quote};
+use std::path::{Path, PathBuf};
+
+/// Targets smaller than this are not worth a finding.
+const MIN_REPORT_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Whether a cleanup target can be removed without further thought.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Safety {
+    /// Regenerated automatically or only holds disposable data.
+    Safe,
+    /// Removing it is usually fine but may lose something the user wants.
+    Review,
+}
+
+impl Safety {
+    fn label(&self) -> &'static str {
+        match self {
+            Safety::Safe => "Safe to delete",
+            Safety::Review => "Review before deleting",
+        }
+    }
+}
+
+/// Kind of well-known space consumer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CleanupKind {
+    Journal,
+    PackageCache(&'static str),
+    UserCache,
+    Containers(&'static str),
+    CargoTarget,
+    OldKernel(String),
+    Flatpak,
+    CoreDumps,
+    Trash,
+}
+
+impl CleanupKind {
+    fn label(&self) -> String {
+        match self {
+            CleanupKind::Journal => "systemd journal".into(),
+            CleanupKind::PackageCache(pm) => format!("{} package cache", pm),
+            CleanupKind::UserCache => "user cache".into(),
+            CleanupKind::Containers(engine) => format!("{} images and containers", engine),
+            CleanupKind::CargoTarget => "Cargo build output".into(),
+            CleanupKind::OldKernel(version) => format!("old kernel {}", version),
+            CleanupKind::Flatpak => "Flatpak runtimes".into(),
+            CleanupKind::CoreDumps => "core dumps".into(),
+            CleanupKind::Trash => "trash".into(),
+        }
+    }
+
+    fn safety(&self) -> Safety {
+        match self {
+            CleanupKind::Containers(_) | CleanupKind::OldKernel(_) => Safety::Review,
+            _ => Safety::Safe,
+        }
+    }
+
+    fn explanation(&self) -> &'static str {
+        match self {
+            CleanupKind::Journal => "Vacuuming drops the oldest journal files and keeps recent logs.",
+            CleanupKind::PackageCache(_) => {
+                "Downloaded packages are only kept for reinstalls; they are fetched again if needed."
+            }
+            CleanupKind::UserCache => "Applications rebuild their caches on demand.",
+            CleanupKind::Containers(_) => {
+                "Prune removes stopped containers, dangling images and unused networks; \
+                 check nothing you need is stopped."
+            }
+            CleanupKind::CargoTarget => "Build artifacts are recreated by the next cargo build.",
+            CleanupKind::OldKernel(_) => {
+                "Kernels other than the running one are fallbacks; keep at least one that boots."
+            }
+            CleanupKind::Flatpak => "Runtimes no longer used by any installed app can be removed.",
+            CleanupKind::CoreDumps => "Core dumps are only useful while debugging a crash.",
+            CleanupKind::Trash => "Files in the trash were already deleted by the user.",
+        }
+    }
+
+    fn command(&self, path: &Path) -> Option<String> {
+        let cmd = match self {
+            CleanupKind::Journal => "sudo journalctl --vacuum-size=200M".into(),
+            CleanupKind::PackageCache(pm) => match *pm {
+                "apt" => "sudo apt-get clean".into(),
+                "pacman" if command_exists("paccache") => "sudo paccache -rk1".into(),
+                "pacman" => "sudo pacman -Sc".into(),
+                "dnf" => "sudo dnf clean packages".into(),
+                "yum" => "sudo yum clean packages".into(),
+                "zypper" => "sudo zypper clean --all".into(),
+                _ => return None,
+            },
+            CleanupKind::UserCache => format!("rm -rf {}/*", quoted(path)),
+            CleanupKind::Containers(engine) => format!("{} system prune", engine),
+            CleanupKind::CargoTarget => format!(
+                "cargo clean --manifest-path {}",
+                quoted(&path.with_file_name("Cargo.toml"))
+            ),
+            CleanupKind::OldKernel(_) if command_exists("apt-get") => {
+                "sudo apt-get autoremove --purge".into()
+            }
+            CleanupKind::OldKernel(_) if command_exists("dnf") => {
+                "sudo dnf remove --oldinstallonly".into()
+            }
+            CleanupKind::OldKernel(_) => return None,
+            CleanupKind::Flatpak => "flatpak uninstall --unused".into(),
+            CleanupKind::CoreDumps => format!("sudo find {} -type f -delete", quoted(path)),
+            CleanupKind::Trash if command_exists("gio") => "gio trash --empty".into(),
+            CleanupKind::Trash => {
+                let path = quoted(path);
+                format!("rm -rf {}/files/* {}/info/*", path, path)
+            }
+        };
+        Some(cmd)
+    }
+}
+
+fn quoted(path: &Path) -> String {
+    shell_quote(&path.to_string_lossy())
+}
+
+/// A recognized location with its measured size.
+#[derive(Debug, Clone)]
+pub struct CleanupTarget {
+    pub kind: CleanupKind,
+    pub path: PathBuf,
+    /// `None` when the location exists but could not be read (usually needs root).
+    pub size: Option<u64>,
+}
+
+/// Home directories to look into: /root, /home/* and $HOME.
+fn home_dirs() -> Vec<PathBuf> {
+    let mut homes: Vec<PathBuf> = vec![PathBuf::from("/root")];
+    homes.extend(list_dir(Path::new("/home")).unwrap_or_default());
+    if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        if !homes.contains(&home) {
+            homes.push(home);
+        }
+    }
+    homes.into_iter().filter(|h| h.is_dir()).collect()
+}
+
+/// Fixed system and per-user locations that commonly fill up a disk.
+fn known_locations() -> Vec<(CleanupKind, PathBuf)> {
+    let mut out = vec![
+        (CleanupKind::Journal, PathBuf::from("/var/log/journal")),
+        (CleanupKind::PackageCache("apt"), PathBuf::from("/var/cache/apt/archives")),
+        (CleanupKind::PackageCache("pacman"), PathBuf::from("/var/cache/pacman/pkg")),
+        (CleanupKind::PackageCache("dnf"), PathBuf::from("/var/cache/dnf")),
+        (CleanupKind::PackageCache("yum"), PathBuf::from("/var/cache/yum")),
+        (CleanupKind::PackageCache("zypper"), PathBuf::from("/var/cache/zypp/packages")),
+        (CleanupKind::Containers("docker"), PathBuf::from("/var/lib/docker")),
+        (CleanupKind::Containers("podman"), PathBuf::from("/var/lib/containers/storage")),
+        (CleanupKind::Flatpak, PathBuf::from("/var/lib/flatpak")),
+        (CleanupKind::CoreDumps, PathBuf::from("/var/lib/systemd/coredump")),
+        (CleanupKind::CoreDumps, PathBuf::from("/var/crash")),
+    ];
+    for home in home_dirs() {
+        out.push((CleanupKind::UserCache, home.join(".cache")));
+        out.push((CleanupKind::Trash, home.join(".local/share/Trash")));
+        out.push((CleanupKind::Flatpak, home.join(".local/share/flatpak")));
+        out.push((CleanupKind::Containers("podman"), home.join(".local/share/containers/storage")));
+    }
+    out.extend(old_kernels());
+    out
+}
+
+/// Module directories of installed kernels other than the running one.
+fn old_kernels() -> Vec<(CleanupKind, PathBuf)> {
+    let running = read_first_line(Path::new("/proc/sys/kernel/osrelease"))
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    list_dir(Path::new("/lib/modules"))
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|dir| {
+            let version = dir.file_name()?.to_string_lossy().into_owned();
+            (!running.is_empty() && version != running).then_some((CleanupKind::OldKernel(version), dir))
+        })
+        .collect()
+}
+
+/// Size of an old kernel: its modules plus the matching files in /boot.
+fn kernel_size(version: &str, modules_dir: &Path) -> u64 {
+    let boot: u64 = list_dir(Path::new("/boot"))
+        .unwrap_or_default()
+        .iter()
+        .filter(|p| p.file_name().is_some_and(|n| n.to_string_lossy().ends_with(version)))
+        .filter_map(|p| std::fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum();
+    dir_size(modules_dir) + boot
+}
+
+/// Find and measure known cleanup targets; `include` decides which locations are relevant.
+pub fn classify(include: impl Fn(&Path) -> bool, cargo_targets: &[(PathBuf, u64)]) -> Vec<CleanupTarget> {
+    let mut targets = Vec::new();
+    for (kind, path) in known_locations() {
+        let path = match path.canonicalize() {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+        if !include(&path) || targets.iter().any(|t: &CleanupTarget| t.path == path) {
+            continue;
+        }
+        let size = if std::fs::read_dir(&path).is_err() {
+            None
+        } else if let CleanupKind::OldKernel(ref version) = kind {
+            Some(kernel_size(version, &path))
+        } else {
+            Some(dir_size(&path))
+        };
+        targets.push(CleanupTarget { kind, path, size });
+    }
+    for (path, size) in cargo_targets {
+        targets.push(CleanupTarget {
+            kind: CleanupKind::CargoTarget,
+            path: path.clone(),
+            size: Some(*size),
+        });
+    }
+    // Unreadable locations stay listed.
+    targets.retain(|t| t.size.unwrap_or(u64::MAX) >= MIN_REPORT_SIZE);
+    targets.sort_by_key(|t| std::cmp::Reverse(t.size.unwrap_or(0)));
+    targets
+}
+
+/// Add one finding and recommendation per cleanup target.
+pub fn analyze(report: &mut DiagnosticReport, targets: &[CleanupTarget], limit: usize) {
+    let reclaimable: u64 = targets
+        .iter()
+        .filter(|t| t.kind.safety() == Safety::Safe)
+        .filter_map(|t| t.size)
+        .sum();
+    if reclaimable > 0 {
+        report.add_metric(Metric {
+            name: "Safely reclaimable".into(),
+            value: MetricValue::Text(format_bytes(reclaimable)),
+            unit: None,
+            threshold: None,
+        });
+    }
+
+    for target in targets.iter().take(limit) {
+        let size = match target.size {
+            Some(s) => format_bytes(s),
+            None => "size unknown (run as root)".into(),
+        };
+        let safety = target.kind.safety();
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "cleanup".into(),
+            message: format!("{}: {} in {}", target.kind.label(), size, target.path.display()),
+            details: Some(safety.label().into()),
+        });
+        let big = target.size.is_some_and(|s| s >= 1024 * 1024 * 1024);
+        report.add_recommendation(Recommendation {
+            priority: match (safety, big) {
+                (Safety::Safe, true) => 2,
+                (Safety::Safe, false) => 3,
+                (Safety::Review, _) => 4,
+            },
+            action: format!("Reclaim space from {}", target.kind.label()),
+            command: target.kind.command(&target.path),
+            explanation: target.kind.explanation().into(),
+        });
+    }
+}
@@ -0,0 +1,186 @@
+//! Space held by deleted files that are still open (`df` full, `du` can't find it).
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use crate::utils::{format_bytes, mount_for_path, process_name, read_mounts};
+use std::collections::{BTreeMap, HashSet};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Open files below this size are ignored.
+const MIN_FILE_SIZE: u64 = 1024 * 1024;
+/// A process holding more than this in deleted files gets a warning.
+const WARN_HELD_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// One deleted file held open through a file descriptor.
+#[derive(Debug, Clone)]
+pub struct DeletedFile {
+    pub fd: u32,
+    pub path: PathBuf,
+    pub size: u64,
+    pub mountpoint: PathBuf,
+}
+
+/// Plain-text logs (`*.log`, `*.log.1`, files under a `log`/`logs` directory) are safe to
+/// truncate; databases, journals and other structured files are not.
+fn is_log_file(path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    if name.ends_with(".journal") || name.ends_with(".journal~") {
+        return false;
+    }
+    name.ends_with(".log")
+        || name.contains(".log.")
+        || name == "nohup.out"
+        || path
+            .parent()
+            .and_then(Path::file_name)
+            .is_some_and(|d| d == "log" || d == "logs")
+}
+
+/// All deleted-but-open files of one process.
+#[derive(Debug, Clone)]
+pub struct HoldingProcess {
+    pub pid: u32,
+    pub name: String,
+    pub files: Vec<DeletedFile>,
+}
+
+impl HoldingProcess {
+    pub fn total(&self) -> u64 {
+        self.files.iter().map(|f| f.size).sum()
+    }
+}
+
+/// Walk /proc/<pid>/fd for targets marked "(deleted)" on real filesystems.
+///
+/// Each inode is counted once even if several descriptors or processes keep it open.
+pub fn scan() -> Vec<HoldingProcess> {
+    let mounts = read_mounts().unwrap_or_default();
+    let mut seen: HashSet<(u64, u64)> = HashSet::new();
+    let mut out = Vec::new();
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return out;
+    };
+    for entry in entries.flatten() {
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let mut files = Vec::new();
+        for fd_entry in fds.flatten() {
+            let Ok(fd) = fd_entry.file_name().to_string_lossy().parse::<u32>() else {
+                continue;
+            };
+            let Ok(target) = std::fs::read_link(fd_entry.path()) else {
+                continue;
+            };
+            let target = target.to_string_lossy();
+            let Some(original) = target.strip_suffix(" (deleted)") else {
+                continue;
+            };
+            // memfd and SysV shared memory are anonymous, not space on a filesystem.
+            if !original.starts_with('/') || original.starts_with("/memfd:") || original.starts_with("/SYSV") {
+                continue;
+            }
+            let path = PathBuf::from(original);
+            let Some(mount) = mount_for_path(&mounts, &path) else {
+                continue;
+            };
+            if mount.is_pseudo() {
+                continue;
+            }
+            // Metadata through the fd link still reaches the unlinked inode.
+            let Ok(meta) = std::fs::metadata(fd_entry.path()) else {
+                continue;
+            };
+            let size = meta.blocks().saturating_mul(512);
+            if !meta.is_file() || size < MIN_FILE_SIZE || !seen.insert((meta.dev(), meta.ino())) {
+                continue;
+            }
+            files.push(DeletedFile {
+                fd,
+                path,
+                size,
+                mountpoint: mount.mountpoint.clone(),
+            });
+        }
+        if !files.is_empty() {
+            files.sort_by_key(|f| std::cmp::Reverse(f.size));
+            out.push(HoldingProcess {
+                pid,
+                name: process_name(pid).unwrap_or_else(|_| format!("[pid {}]", pid)),
+                files,
+            });
+        }
+    }
+    out.sort_by_key(|p| std::cmp::Reverse(p.total()));
+    out
+}
+
+/// Report deleted-but-open space per filesystem and per process.
+pub fn analyze(report: &mut DiagnosticReport, holders: &[HoldingProcess], limit: usize) {
+    let mut per_fs: BTreeMap<&Path, u64> = BTreeMap::new();
+    for file in holders.iter().flat_map(|p| &p.files) {
+        *per_fs.entry(file.mountpoint.as_path()).or_insert(0) += file.size;
+    }
+    for (mountpoint, size) in &per_fs {
+        report.add_metric(Metric {
+            name: format!("{} deleted but open", mountpoint.display()),
+            value: MetricValue::Text(format_bytes(*size)),
+            unit: None,
+            threshold: None,
+        });
+    }
+
+    for proc_ in holders.iter().take(limit) {
+        let total = proc_.total();
+        let file_list = proc_
+            .files
+            .iter()
+            .map(|f| format!("fd {}: {} ({})", f.fd, f.path.display(), format_bytes(f.size)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        report.add_finding(Finding {
+            severity: if total >= WARN_HELD_BYTES {
+                Severity::Warning
+            } else {
+                Severity::Info
+            },
+            category: "deleted-open".into(),
+            message: format!(
+                "{} (PID {}) holds {} in {} deleted file(s)",
+                proc_.name,
+                proc_.pid,
+                format_bytes(total),
+                proc_.files.len()
+            ),
+            details: Some(format!(
+                "The space is only freed when the file is closed; du cannot see it.\n{}",
+                file_list
+            )),
+        });
+
+        report.add_recommendation(Recommendation {
+            priority: if total >= WARN_HELD_BYTES { 2 } else { 5 },
+            action: format!("Restart {} (PID {}) to release deleted files", proc_.name, proc_.pid),
+            command: Some(format!("systemctl status {}", proc_.pid)),
+            explanation: "systemctl status shows the owning unit; restarting it closes the \
+                          descriptors. Usually a log file was rotated or removed while still open."
+                .into(),
+        });
+        let largest = &proc_.files[0];
+        if total >= WARN_HELD_BYTES && is_log_file(&largest.path) {
+            report.add_recommendation(Recommendation {
+                priority: 2,
+                action: format!("Or truncate {} without restarting", largest.path.display()),
+                command: Some(format!("sudo truncate -s 0 /proc/{}/fd/{}", proc_.pid, largest.fd)),
+                explanation: "Truncating through /proc frees the blocks immediately, and the content \
+                              of the file is lost for good; the process keeps writing to the now \
+                              empty file. Only do this for logs nobody needs."
+                    .into(),
+            });
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Duplicate file finder: size buckets, then a partial hash, then a full content hash.
+
+use super::tree::ScanFilter;
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use crate::utils::{command_exists, format_bytes, shell_quote};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Bytes read from the start of each candidate for the cheap first-pass hash.
+const PARTIAL_HASH_LEN: u64 = 16 * 1024;
+
+/// Files with identical content; hardlinks of one inode appear only once.
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    pub paths: Vec<PathBuf>,
+}
+
+impl DuplicateGroup {
+    /// Bytes that would be freed by keeping a single copy.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64).saturating_sub(1)
+    }
+}
+
+fn hash_prefix(path: &Path) -> io::Result<blake3::Hash> {
+    let mut buf = Vec::with_capacity(PARTIAL_HASH_LEN as usize);
+    File::open(path)?.take(PARTIAL_HASH_LEN).read_to_end(&mut buf)?;
+    Ok(blake3::hash(&buf))
+}
+
+fn hash_full(path: &Path) -> io::Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize())
+}
+
+/// Split every group with more than one member by `key`, dropping singletons and unreadable files.
+fn refine<K, F>(groups: Vec<(u64, Vec<PathBuf>)>, key: F) -> Vec<(u64, Vec<PathBuf>)>
+where
+    K: std::hash::Hash + Eq,
+    F: Fn(&Path) -> io::Result<K>,
+{
+    let mut out = Vec::new();
+    for (size, paths) in groups {
+        let mut buckets: HashMap<K, Vec<PathBuf>> = HashMap::new();
+        for path in paths {
+            if let Ok(k) = key(&path) {
+                buckets.entry(k).or_default().push(path);
+            }
+        }
+        out.extend(buckets.into_values().filter(|p| p.len() > 1).map(|p| (size, p)));
+    }
+    out
+}
+
+/// Find groups of identical files of at least `min_size` bytes under `root`.
+pub fn find(root: &Path, filter: &ScanFilter, min_size: u64) -> Vec<DuplicateGroup> {
+    let mut seen_inodes: HashSet<(u64, u64)> = HashSet::new();
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+
+    let walker = WalkDir::new(root)
+        .follow_links(false)
+        .same_file_system(filter.one_file_system)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !filter.is_excluded(e.path(), e.file_name()));
+    for entry in walker.flatten() {
+        let meta = match entry.metadata() {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        if !meta.is_file() || meta.len() < min_size.max(1) {
+            continue;
+        }
+        if !seen_inodes.insert((meta.dev(), meta.ino())) {
+            continue;
+        }
+        by_size.entry(meta.len()).or_default().push(entry.into_path());
+    }
+
+    let candidates: Vec<_> = by_size.into_iter().filter(|(_, p)| p.len() > 1).collect();
+    let partial = refine(candidates, hash_prefix);
+    // The prefix hash already covers small files completely.
+    let (complete, large): (Vec<_>, Vec<_>) =
+        partial.into_iter().partition(|(size, _)| *size <= PARTIAL_HASH_LEN);
+    let confirmed = complete.into_iter().chain(refine(large, hash_full));
+
+    let mut groups: Vec<DuplicateGroup> = confirmed
+        .map(|(size, mut paths)| {
+            paths.sort();
+            DuplicateGroup { size, paths }
+        })
+        .collect();
+    groups.sort_by_key(|g| std::cmp::Reverse(g.wasted()));
+    groups
+}
+
+/// Report duplicate groups ordered by wasted space.
+pub fn analyze(report: &mut DiagnosticReport, root: &Path, groups: &[DuplicateGroup], limit: usize) {
+    let wasted: u64 = groups.iter().map(DuplicateGroup::wasted).sum();
+    report.add_metric(Metric {
+        name: "Duplicate groups".into(),
+        value: MetricValue::Integer(groups.len() as i64),
+        unit: None,
+        threshold: None,
+    });
+    report.add_metric(Metric {
+        name: "Reclaimable by removing duplicates".into(),
+        value: MetricValue::Text(format_bytes(wasted)),
+        unit: None,
+        threshold: None,
+    });
+
+    for group in groups.iter().take(limit) {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "duplicates".into(),
+            message: format!(
+                "{} copies of {} ({} wasted)",
+                group.paths.len(),
+                format_bytes(group.size),
+                format_bytes(group.wasted())
+            ),
+            details: Some(
+                group
+                    .paths
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        });
+    }
+
+    if wasted > 0 {
+        report.add_recommendation(Recommendation {
+            priority: 3,
+            action: "Remove redundant copies or replace them with hardlinks".into(),
+            command: Some(format!(
+                "{}rdfind -dryrun true {}",
+                if command_exists("rdfind") { "" } else { "sudo apt install rdfind && " },
+                shell_quote(&root.to_string_lossy())
+            )),
+            explanation: "Identical files only need to be stored once; keep one copy per group. \
+                          rdfind is packaged as rdfind on apt, dnf and pacman."
+                .into(),
+        });
+    }
+}
@@ -0,0 +1,318 @@
+//! Usage history: growth rate and time-to-full per filesystem, directory growth between scans.
+//!
+//! Samples and snapshots live under `$RUSTWHY_STATE_DIR`, `$XDG_STATE_HOME/rustwhy` or
+//! `~/.local/state/rustwhy`. They are only written with `rustwhy disk --record`; other runs
+//! read what earlier recordings left behind.
+
+use super::capacity::FsUsage;
+use super::tree::DirNode;
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use crate::utils::{format_bytes, format_duration, shell_quote};
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const USAGE_FILE: &str = "disk-usage.json";
+const SNAPSHOT_DIR: &str = "disk-snapshots";
+/// Samples older than this are dropped.
+const KEEP_DAYS: i64 = 90;
+/// Only the most recent samples feed the growth fit, so old trends don't mask new ones.
+const FIT_DAYS: i64 = 14;
+/// Do not record more than one sample per filesystem in this window.
+const MIN_SAMPLE_GAP_MINUTES: i64 = 10;
+/// The fit needs at least this much history to be meaningful.
+const MIN_FIT_SPAN_SECS: f64 = 3600.0;
+/// A child explaining this share of its parent's growth hides the parent.
+const EXPLAINED_SHARE: f64 = 0.9;
+
+/// One usage observation of a filesystem.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageSample {
+    pub timestamp: DateTime<Utc>,
+    pub mountpoint: PathBuf,
+    pub used: u64,
+    pub total: u64,
+}
+
+/// Directory sizes of one scan, keyed by path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirSnapshot {
+    pub root: PathBuf,
+    pub timestamp: DateTime<Utc>,
+    pub depth: usize,
+    pub dirs: BTreeMap<PathBuf, u64>,
+}
+
+/// Resolve the state directory, preferring an explicit override.
+pub fn state_dir(explicit: Option<&str>) -> Option<PathBuf> {
+    if let Some(dir) = explicit.filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = std::env::var_os("RUSTWHY_STATE_DIR").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = std::env::var_os("XDG_STATE_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("rustwhy"));
+    }
+    std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state/rustwhy"))
+}
+
+fn load_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Option<T> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Write-then-rename so an interrupted run never leaves a truncated file behind.
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec(value)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Recorded history plus the current usage samples, old ones pruned. Nothing is written.
+pub fn usage_with_current(dir: &Path, filesystems: &[FsUsage], now: DateTime<Utc>) -> Vec<UsageSample> {
+    let mut samples: Vec<UsageSample> = load_json(&dir.join(USAGE_FILE)).unwrap_or_default();
+    samples.retain(|s| now - s.timestamp < ChronoDuration::days(KEEP_DAYS));
+    for fs in filesystems {
+        let recent = samples.iter().any(|s| {
+            s.mountpoint == fs.mount.mountpoint
+                && now - s.timestamp < ChronoDuration::minutes(MIN_SAMPLE_GAP_MINUTES)
+        });
+        if !recent {
+            samples.push(UsageSample {
+                timestamp: now,
+                mountpoint: fs.mount.mountpoint.clone(),
+                used: fs.used,
+                total: fs.total,
+            });
+        }
+    }
+    samples
+}
+
+/// Store the usage history returned by [`usage_with_current`].
+pub fn save_usage(dir: &Path, samples: &[UsageSample]) {
+    // History is best effort; a read-only home must not break the report.
+    let _ = save_json(&dir.join(USAGE_FILE), &samples);
+}
+
+/// Least-squares slope of used bytes over time, in bytes per second.
+pub fn growth_rate(samples: &[&UsageSample]) -> Option<f64> {
+    let first = samples.iter().map(|s| s.timestamp).min()?;
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|s| ((s.timestamp - first).num_milliseconds() as f64 / 1000.0, s.used as f64))
+        .collect();
+    let span = points.iter().map(|p| p.0).fold(0.0, f64::max);
+    if points.len() < 2 || span < MIN_FIT_SPAN_SECS {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_t = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_u = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let cov: f64 = points.iter().map(|p| (p.0 - mean_t) * (p.1 - mean_u)).sum();
+    let var: f64 = points.iter().map(|p| (p.0 - mean_t).powi(2)).sum();
+    (var > 0.0).then(|| cov / var)
+}
+
+/// Report growth rate and estimated time until each filesystem is full.
+pub fn analyze_growth(
+    report: &mut DiagnosticReport,
+    filesystems: &[FsUsage],
+    samples: &[UsageSample],
+    now: DateTime<Utc>,
+) {
+    for fs in filesystems.iter().filter(|fs| !fs.mount.is_read_only()) {
+        let mp = fs.mount.mountpoint.display().to_string();
+        let window: Vec<&UsageSample> = samples
+            .iter()
+            .filter(|s| s.mountpoint == fs.mount.mountpoint)
+            .filter(|s| now - s.timestamp < ChronoDuration::days(FIT_DAYS))
+            .collect();
+        let Some(rate) = growth_rate(&window) else {
+            continue;
+        };
+        let per_day = rate * 86_400.0;
+        report.add_metric(Metric {
+            name: format!("{} growth", mp),
+            value: MetricValue::Text(format!(
+                "{}{}/day",
+                if per_day < 0.0 { "-" } else { "+" },
+                format_bytes(per_day.abs() as u64)
+            )),
+            unit: None,
+            threshold: None,
+        });
+        if rate <= 0.0 {
+            continue;
+        }
+        let secs_left = fs.avail as f64 / rate;
+        let days_left = secs_left / 86_400.0;
+        report.add_metric(Metric {
+            name: format!("{} full in", mp),
+            value: MetricValue::Float(days_left),
+            unit: Some(" days".into()),
+            threshold: None,
+        });
+        let severity = if days_left < 1.0 {
+            Severity::Critical
+        } else if days_left < 7.0 {
+            Severity::Warning
+        } else if days_left < 30.0 {
+            Severity::Info
+        } else {
+            continue;
+        };
+        report.add_finding(Finding {
+            severity,
+            category: "growth".into(),
+            message: format!(
+                "{} fills up in about {} at {}/day",
+                mp,
+                format_duration(Duration::from_secs(secs_left.round() as u64)),
+                format_bytes(per_day as u64)
+            ),
+            details: Some(format!(
+                "Linear fit over {} samples from the last {} days; {} still available.",
+                window.len(),
+                FIT_DAYS,
+                format_bytes(fs.avail)
+            )),
+        });
+        report.add_recommendation(Recommendation {
+            priority: if severity == Severity::Critical { 1 } else { 2 },
+            action: format!("Find which directories on {} keep growing", mp),
+            command: Some(format!("rustwhy disk {} -x --depth 3 --record", shell_quote(&mp))),
+            explanation: "Repeated recorded scans of the same path show the directories that grew the most."
+                .into(),
+        });
+    }
+}
+
+fn snapshot_path(dir: &Path, root: &Path) -> PathBuf {
+    let key = blake3::hash(root.as_os_str().as_bytes()).to_hex();
+    dir.join(SNAPSHOT_DIR).join(format!("{}.json", &key[..16]))
+}
+
+fn flatten(node: &DirNode, out: &mut BTreeMap<PathBuf, u64>) {
+    out.insert(node.path.clone(), node.size);
+    for child in &node.children {
+        flatten(child, out);
+    }
+}
+
+/// The last recorded snapshot of `root`.
+pub fn load_snapshot(dir: &Path, root: &Path) -> Option<DirSnapshot> {
+    load_json(&snapshot_path(dir, root)).filter(|s: &DirSnapshot| s.root == root)
+}
+
+/// Store the directory sizes of this scan, replacing the previous snapshot of the same root.
+pub fn save_snapshot(dir: &Path, tree: &DirNode, depth: usize, now: DateTime<Utc>) {
+    let mut dirs = BTreeMap::new();
+    flatten(tree, &mut dirs);
+    let current = DirSnapshot {
+        root: tree.path.clone(),
+        timestamp: now,
+        depth,
+        dirs,
+    };
+    let _ = save_json(&snapshot_path(dir, &tree.path), &current);
+    prune_snapshots(&dir.join(SNAPSHOT_DIR), now);
+}
+
+/// Drop snapshots of paths that were not scanned again within the retention window.
+fn prune_snapshots(dir: &Path, now: DateTime<Utc>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let modified = entry.metadata().and_then(|m| m.modified()).map(DateTime::<Utc>::from);
+        if modified.is_ok_and(|m| now - m > ChronoDuration::days(KEEP_DAYS)) {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Directories that grew the most since `previous`, hiding parents explained by one child.
+pub fn top_growth(previous: &DirSnapshot, tree: &DirNode, depth: usize) -> Vec<(PathBuf, u64, u64)> {
+    let mut current = BTreeMap::new();
+    flatten(tree, &mut current);
+    let root_depth = tree.path.components().count();
+    let max_depth = depth.min(previous.depth);
+
+    let mut grown: BTreeMap<&Path, (u64, u64)> = BTreeMap::new();
+    for (path, &now_size) in &current {
+        if path.components().count() - root_depth > max_depth || *path == tree.path {
+            continue;
+        }
+        let before = previous.dirs.get(path).copied().unwrap_or(0);
+        if now_size > before {
+            grown.insert(path.as_path(), (before, now_size));
+        }
+    }
+    let delta = |(before, now): (u64, u64)| (now - before) as f64;
+    let explained: Vec<&Path> = grown
+        .iter()
+        .filter_map(|(path, sizes)| {
+            let parent = path.parent()?;
+            let parent_sizes = grown.get(parent)?;
+            (delta(*sizes) >= EXPLAINED_SHARE * delta(*parent_sizes)).then_some(parent)
+        })
+        .collect();
+
+    let mut out: Vec<(PathBuf, u64, u64)> = grown
+        .into_iter()
+        .filter(|(path, _)| !explained.contains(path))
+        .map(|(path, (before, now))| (path.to_path_buf(), before, now))
+        .collect();
+    out.sort_by_key(|(_, before, now)| std::cmp::Reverse(now - before));
+    out
+}
+
+/// Report the directories that grew the most between the previous and the current scan.
+pub fn analyze_dir_growth(
+    report: &mut DiagnosticReport,
+    previous: &DirSnapshot,
+    tree: &DirNode,
+    depth: usize,
+    limit: usize,
+) {
+    let growth = top_growth(previous, tree, depth);
+    if growth.is_empty() {
+        return;
+    }
+    let lines: Vec<String> = growth
+        .iter()
+        .take(limit)
+        .map(|(path, before, now)| {
+            format!(
+                "{}  +{} ({} -> {})",
+                path.display(),
+                format_bytes(now - before),
+                format_bytes(*before),
+                format_bytes(*now)
+            )
+        })
+        .collect();
+    let (top_path, before, now) = &growth[0];
+    report.add_finding(Finding {
+        severity: Severity::Info,
+        category: "growth".into(),
+        message: format!(
+            "{} grew the most since the scan on {} (+{})",
+            top_path.display(),
+            previous.timestamp.format("%Y-%m-%d %H:%M UTC"),
+            format_bytes(now - before)
+        ),
+        details: Some(lines.join("\n")),
+    });
+}
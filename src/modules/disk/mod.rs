@@ -0,0 +1,204 @@
+//! Disk space analysis (diskwhy) - filesystem capacity, snapshots and thin pools,
+//! recursive directory sizes, large/old files.
+
+mod capacity;
+mod cleanup;
+mod deleted;
+mod duplicates;
+mod history;
+mod tree;
+pub mod volumes;
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use crate::core::traits::{DiagnosticModule, ModuleConfig};
+use crate::utils::{format_bytes, mount_for_path, parse_size_human, read_mounts};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tree::{ScanFilter, ScanOptions};
+
+pub fn module() -> Arc<dyn DiagnosticModule> {
+    Arc::new(DiskModule)
+}
+
+struct DiskModule;
+
+#[async_trait]
+impl DiagnosticModule for DiskModule {
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+
+    fn description(&self) -> &'static str {
+        "Analyze disk space usage and find large or old files"
+    }
+
+    async fn run(&self, config: &ModuleConfig) -> Result<DiagnosticReport> {
+        let path_str = config.extra_args.get("path").map(String::as_str).unwrap_or("/");
+        let path = Path::new(path_str);
+        let depth: usize = config
+            .extra_args
+            .get("depth")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3);
+        let _older_than_days: Option<u64> = config.extra_args.get("old").and_then(|s| s.parse().ok());
+        let larger_than_bytes: Option<u64> = config.extra_args.get("large").and_then(|s| parse_size_human(s));
+        let include_hidden = config.extra_args.get("hidden").map(|s| s == "true").unwrap_or(false);
+        let filter = ScanFilter {
+            include_hidden,
+            excludes: config
+                .extra_args
+                .get("exclude")
+                .map(|s| ScanFilter::parse_excludes(s))
+                .unwrap_or_default(),
+            one_file_system: config
+                .extra_args
+                .get("one_file_system")
+                .is_some_and(|s| s == "true"),
+        };
+
+        let mut report = DiagnosticReport::new("disk", "Disk space analysis");
+
+        if !path.exists() {
+            report.add_finding(Finding {
+                severity: Severity::Critical,
+                category: "disk".into(),
+                message: format!("Path does not exist: {}", path.display()),
+                details: None,
+            });
+            return Ok(report);
+        }
+
+        let filesystems = capacity::real_filesystems();
+        capacity::analyze(&mut report, &filesystems);
+        volumes::analyze(&mut report, &filesystems, config.top_n);
+        let now = chrono::Utc::now();
+        let state_dir = history::state_dir(config.extra_args.get("state_dir").map(String::as_str));
+        // Writing state is opt-in; `rustwhy all` never records.
+        let record = config.extra_args.get("record").is_some_and(|s| s == "true");
+        if let Some(dir) = &state_dir {
+            let samples = history::usage_with_current(dir, &filesystems, now);
+            if record {
+                history::save_usage(dir, &samples);
+            }
+            history::analyze_growth(&mut report, &filesystems, &samples, now);
+        }
+        deleted::analyze(&mut report, &deleted::scan(), config.top_n);
+
+        // `rustwhy all` passes no path; there the capacity check is enough and a full
+        // tree scan of / would dominate the run time.
+        if !config.extra_args.contains_key("path") {
+            let full: Vec<PathBuf> = filesystems
+                .iter()
+                .filter(|fs| fs.is_nearly_full())
+                .map(|fs| fs.mount.mountpoint.clone())
+                .collect();
+            if !full.is_empty() {
+                let mounts = read_mounts().unwrap_or_default();
+                let targets = tokio::task::spawn_blocking(move || {
+                    cleanup::classify(
+                        |p| mount_for_path(&mounts, p).is_some_and(|m| full.contains(&m.mountpoint)),
+                        &[],
+                    )
+                })
+                .await?;
+                cleanup::analyze(&mut report, &targets, config.top_n);
+            }
+            report.compute_overall_severity();
+            return Ok(report);
+        }
+
+        if config.extra_args.get("duplicates").is_some_and(|s| s == "true") {
+            let root = path.to_path_buf();
+            let min_size = larger_than_bytes.unwrap_or(0);
+            let groups =
+                tokio::task::spawn_blocking(move || duplicates::find(&root, &filter, min_size)).await?;
+            duplicates::analyze(&mut report, path, &groups, config.top_n);
+            report.compute_overall_severity();
+            return Ok(report);
+        }
+
+        // Directory walks block; keep them off the async runtime's worker threads.
+        let opts = ScanOptions {
+            max_depth: depth,
+            filter,
+            large_threshold: larger_than_bytes,
+            large_limit: config.top_n,
+            progress: !config.json_output,
+        };
+        let root = path.to_path_buf();
+        let scan = tokio::task::spawn_blocking(move || tree::scan(&root, &opts)).await?;
+        let total_size = scan.total;
+
+        report.add_metric(Metric {
+            name: "Path analyzed".into(),
+            value: MetricValue::Text(path.display().to_string()),
+            unit: None,
+            threshold: None,
+        });
+        report.add_metric(Metric {
+            name: "Total size (allocated)".into(),
+            value: MetricValue::Text(format_bytes(total_size)),
+            unit: None,
+            threshold: None,
+        });
+        report.add_metric(Metric {
+            name: "Files scanned".into(),
+            value: MetricValue::Integer(scan.files as i64),
+            unit: None,
+            threshold: None,
+        });
+
+        for (fp, size) in scan.large_files {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "file".into(),
+                message: format!("{} – {}", fp.display(), format_bytes(size)),
+                details: Some("Consider moving or compressing.".into()),
+            });
+        }
+
+        if let Some(dir) = &state_dir {
+            if let Some(previous) = history::load_snapshot(dir, &scan.root.path) {
+                history::analyze_dir_growth(&mut report, &previous, &scan.root, depth, config.top_n);
+            }
+            if record {
+                history::save_snapshot(dir, &scan.root, depth, now);
+            }
+        }
+
+        // Hide directories under 1% of the total so the tree stays readable.
+        let mut dir_tree = scan.root;
+        dir_tree.truncate(config.top_n, total_size / 100);
+        if !dir_tree.children.is_empty() {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "directory".into(),
+                message: format!("Directory tree of {} (depth {})", path.display(), depth),
+                details: Some(dir_tree.render()),
+            });
+        }
+        report.raw_data = Some(serde_json::json!({ "tree": dir_tree }));
+
+        let root = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let cargo_targets = scan.cargo_targets;
+        let targets =
+            tokio::task::spawn_blocking(move || cleanup::classify(|p| p.starts_with(&root), &cargo_targets))
+                .await?;
+        cleanup::analyze(&mut report, &targets, config.top_n);
+
+        if total_size > 50 * 1024 * 1024 * 1024 {
+            report.add_recommendation(Recommendation {
+                priority: 2,
+                action: "Review large directories (e.g. /var/log, caches) and clean old data.".into(),
+                command: Some("du -sh /var/log/* 2>/dev/null | sort -hr | head -10".into()),
+                explanation: "Logs and caches often consume significant space.".into(),
+            });
+        }
+
+        report.compute_overall_severity();
+        Ok(report)
+    }
+}
@@ -0,0 +1,474 @@
+//! Recursive du-like directory size aggregation.
+//!
+//! The whole tree under the root is always scanned so that sizes roll up through every
+//! ancestor; only the *retained* tree is limited to the display depth. Directories are read
+//! by a pool of worker threads. Each directory is a small record in a flat arena (parent
+//! index + size), so memory grows with the number of directories, not files. Large files
+//! are kept in a bounded top-N heap. Pseudo filesystems mounted below the root (/proc, /sys,
+//! ...) are never entered.
+
+use crate::utils::mounts::PSEUDO_FILESYSTEMS;
+use crate::utils::format_bytes;
+use glob::Pattern;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::ffi::OsStr;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// Upper bound on scanner threads; more mostly adds contention on local disks.
+const MAX_WORKERS: usize = 16;
+
+/// A directory with its recursive allocated size.
+#[derive(Debug, Clone, Serialize)]
+pub struct DirNode {
+    pub path: PathBuf,
+    pub size: u64,
+    pub children: Vec<DirNode>,
+}
+
+/// Result of a full scan of a directory tree.
+#[derive(Debug)]
+pub struct ScanResult {
+    /// Directory tree down to the requested depth.
+    pub root: DirNode,
+    /// Total allocated bytes under the root (hardlinks counted once).
+    pub total: u64,
+    /// Regular files seen.
+    pub files: u64,
+    /// Largest files at or above the `large` threshold, biggest first.
+    pub large_files: Vec<(PathBuf, u64)>,
+    /// Cargo `target/` directories (next to a `Cargo.toml`) with their sizes.
+    pub cargo_targets: Vec<(PathBuf, u64)>,
+}
+
+/// Which entries a scan skips.
+#[derive(Debug, Clone, Default)]
+pub struct ScanFilter {
+    pub include_hidden: bool,
+    /// Glob patterns; patterns containing `/` match the full path, others the file name.
+    pub excludes: Vec<Pattern>,
+    /// Do not descend into other filesystems (like `du -x`).
+    pub one_file_system: bool,
+}
+
+impl ScanFilter {
+    /// Parse newline-separated glob patterns, ignoring invalid ones.
+    pub fn parse_excludes(patterns: &str) -> Vec<Pattern> {
+        patterns
+            .lines()
+            .filter(|p| !p.trim().is_empty())
+            .filter_map(|p| Pattern::new(p.trim()).ok())
+            .collect()
+    }
+
+    /// True if the entry (never the scan root itself) should be skipped.
+    pub fn is_excluded(&self, path: &Path, name: &OsStr) -> bool {
+        let name = name.to_string_lossy();
+        if !self.include_hidden && name.starts_with('.') {
+            return true;
+        }
+        self.excludes.iter().any(|p| {
+            if p.as_str().contains('/') {
+                p.matches_path(path)
+            } else {
+                p.matches(&name)
+            }
+        })
+    }
+}
+
+/// Options controlling a tree scan.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    pub max_depth: usize,
+    pub filter: ScanFilter,
+    pub large_threshold: Option<u64>,
+    /// How many of the largest files to keep.
+    pub large_limit: usize,
+    /// Show a spinner with scan progress on stderr.
+    pub progress: bool,
+}
+
+/// Keeps the `limit` largest entries seen so far.
+struct TopN {
+    limit: usize,
+    heap: BinaryHeap<Reverse<(u64, PathBuf)>>,
+}
+
+impl TopN {
+    fn new(limit: usize) -> Self {
+        Self { limit, heap: BinaryHeap::new() }
+    }
+
+    fn push(&mut self, size: u64, path: PathBuf) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.heap.len() < self.limit {
+            self.heap.push(Reverse((size, path)));
+        } else if self.heap.peek().is_some_and(|Reverse((min, _))| size > *min) {
+            self.heap.pop();
+            self.heap.push(Reverse((size, path)));
+        }
+    }
+
+    fn merge(&mut self, other: TopN) {
+        for Reverse((size, path)) in other.heap {
+            self.push(size, path);
+        }
+    }
+
+    fn into_sorted(self) -> Vec<(PathBuf, u64)> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((size, path))| (path, size))
+            .collect()
+    }
+}
+
+/// One directory in the arena. Parents always have a lower index than their children.
+struct DirRecord {
+    parent: usize,
+    depth: usize,
+    size: u64,
+    /// Only kept for directories within the display depth and Cargo target dirs.
+    path: Option<PathBuf>,
+    cargo_target: bool,
+}
+
+struct Job {
+    index: usize,
+    path: PathBuf,
+    depth: usize,
+}
+
+/// Shared state of the worker pool.
+struct Shared<'a> {
+    opts: &'a ScanOptions,
+    root_dev: u64,
+    /// Devices of /proc, /sys and other pseudo filesystems, never descended into.
+    pseudo_devs: HashSet<u64>,
+    queue: Mutex<(Vec<Job>, usize)>,
+    ready: Condvar,
+    arena: Mutex<Vec<DirRecord>>,
+    seen_inodes: Mutex<HashSet<(u64, u64)>>,
+    files: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl Shared<'_> {
+    /// Take the next directory, or `None` once the queue is empty and no worker is busy.
+    fn next_job(&self) -> Option<Job> {
+        let mut guard = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(job) = guard.0.pop() {
+                guard.1 += 1;
+                return Some(job);
+            }
+            if guard.1 == 0 {
+                self.ready.notify_all();
+                return None;
+            }
+            guard = self.ready.wait(guard).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn finish_job(&self, new_jobs: Vec<Job>) {
+        let mut guard = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        guard.0.extend(new_jobs);
+        guard.1 -= 1;
+        self.ready.notify_all();
+    }
+
+    /// Read one directory: sum its files, register its subdirectories as new jobs.
+    fn process(&self, job: Job, large: &mut TopN) -> Vec<Job> {
+        let entries = match std::fs::read_dir(&job.path) {
+            Ok(e) => e,
+            Err(_) => return Vec::new(),
+        };
+        let mut local_size = 0u64;
+        let mut local_files = 0u64;
+        let mut subdirs: Vec<(PathBuf, u64)> = Vec::new();
+        let mut has_manifest = false;
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            if self.opts.filter.is_excluded(&path, &name) {
+                continue;
+            }
+            // DirEntry::metadata does not follow symlinks.
+            let meta = match entry.metadata() {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+            if meta.is_dir() {
+                if meta.dev() != self.root_dev
+                    && (self.opts.filter.one_file_system || self.pseudo_devs.contains(&meta.dev()))
+                {
+                    continue;
+                }
+                subdirs.push((path, allocated_size(&meta)));
+                continue;
+            }
+            if name == "Cargo.toml" {
+                has_manifest = true;
+            }
+            if meta.nlink() > 1 {
+                let mut seen = self.seen_inodes.lock().unwrap_or_else(|e| e.into_inner());
+                if !seen.insert((meta.dev(), meta.ino())) {
+                    continue;
+                }
+            }
+            let size = allocated_size(&meta);
+            local_size = local_size.saturating_add(size);
+            if meta.is_file() {
+                local_files += 1;
+                if self.opts.large_threshold.is_some_and(|min| size >= min) {
+                    large.push(size, path);
+                }
+            }
+        }
+
+        let dir_bytes: u64 = subdirs.iter().map(|(_, s)| s).sum();
+        self.files.fetch_add(local_files, Ordering::Relaxed);
+        self.bytes.fetch_add(local_size + dir_bytes, Ordering::Relaxed);
+
+        let depth = job.depth + 1;
+        let mut arena = self.arena.lock().unwrap_or_else(|e| e.into_inner());
+        arena[job.index].size += local_size;
+        subdirs
+            .into_iter()
+            .map(|(path, size)| {
+                let cargo_target = has_manifest && path.file_name().is_some_and(|n| n == "target");
+                let keep_path = depth <= self.opts.max_depth || cargo_target;
+                arena.push(DirRecord {
+                    parent: job.index,
+                    depth,
+                    size,
+                    path: keep_path.then(|| path.clone()),
+                    cargo_target,
+                });
+                Job { index: arena.len() - 1, path, depth }
+            })
+            .collect()
+    }
+}
+
+/// Allocated size on disk (`st_blocks` is always in 512-byte units).
+fn allocated_size(meta: &std::fs::Metadata) -> u64 {
+    meta.blocks().saturating_mul(512)
+}
+
+/// Device numbers of pseudo filesystem mounts, from the `maj:min` field of
+/// /proc/self/mountinfo so that the mountpoints themselves are never touched.
+fn pseudo_devices() -> HashSet<u64> {
+    std::fs::read_to_string("/proc/self/mountinfo")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let sep = fields.iter().position(|f| *f == "-")?;
+            if !PSEUDO_FILESYSTEMS.contains(fields.get(sep + 1)?) {
+                return None;
+            }
+            let (major, minor) = fields.get(2)?.split_once(':')?;
+            Some(nix::sys::stat::makedev(major.parse().ok()?, minor.parse().ok()?))
+        })
+        .collect()
+}
+
+fn worker_count() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .clamp(2, MAX_WORKERS)
+}
+
+fn spinner(enabled: bool) -> ProgressBar {
+    if !enabled {
+        return ProgressBar::hidden();
+    }
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::with_template("{spinner} Scanning: {msg}").unwrap_or_else(|_| ProgressStyle::default_spinner()),
+    );
+    pb
+}
+
+/// Scan `root` recursively, rolling file sizes up into every ancestor directory.
+pub fn scan(root: &Path, opts: &ScanOptions) -> ScanResult {
+    let root_meta = match std::fs::symlink_metadata(root) {
+        Ok(m) => m,
+        Err(_) => return empty_result(root, 0, 0),
+    };
+    if !root_meta.is_dir() {
+        let size = allocated_size(&root_meta);
+        return empty_result(root, size, u64::from(root_meta.is_file()));
+    }
+
+    let shared = Shared {
+        opts,
+        root_dev: root_meta.dev(),
+        pseudo_devs: pseudo_devices(),
+        queue: Mutex::new((
+            vec![Job { index: 0, path: root.to_path_buf(), depth: 0 }],
+            0,
+        )),
+        ready: Condvar::new(),
+        arena: Mutex::new(vec![DirRecord {
+            parent: 0,
+            depth: 0,
+            size: allocated_size(&root_meta),
+            path: Some(root.to_path_buf()),
+            cargo_target: false,
+        }]),
+        seen_inodes: Mutex::new(HashSet::new()),
+        files: AtomicU64::new(0),
+        bytes: AtomicU64::new(allocated_size(&root_meta)),
+    };
+
+    let pb = spinner(opts.progress);
+    let done = AtomicBool::new(false);
+    let mut large = TopN::new(opts.large_limit);
+    std::thread::scope(|s| {
+        let workers: Vec<_> = (0..worker_count())
+            .map(|_| {
+                s.spawn(|| {
+                    let mut local = TopN::new(opts.large_limit);
+                    while let Some(job) = shared.next_job() {
+                        let new_jobs = shared.process(job, &mut local);
+                        shared.finish_job(new_jobs);
+                    }
+                    local
+                })
+            })
+            .collect();
+        s.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                pb.set_message(format!(
+                    "{} files, {}",
+                    shared.files.load(Ordering::Relaxed),
+                    format_bytes(shared.bytes.load(Ordering::Relaxed))
+                ));
+                pb.tick();
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        });
+        for w in workers {
+            if let Ok(local) = w.join() {
+                large.merge(local);
+            }
+        }
+        done.store(true, Ordering::Relaxed);
+    });
+    pb.finish_and_clear();
+
+    let mut arena = shared.arena.into_inner().unwrap_or_else(|e| e.into_inner());
+    // Children always come after their parent, so one reverse pass rolls sizes up.
+    for i in (1..arena.len()).rev() {
+        let (size, parent) = (arena[i].size, arena[i].parent);
+        arena[parent].size = arena[parent].size.saturating_add(size);
+    }
+    let cargo_targets = arena
+        .iter()
+        .filter(|r| r.cargo_target)
+        .filter_map(|r| Some((r.path.clone()?, r.size)))
+        .collect();
+
+    ScanResult {
+        root: build_tree(&arena, opts.max_depth),
+        total: arena[0].size,
+        files: shared.files.into_inner(),
+        large_files: large.into_sorted(),
+        cargo_targets,
+    }
+}
+
+fn empty_result(root: &Path, size: u64, files: u64) -> ScanResult {
+    ScanResult {
+        root: DirNode { path: root.to_path_buf(), size, children: Vec::new() },
+        total: size,
+        files,
+        large_files: Vec::new(),
+        cargo_targets: Vec::new(),
+    }
+}
+
+/// Turn the retained part of the arena (depth <= `max_depth`) into a sorted tree.
+fn build_tree(arena: &[DirRecord], max_depth: usize) -> DirNode {
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); arena.len()];
+    for (i, r) in arena.iter().enumerate().skip(1) {
+        if r.depth <= max_depth {
+            children[r.parent].push(i);
+        }
+    }
+    fn node(arena: &[DirRecord], children: &[Vec<usize>], i: usize) -> DirNode {
+        let mut kids: Vec<DirNode> = children[i].iter().map(|&c| node(arena, children, c)).collect();
+        kids.sort_by_key(|c| Reverse(c.size));
+        DirNode {
+            path: arena[i].path.clone().unwrap_or_default(),
+            size: arena[i].size,
+            children: kids,
+        }
+    }
+    node(arena, &children, 0)
+}
+
+/// Total allocated size under `path`, counting hardlinks once and including hidden files.
+pub fn dir_size(path: &Path) -> u64 {
+    scan(
+        path,
+        &ScanOptions {
+            max_depth: 0,
+            filter: ScanFilter { include_hidden: true, ..ScanFilter::default() },
+            large_threshold: None,
+            large_limit: 0,
+            progress: false,
+        },
+    )
+    .total
+}
+
+impl DirNode {
+    /// Keep at most `max_children` children per directory and drop entries below `min_size`.
+    pub fn truncate(&mut self, max_children: usize, min_size: u64) {
+        self.children.retain(|c| c.size >= min_size);
+        self.children.truncate(max_children);
+        for child in &mut self.children {
+            child.truncate(max_children, min_size);
+        }
+    }
+
+    /// Render the tree with box-drawing connectors, one directory per line.
+    pub fn render(&self) -> String {
+        let mut out = format!("{}  {}", self.path.display(), format_bytes(self.size));
+        render_children(self, "", &mut out);
+        out
+    }
+}
+
+fn render_children(node: &DirNode, prefix: &str, out: &mut String) {
+    let count = node.children.len();
+    for (i, child) in node.children.iter().enumerate() {
+        let last = i + 1 == count;
+        let name = child
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| child.path.display().to_string());
+        out.push('\n');
+        out.push_str(prefix);
+        out.push_str(if last { "└── " } else { "├── " });
+        out.push_str(&format!("{}  {}", name, format_bytes(child.size)));
+        let next_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+        render_children(child, &next_prefix, out);
+    }
+}
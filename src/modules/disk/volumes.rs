@@ -0,0 +1,569 @@
+//! Copy-on-write and thin-provisioned storage: btrfs allocation and snapshots, ZFS pools and
+//! snapshots, LVM thin pools. Plain file sizes can't see space held by any of these.
+
+use super::capacity::FsUsage;
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation, Threshold};
+use crate::core::severity::Severity;
+use crate::utils::{command_exists, format_bytes, is_root, read_first_line, run_cmd, shell_quote};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Pool/metadata usage (percent) that triggers a warning.
+const POOL_WARNING_PCT: f64 = 80.0;
+/// Pool/metadata usage (percent) that triggers a critical finding.
+const POOL_CRITICAL_PCT: f64 = 95.0;
+/// btrfs needs some unallocated space to create new metadata chunks.
+const BTRFS_MIN_UNALLOCATED: u64 = 1024 * 1024 * 1024;
+/// Snapshot space below this is not worth a finding.
+const SNAPSHOT_REPORT_BYTES: u64 = 1024 * 1024 * 1024;
+/// Snapshot space above this share of the filesystem is a warning.
+const SNAPSHOT_WARNING_SHARE: f64 = 0.2;
+
+/// Chunk allocation of one btrfs block group type (data, metadata, system).
+#[derive(Debug, Clone, Default)]
+pub struct BtrfsAllocation {
+    /// Bytes of chunks allocated on disk (including RAID copies).
+    pub disk_total: u64,
+    /// Logical bytes available in the allocated chunks.
+    pub total: u64,
+    /// Logical bytes used in the allocated chunks.
+    pub used: u64,
+}
+
+impl BtrfsAllocation {
+    pub fn used_pct(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.used as f64 / self.total as f64 * 100.0
+        }
+    }
+}
+
+/// One btrfs snapshot with its exclusive size (needs quotas).
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub id: u64,
+    /// Path relative to the top-level subvolume (id 5), not to the mountpoint.
+    pub name: String,
+    /// Bytes only referenced by this snapshot, freed when it is deleted.
+    pub exclusive: Option<u64>,
+}
+
+/// One row of `zpool list -Hp`.
+#[derive(Debug, Clone)]
+pub struct ZfsPool {
+    pub name: String,
+    pub size: u64,
+    pub alloc: u64,
+    pub cap: f64,
+    pub frag: Option<f64>,
+}
+
+/// One row of `zfs list -Hp` for filesystems and volumes.
+#[derive(Debug, Clone)]
+pub struct ZfsDataset {
+    pub name: String,
+    pub used: u64,
+    pub avail: u64,
+    pub used_by_snapshots: u64,
+}
+
+/// One row of `lvs`; thin pools have `pool_lv` empty and an attribute starting with 't'.
+#[derive(Debug, Clone)]
+pub struct LogicalVolume {
+    pub vg: String,
+    pub name: String,
+    pub attr: String,
+    pub size: u64,
+    pub data_pct: Option<f64>,
+    pub metadata_pct: Option<f64>,
+    pub pool: String,
+}
+
+impl LogicalVolume {
+    pub fn is_thin_pool(&self) -> bool {
+        self.attr.starts_with('t')
+    }
+}
+
+/// Add btrfs, ZFS and LVM thin findings for whatever is present on this system.
+pub fn analyze(report: &mut DiagnosticReport, filesystems: &[FsUsage], limit: usize) {
+    for fs in filesystems.iter().filter(|fs| fs.mount.fstype == "btrfs") {
+        analyze_btrfs(report, fs, limit);
+    }
+    if filesystems.iter().any(|fs| fs.mount.fstype == "zfs") && command_exists("zfs") {
+        analyze_zfs(report, limit);
+    }
+    if command_exists("lvs") {
+        analyze_lvm_thin(report);
+    }
+}
+
+/// Find the /sys/fs/btrfs/<uuid> directory that owns `device`.
+fn btrfs_sysfs_dir(device: &str) -> Option<PathBuf> {
+    let resolved = std::fs::canonicalize(device).unwrap_or_else(|_| PathBuf::from(device));
+    let name = resolved.file_name()?;
+    std::fs::read_dir("/sys/fs/btrfs")
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .find(|dir| dir.join("devices").join(name).exists())
+}
+
+fn read_u64(path: &Path) -> u64 {
+    read_first_line(path)
+        .ok()
+        .flatten()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn btrfs_allocation(dir: &Path, kind: &str) -> BtrfsAllocation {
+    let base = dir.join("allocation").join(kind);
+    BtrfsAllocation {
+        disk_total: read_u64(&base.join("disk_total")),
+        total: read_u64(&base.join("total_bytes")),
+        used: read_u64(&base.join("bytes_used")),
+    }
+}
+
+/// Sum of member device sizes (sysfs `size` is in 512-byte sectors).
+fn btrfs_device_bytes(dir: &Path) -> u64 {
+    std::fs::read_dir(dir.join("devices"))
+        .map(|entries| entries.flatten().map(|e| read_u64(&e.path().join("size")) * 512).sum())
+        .unwrap_or(0)
+}
+
+/// Parse `btrfs subvolume list -s` into (subvolume id, path).
+pub fn parse_btrfs_snapshot_list(output: &str) -> Vec<(u64, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let id = words
+                .by_ref()
+                .skip_while(|w| *w != "ID")
+                .nth(1)?
+                .parse()
+                .ok()?;
+            let path = line.split_once(" path ")?.1.trim().to_string();
+            Some((id, path))
+        })
+        .collect()
+}
+
+/// Parse `btrfs qgroup show --raw` into subvolume id -> exclusive bytes (level 0 only).
+pub fn parse_btrfs_qgroups(output: &str) -> HashMap<u64, u64> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut cols = line.split_whitespace();
+            let id = cols.next()?.strip_prefix("0/")?.parse().ok()?;
+            let _referenced = cols.next()?;
+            let exclusive = cols.next()?.parse().ok()?;
+            Some((id, exclusive))
+        })
+        .collect()
+}
+
+fn btrfs_snapshots(mountpoint: &str) -> Vec<Snapshot> {
+    // Both commands need CAP_SYS_ADMIN.
+    if !is_root() || !command_exists("btrfs") {
+        return Vec::new();
+    }
+    let Ok(list) = run_cmd(&["btrfs", "subvolume", "list", "-s", mountpoint]) else {
+        return Vec::new();
+    };
+    // Fails when quotas are disabled; snapshots are still listed without sizes.
+    let exclusive = run_cmd(&["btrfs", "qgroup", "show", "--raw", mountpoint])
+        .map(|out| parse_btrfs_qgroups(&out))
+        .unwrap_or_default();
+    let mut snapshots: Vec<Snapshot> = parse_btrfs_snapshot_list(&list)
+        .into_iter()
+        .map(|(id, name)| Snapshot {
+            id,
+            name,
+            exclusive: exclusive.get(&id).copied(),
+        })
+        .collect();
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.exclusive.unwrap_or(0)));
+    snapshots
+}
+
+fn analyze_btrfs(report: &mut DiagnosticReport, fs: &FsUsage, limit: usize) {
+    let mp = fs.mount.mountpoint.display().to_string();
+    if let Some(dir) = btrfs_sysfs_dir(&fs.mount.device) {
+        let data = btrfs_allocation(&dir, "data");
+        let metadata = btrfs_allocation(&dir, "metadata");
+        let system = btrfs_allocation(&dir, "system");
+        let device_bytes = btrfs_device_bytes(&dir);
+        let unallocated =
+            device_bytes.saturating_sub(data.disk_total + metadata.disk_total + system.disk_total);
+
+        report.add_metric(Metric {
+            name: format!("{} btrfs unallocated", mp),
+            value: MetricValue::Text(format_bytes(unallocated)),
+            unit: None,
+            threshold: None,
+        });
+        report.add_metric(Metric {
+            name: format!("{} btrfs metadata used", mp),
+            value: MetricValue::Float(metadata.used_pct()),
+            unit: Some("%".into()),
+            threshold: Some(Threshold {
+                warning: POOL_WARNING_PCT,
+                critical: POOL_CRITICAL_PCT,
+            }),
+        });
+
+        if device_bytes > 0 && unallocated < BTRFS_MIN_UNALLOCATED && !fs.mount.is_read_only() {
+            let meta_pct = metadata.used_pct();
+            report.add_finding(Finding {
+                severity: if meta_pct >= POOL_CRITICAL_PCT {
+                    Severity::Critical
+                } else {
+                    Severity::Warning
+                },
+                category: "btrfs".into(),
+                message: format!(
+                    "{} has only {} unallocated; metadata chunks are {:.0}% full",
+                    mp,
+                    format_bytes(unallocated),
+                    meta_pct
+                ),
+                details: Some(format!(
+                    "Data chunks hold {} but only {} of it is used. Once metadata fills up, \
+                     writes fail with 'No space left on device' even though df shows free space.",
+                    format_bytes(data.total),
+                    format_bytes(data.used)
+                )),
+            });
+            report.add_recommendation(Recommendation {
+                priority: 1,
+                action: format!("Compact half-empty data chunks on {}", mp),
+                command: Some(format!("sudo btrfs balance start -dusage=50 {}", shell_quote(&mp))),
+                explanation: "A filtered balance rewrites mostly empty chunks and returns the \
+                              space to the unallocated pool so metadata can grow."
+                    .into(),
+            });
+        }
+    }
+
+    let snapshots = btrfs_snapshots(&mp);
+    if snapshots.is_empty() {
+        return;
+    }
+    let sized: Vec<&Snapshot> = snapshots.iter().filter(|s| s.exclusive.is_some()).collect();
+    if sized.is_empty() {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "snapshots".into(),
+            message: format!("{} has {} btrfs snapshot(s) of unknown size", mp, snapshots.len()),
+            details: Some(
+                "Snapshots keep deleted and overwritten data alive; quotas are needed to see \
+                 how much each one holds."
+                    .into(),
+            ),
+        });
+        report.add_recommendation(Recommendation {
+            priority: 4,
+            action: format!("Enable quotas on {} to measure snapshot usage", mp),
+            command: Some(format!("sudo btrfs quota enable {}", shell_quote(&mp))),
+            explanation: "Quota accounting adds some write overhead on busy filesystems.".into(),
+        });
+        return;
+    }
+    let held: u64 = sized.iter().filter_map(|s| s.exclusive).sum();
+    report.add_metric(Metric {
+        name: format!("{} held by snapshots", mp),
+        value: MetricValue::Text(format_bytes(held)),
+        unit: None,
+        threshold: None,
+    });
+    if held < SNAPSHOT_REPORT_BYTES {
+        return;
+    }
+    report.add_finding(Finding {
+        severity: snapshot_severity(held, fs.total),
+        category: "snapshots".into(),
+        message: format!(
+            "{} btrfs snapshot(s) on {} exclusively hold {}",
+            snapshots.len(),
+            mp,
+            format_bytes(held)
+        ),
+        details: Some(
+            sized
+                .iter()
+                .take(limit)
+                .map(|s| format!("ID {}  {}  {}", s.id, s.name, format_bytes(s.exclusive.unwrap_or(0))))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+    });
+    report.add_recommendation(Recommendation {
+        priority: 2,
+        action: format!("Delete old snapshots on {}", mp),
+        // Listed paths are relative to the top-level subvolume, which is often not what is
+        // mounted (subvol=@); the ID is unambiguous.
+        command: Some(format!("sudo btrfs subvolume delete -i {} {}", sized[0].id, shell_quote(&mp))),
+        explanation: format!(
+            "Deletes the largest snapshot ({}). Space is returned in the background once no other \
+             snapshot references it; snapper or timeshift users should prune through their tool instead.",
+            sized[0].name
+        ),
+    });
+}
+
+fn snapshot_severity(held: u64, total: u64) -> Severity {
+    if total > 0 && held as f64 / total as f64 >= SNAPSHOT_WARNING_SHARE {
+        Severity::Warning
+    } else {
+        Severity::Info
+    }
+}
+
+fn parse_percent_col(s: &str) -> Option<f64> {
+    s.trim().trim_end_matches('%').parse().ok()
+}
+
+/// Parse `zpool list -Hp -o name,size,alloc,cap,frag`.
+pub fn parse_zpool_list(output: &str) -> Vec<ZfsPool> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split('\t').collect();
+            if cols.len() < 5 {
+                return None;
+            }
+            Some(ZfsPool {
+                name: cols[0].to_string(),
+                size: cols[1].parse().ok()?,
+                alloc: cols[2].parse().ok()?,
+                cap: parse_percent_col(cols[3])?,
+                frag: parse_percent_col(cols[4]),
+            })
+        })
+        .collect()
+}
+
+/// Parse `zfs list -Hp -o name,used,avail,usedbysnapshots`.
+pub fn parse_zfs_datasets(output: &str) -> Vec<ZfsDataset> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split('\t').collect();
+            if cols.len() < 4 {
+                return None;
+            }
+            Some(ZfsDataset {
+                name: cols[0].to_string(),
+                used: cols[1].parse().ok()?,
+                avail: cols[2].parse().ok()?,
+                used_by_snapshots: cols[3].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Parse `zfs list -Hp -t snapshot -o name,used` into (name, used).
+pub fn parse_zfs_snapshots(output: &str) -> Vec<(String, u64)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, used) = line.split_once('\t')?;
+            Some((name.to_string(), used.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+fn analyze_zfs(report: &mut DiagnosticReport, limit: usize) {
+    let pools = run_cmd(&["zpool", "list", "-Hp", "-o", "name,size,alloc,cap,frag"])
+        .map(|out| parse_zpool_list(&out))
+        .unwrap_or_default();
+    for pool in &pools {
+        report.add_metric(Metric {
+            name: format!("zpool {} used", pool.name),
+            value: MetricValue::Float(pool.cap),
+            unit: Some("%".into()),
+            threshold: Some(Threshold {
+                warning: POOL_WARNING_PCT,
+                critical: POOL_CRITICAL_PCT,
+            }),
+        });
+        if pool.cap < POOL_WARNING_PCT {
+            continue;
+        }
+        report.add_finding(Finding {
+            severity: if pool.cap >= POOL_CRITICAL_PCT {
+                Severity::Critical
+            } else {
+                Severity::Warning
+            },
+            category: "zfs".into(),
+            message: format!(
+                "ZFS pool {} is {:.0}% full ({} of {})",
+                pool.name,
+                pool.cap,
+                format_bytes(pool.alloc),
+                format_bytes(pool.size)
+            ),
+            details: Some(format!(
+                "ZFS allocation slows down sharply above 80%{}.",
+                pool.frag
+                    .map(|f| format!("; free space is {:.0}% fragmented", f))
+                    .unwrap_or_default()
+            )),
+        });
+    }
+
+    let datasets = run_cmd(&["zfs", "list", "-Hp", "-t", "filesystem,volume", "-o", "name,used,avail,usedbysnapshots"])
+        .map(|out| parse_zfs_datasets(&out))
+        .unwrap_or_default();
+    let snapshots = run_cmd(&["zfs", "list", "-Hp", "-t", "snapshot", "-o", "name,used", "-s", "used"])
+        .map(|out| parse_zfs_snapshots(&out))
+        .unwrap_or_default();
+    let mut heavy: Vec<&ZfsDataset> = datasets
+        .iter()
+        .filter(|d| d.used_by_snapshots >= SNAPSHOT_REPORT_BYTES)
+        .collect();
+    heavy.sort_by_key(|d| std::cmp::Reverse(d.used_by_snapshots));
+    for dataset in heavy.into_iter().take(limit) {
+        let prefix = format!("{}@", dataset.name);
+        // Sorted ascending by `used`; the largest snapshots come last.
+        let largest: Vec<String> = snapshots
+            .iter()
+            .rev()
+            .filter(|(name, _)| name.starts_with(&prefix))
+            .take(limit)
+            .map(|(name, used)| format!("{}  {}", name, format_bytes(*used)))
+            .collect();
+        report.add_finding(Finding {
+            severity: snapshot_severity(dataset.used_by_snapshots, dataset.used + dataset.avail),
+            category: "snapshots".into(),
+            message: format!(
+                "Snapshots of {} hold {} ({:.0}% of its usage)",
+                dataset.name,
+                format_bytes(dataset.used_by_snapshots),
+                dataset.used_by_snapshots as f64 / dataset.used.max(1) as f64 * 100.0
+            ),
+            details: Some(if largest.is_empty() {
+                "Space is shared between snapshots; destroying a range of them frees it.".into()
+            } else {
+                format!(
+                    "Largest snapshots (space unique to each):\n{}",
+                    largest.join("\n")
+                )
+            }),
+        });
+        report.add_recommendation(Recommendation {
+            priority: 2,
+            action: format!("Preview how much destroying old snapshots of {} frees", dataset.name),
+            command: Some(format!("zfs destroy -nv {}@<first>%<last>", dataset.name)),
+            explanation: "`-n` only reports the reclaimable space; drop it to actually destroy the range."
+                .into(),
+        });
+    }
+}
+
+/// Parse `lvs --noheadings --units b --nosuffix --separator ';'` with the columns
+/// vg_name,lv_name,lv_attr,lv_size,data_percent,metadata_percent,pool_lv.
+pub fn parse_lvs(output: &str) -> Vec<LogicalVolume> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split(';').map(str::trim).collect();
+            if cols.len() < 7 {
+                return None;
+            }
+            Some(LogicalVolume {
+                vg: cols[0].to_string(),
+                name: cols[1].to_string(),
+                attr: cols[2].to_string(),
+                size: cols[3].parse().ok()?,
+                data_pct: parse_percent_col(cols[4]),
+                metadata_pct: parse_percent_col(cols[5]),
+                pool: cols[6].to_string(),
+            })
+        })
+        .collect()
+}
+
+fn analyze_lvm_thin(report: &mut DiagnosticReport) {
+    let Ok(output) = run_cmd(&[
+        "lvs",
+        "--noheadings",
+        "--units",
+        "b",
+        "--nosuffix",
+        "--separator",
+        ";",
+        "-o",
+        "vg_name,lv_name,lv_attr,lv_size,data_percent,metadata_percent,pool_lv",
+    ]) else {
+        return;
+    };
+    let volumes = parse_lvs(&output);
+    for pool in volumes.iter().filter(|lv| lv.is_thin_pool()) {
+        let label = format!("{}/{}", pool.vg, pool.name);
+        let provisioned: u64 = volumes
+            .iter()
+            .filter(|lv| lv.vg == pool.vg && lv.pool == pool.name)
+            .map(|lv| lv.size)
+            .sum();
+        let overcommit = provisioned as f64 / pool.size.max(1) as f64 * 100.0;
+        report.add_metric(Metric {
+            name: format!("{} thin provisioned", label),
+            value: MetricValue::Float(overcommit),
+            unit: Some("%".into()),
+            threshold: None,
+        });
+
+        for (part, pct) in [("data", pool.data_pct), ("metadata", pool.metadata_pct)] {
+            let Some(pct) = pct else {
+                continue;
+            };
+            report.add_metric(Metric {
+                name: format!("{} thin {} used", label, part),
+                value: MetricValue::Float(pct),
+                unit: Some("%".into()),
+                threshold: Some(Threshold {
+                    warning: POOL_WARNING_PCT,
+                    critical: POOL_CRITICAL_PCT,
+                }),
+            });
+            if pct < POOL_WARNING_PCT {
+                continue;
+            }
+            report.add_finding(Finding {
+                severity: if pct >= POOL_CRITICAL_PCT {
+                    Severity::Critical
+                } else {
+                    Severity::Warning
+                },
+                category: "thin-pool".into(),
+                message: format!("Thin pool {} {} is {:.0}% full", label, part, pct),
+                details: Some(format!(
+                    "Its thin volumes are provisioned {} ({:.0}% of the {} pool). When the pool \
+                     runs out, writes to every volume in it fail or the pool turns read-only.",
+                    format_bytes(provisioned),
+                    overcommit,
+                    format_bytes(pool.size)
+                )),
+            });
+            report.add_recommendation(Recommendation {
+                priority: if pct >= POOL_CRITICAL_PCT { 1 } else { 2 },
+                action: format!("Grow the {} of thin pool {}", part, label),
+                command: Some(if part == "data" {
+                    format!("sudo lvextend -L +10G {}", label)
+                } else {
+                    format!("sudo lvextend --poolmetadatasize +1G {}", label)
+                }),
+                explanation: "Setting thin_pool_autoextend_threshold in lvm.conf lets dmeventd grow \
+                              the pool automatically while the volume group has free extents."
+                    .into(),
+            });
+        }
+    }
+}
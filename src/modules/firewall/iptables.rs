@@ -0,0 +1,138 @@
+//! `iptables-save` / `ip6tables-save`: the `*filter` table's chains, policies and rules.
+//! Built-in chains map to the nftables hooks of the same name at priority 0.
+
+use super::{Chain, Match, Rule, Verdict};
+
+/// Split a rule line, keeping quoted arguments (`--comment "allow ssh"`) together.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => current.extend(chars.next()),
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn port_ranges(spec: &str) -> Option<Vec<(u16, u16)>> {
+    spec.split(',')
+        .map(|part| match part.split_once(':') {
+            Some((lo, hi)) => Some((
+                if lo.is_empty() { 0 } else { lo.parse().ok()? },
+                if hi.is_empty() { u16::MAX } else { hi.parse().ok()? },
+            )),
+            None => part.parse().ok().map(|p| (p, p)),
+        })
+        .collect()
+}
+
+fn parse_rule(tokens: &[String]) -> Rule {
+    let mut matches = Vec::new();
+    let mut verdict = None;
+    let mut negate = false;
+    // Skip "-A CHAIN".
+    let mut i = 2;
+    while i < tokens.len() {
+        let opt = tokens[i].as_str();
+        i += 1;
+        if opt == "!" {
+            negate = true;
+            continue;
+        }
+        let start = i;
+        while i < tokens.len() && !tokens[i].starts_with('-') && tokens[i] != "!" {
+            i += 1;
+        }
+        let args = &tokens[start..i];
+        let arg = args.first().map(String::as_str).unwrap_or_default();
+        match opt {
+            // Match modules (-m tcp, -m conntrack) only enable the options that follow.
+            "-m" | "--match" => {}
+            "-p" | "--protocol" => matches.push(Match::Proto { protos: vec![arg.to_lowercase()], negate }),
+            "-i" | "--in-interface" => matches.push(Match::InIface { names: vec![arg.to_string()], negate }),
+            "--dport" | "--destination-port" | "--dports" | "--destination-ports" => match port_ranges(arg) {
+                Some(ranges) => matches.push(Match::DPort { ranges, proto: None, negate }),
+                None => matches.push(Match::Other(format!("{} {}", opt, arg))),
+            },
+            "--ctstate" | "--state" => matches.push(Match::CtState {
+                states: arg.split(',').map(str::to_lowercase).collect(),
+                negate,
+            }),
+            "-j" | "--jump" => {
+                verdict = match arg {
+                    "ACCEPT" => Some(Verdict::Accept),
+                    "DROP" => Some(Verdict::Drop),
+                    "REJECT" => Some(Verdict::Reject),
+                    "RETURN" => Some(Verdict::Return),
+                    // Non-terminating targets (LOG, NFLOG...) are jumps to chains that do not exist.
+                    target => Some(Verdict::Jump(target.to_string())),
+                }
+            }
+            "-g" | "--goto" => verdict = Some(Verdict::Goto(arg.to_string())),
+            // Options of targets and informational matches.
+            "--comment" | "--reject-with" | "--log-prefix" | "--log-level" => {}
+            _ => matches.push(Match::Other(format!("{} {}", opt, args.join(" ")))),
+        }
+        negate = false;
+    }
+    Rule { matches, verdict, text: tokens[2..].join(" ") }
+}
+
+/// Chains of the `*filter` table in `iptables-save` output; `family` is "ip" or "ip6".
+pub fn parse(output: &str, family: &str) -> Vec<Chain> {
+    let mut chains: Vec<Chain> = Vec::new();
+    let mut in_filter = false;
+    for line in output.lines().map(str::trim) {
+        if let Some(table) = line.strip_prefix('*') {
+            in_filter = table == "filter";
+            continue;
+        }
+        if !in_filter {
+            continue;
+        }
+        if let Some(decl) = line.strip_prefix(':') {
+            // ":INPUT DROP [0:0]"; user-defined chains have policy "-".
+            let mut f = decl.split_whitespace();
+            let name = f.next().unwrap_or_default().to_string();
+            let policy = match f.next() {
+                Some("ACCEPT") => Some(Verdict::Accept),
+                Some("DROP") => Some(Verdict::Drop),
+                _ => None,
+            };
+            let hook = matches!(name.as_str(), "INPUT" | "FORWARD" | "OUTPUT").then(|| name.to_lowercase());
+            chains.push(Chain {
+                family: family.to_string(),
+                table: "filter".into(),
+                name,
+                hook,
+                kind: "filter".into(),
+                priority: 0,
+                policy,
+                rules: Vec::new(),
+            });
+        } else if line.starts_with("-A ") {
+            let tokens = tokenize(line);
+            if tokens.len() < 2 {
+                continue;
+            }
+            let rule = parse_rule(&tokens);
+            if let Some(chain) = chains.iter_mut().find(|c| c.name == tokens[1]) {
+                chain.rules.push(rule);
+            }
+        }
+    }
+    chains
+}
@@ -0,0 +1,648 @@
+//! Firewall inspection (firewhy) - the active nftables ruleset (`nft -j list ruleset`) or
+//! iptables rules (`iptables-save`, `iptables-legacy-save`), default policies of the base chains, whether a new
+//! connection to a given port/protocol from a given interface would be accepted, and
+//! connection tracking table usage.
+
+pub mod iptables;
+pub mod nft;
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation, Threshold};
+use crate::core::severity::Severity;
+use crate::core::traits::{DiagnosticModule, ModuleConfig};
+use crate::utils::{command_exists, is_root, read_first_line, run_cmd};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Conntrack table fill levels (percent of nf_conntrack_max).
+const CONNTRACK_WARN: f64 = 80.0;
+const CONNTRACK_CRIT: f64 = 95.0;
+/// Nested jumps followed before giving up (guards against loops).
+const MAX_DEPTH: usize = 16;
+/// iptables dump tools per family, tried in order. Rules loaded with iptables-legacy live in
+/// the old x_tables, which neither nft nor iptables-nft can see.
+const SAVE_TOOLS: [(Backend, [(&str, &str); 2]); 2] = [
+    (Backend::Iptables, [("iptables-save", "ip"), ("ip6tables-save", "ip6")]),
+    (Backend::IptablesLegacy, [("iptables-legacy-save", "ip"), ("ip6tables-legacy-save", "ip6")]),
+];
+
+pub fn module() -> Arc<dyn DiagnosticModule> {
+    Arc::new(FirewallModule)
+}
+
+struct FirewallModule;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    fn name(self) -> &'static str {
+        match self {
+            Family::V4 => "IPv4",
+            Family::V6 => "IPv6",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Accept,
+    Drop,
+    Reject,
+    Jump(String),
+    Goto(String),
+    Return,
+}
+
+/// One match of a rule, reduced to what the evaluator understands.
+#[derive(Debug, Clone)]
+pub enum Match {
+    Proto { protos: Vec<String>, negate: bool },
+    /// Destination port ranges; `proto` is set when the match implies one (`tcp dport`).
+    DPort { ranges: Vec<(u16, u16)>, proto: Option<String>, negate: bool },
+    /// Interface names; a trailing `*` (nft) or `+` (iptables) is a prefix wildcard.
+    InIface { names: Vec<String>, negate: bool },
+    CtState { states: Vec<String>, negate: bool },
+    /// Anything else (addresses, sets, marks...): the packet may or may not match.
+    Other(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub matches: Vec<Match>,
+    pub verdict: Option<Verdict>,
+    /// As written (iptables) or reconstructed (nft), for messages.
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Chain {
+    /// "inet", "ip", "ip6" for nft; "ip"/"ip6" for iptables.
+    pub family: String,
+    pub table: String,
+    pub name: String,
+    /// Netfilter hook of a base chain ("input", "forward", "output"...); `None` for regular chains.
+    pub hook: Option<String>,
+    /// nft chain type ("filter", "nat", "route"); iptables chains are always "filter".
+    pub kind: String,
+    pub priority: i64,
+    pub policy: Option<Verdict>,
+    pub rules: Vec<Rule>,
+}
+
+impl Chain {
+    fn label(&self) -> String {
+        format!("{} {} {}", self.family, self.table, self.name)
+    }
+
+    fn applies_to(&self, family: Family) -> bool {
+        match family {
+            Family::V4 => self.family == "ip" || self.family == "inet",
+            Family::V6 => self.family == "ip6" || self.family == "inet",
+        }
+    }
+}
+
+/// Which tool the ruleset came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Nft,
+    Iptables,
+    /// iptables-legacy-save, whose rules only the `-legacy` tools can change.
+    IptablesLegacy,
+}
+
+impl Backend {
+    fn name(self) -> &'static str {
+        match self {
+            Backend::Nft => "nftables",
+            Backend::Iptables => "iptables",
+            Backend::IptablesLegacy => "iptables-legacy",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ruleset {
+    pub backend: Backend,
+    pub chains: Vec<Chain>,
+}
+
+impl Ruleset {
+    fn chain(&self, family: &str, table: &str, name: &str) -> Option<&Chain> {
+        self.chains
+            .iter()
+            .find(|c| c.family == family && c.table == table && c.name == name)
+    }
+
+    fn rule_count(&self) -> usize {
+        self.chains.iter().map(|c| c.rules.len()).sum()
+    }
+}
+
+/// A new inbound connection to evaluate.
+#[derive(Debug, Clone)]
+pub struct Packet {
+    pub proto: String,
+    pub dport: Option<u16>,
+    pub iface: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tri {
+    Yes,
+    No,
+    Maybe,
+}
+
+fn negated(result: bool, negate: bool) -> Tri {
+    if result != negate {
+        Tri::Yes
+    } else {
+        Tri::No
+    }
+}
+
+/// Protocols whose header carries ports; `dport` matches nothing else.
+fn has_ports(proto: &str) -> bool {
+    matches!(proto, "tcp" | "udp" | "udplite" | "sctp" | "dccp")
+}
+
+fn iface_matches(pattern: &str, iface: &str) -> bool {
+    match pattern.strip_suffix('*').or_else(|| pattern.strip_suffix('+')) {
+        Some(prefix) => iface.starts_with(prefix),
+        None => pattern == iface,
+    }
+}
+
+fn eval_match(m: &Match, p: &Packet) -> Tri {
+    match m {
+        Match::Proto { protos, negate } => negated(protos.contains(&p.proto), *negate),
+        Match::DPort { ranges, proto, negate } => {
+            if proto.as_ref().is_some_and(|x| *x != p.proto) || !has_ports(&p.proto) {
+                return Tri::No;
+            }
+            match p.dport {
+                Some(port) => negated(ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&port)), *negate),
+                None => Tri::Maybe,
+            }
+        }
+        Match::InIface { names, negate } => match &p.iface {
+            Some(iface) => negated(names.iter().any(|n| iface_matches(n, iface)), *negate),
+            None => Tri::Maybe,
+        },
+        // The packet opens a new connection.
+        Match::CtState { states, negate } => negated(states.iter().any(|s| s == "new"), *negate),
+        Match::Other(_) => Tri::Maybe,
+    }
+}
+
+fn eval_rule(rule: &Rule, p: &Packet) -> Tri {
+    let mut result = Tri::Yes;
+    for m in &rule.matches {
+        match eval_match(m, p) {
+            Tri::No => return Tri::No,
+            Tri::Maybe => result = Tri::Maybe,
+            Tri::Yes => {}
+        }
+    }
+    result
+}
+
+/// Final decision of one base chain and what made it.
+#[derive(Debug, Clone)]
+pub struct Decision {
+    pub verdict: Verdict,
+    pub by: String,
+    /// Rules that might have decided differently, depending on what the evaluator cannot see.
+    pub uncertain: Vec<String>,
+}
+
+/// Walk `chain` for `p`. `None` means the chain returned (or fell off its end).
+fn walk(rs: &Ruleset, chain: &Chain, p: &Packet, depth: usize, uncertain: &mut Vec<String>) -> Option<(Verdict, String)> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    for (i, rule) in chain.rules.iter().enumerate() {
+        let Some(verdict) = &rule.verdict else {
+            continue;
+        };
+        let at = format!("rule {} in {}: {}", i + 1, chain.label(), rule.text);
+        match eval_rule(rule, p) {
+            Tri::No => continue,
+            Tri::Maybe => {
+                if *verdict != Verdict::Return {
+                    uncertain.push(at);
+                }
+                continue;
+            }
+            Tri::Yes => {}
+        }
+        match verdict {
+            Verdict::Accept | Verdict::Drop | Verdict::Reject => return Some((verdict.clone(), at)),
+            Verdict::Return => return None,
+            Verdict::Jump(target) | Verdict::Goto(target) => {
+                let result = rs
+                    .chain(&chain.family, &chain.table, target)
+                    .and_then(|t| walk(rs, t, p, depth + 1, uncertain));
+                if result.is_some() || matches!(verdict, Verdict::Goto(_)) {
+                    return result;
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Decision of every filter base chain on `hook` for `family`, in priority order.
+pub fn evaluate(rs: &Ruleset, family: Family, hook: &str, p: &Packet) -> Vec<Decision> {
+    let mut bases: Vec<&Chain> = rs
+        .chains
+        .iter()
+        .filter(|c| c.hook.as_deref() == Some(hook) && c.kind == "filter" && c.applies_to(family))
+        .collect();
+    bases.sort_by_key(|c| c.priority);
+    bases
+        .into_iter()
+        .map(|chain| {
+            let mut uncertain = Vec::new();
+            let (verdict, by) = walk(rs, chain, p, 0, &mut uncertain).unwrap_or_else(|| {
+                let policy = chain.policy.clone().unwrap_or(Verdict::Accept);
+                (policy, format!("the default policy of {}", chain.label()))
+            });
+            Decision { verdict, by, uncertain }
+        })
+        .collect()
+}
+
+/// The nft ruleset, or the first non-empty iptables dump when nft shows no chains.
+fn load_ruleset() -> Result<Ruleset> {
+    let mut empty = Ruleset { backend: Backend::Iptables, chains: Vec::new() };
+    if command_exists("nft") {
+        let rs = nft::parse(&run_cmd(&["nft", "-j", "list", "ruleset"])?)?;
+        if !rs.chains.is_empty() {
+            return Ok(rs);
+        }
+        empty = rs;
+    }
+    for (backend, tools) in SAVE_TOOLS {
+        let mut chains = Vec::new();
+        for (tool, family) in tools {
+            if command_exists(tool) {
+                chains.extend(iptables::parse(&run_cmd(&[tool])?, family));
+            }
+        }
+        if !chains.is_empty() {
+            return Ok(Ruleset { backend, chains });
+        }
+    }
+    Ok(empty)
+}
+
+fn verdict_name(v: &Verdict) -> String {
+    match v {
+        Verdict::Accept => "accept".into(),
+        Verdict::Drop => "drop".into(),
+        Verdict::Reject => "reject".into(),
+        Verdict::Jump(t) => format!("jump {}", t),
+        Verdict::Goto(t) => format!("goto {}", t),
+        Verdict::Return => "return".into(),
+    }
+}
+
+fn report_policies(report: &mut DiagnosticReport, rs: &Ruleset) {
+    let mut bases: Vec<&Chain> = rs
+        .chains
+        .iter()
+        .filter(|c| c.hook.is_some() && c.kind == "filter")
+        .collect();
+    bases.sort_by_key(|c| (c.family.clone(), c.table.clone(), c.priority));
+    for chain in &bases {
+        let policy = chain.policy.as_ref().map_or("accept".into(), verdict_name);
+        report.add_metric(Metric {
+            name: format!("Policy {}", chain.label()),
+            value: MetricValue::Text(format!("{} ({} rules)", policy, chain.rules.len())),
+            unit: None,
+            threshold: None,
+        });
+        if chain.hook.as_deref() == Some("output") && matches!(chain.policy, Some(Verdict::Drop | Verdict::Reject)) {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "firewall".into(),
+                message: format!("Outgoing traffic is dropped by default ({})", chain.label()),
+                details: Some("Only connections that a rule explicitly allows can leave this host.".into()),
+            });
+        }
+    }
+    if rs.rule_count() == 0 && bases.iter().all(|c| c.policy.is_none() || c.policy == Some(Verdict::Accept)) {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "firewall".into(),
+            message: "No firewall rules are active".into(),
+            details: Some("All traffic is accepted; the firewall is not what blocks connections.".into()),
+        });
+    }
+}
+
+fn allow_command(rs: &Ruleset, family: Family, chain_by: &str, p: &Packet) -> String {
+    let port = p.dport.map(|d| d.to_string()).unwrap_or_default();
+    match rs.backend {
+        Backend::Nft => {
+            // "rule 3 in inet filter input: ..." or "the default policy of inet filter input"
+            let chain = chain_by
+                .split(" in ")
+                .nth(1)
+                .or_else(|| chain_by.strip_prefix("the default policy of "))
+                .map(|c| c.split(':').next().unwrap_or(c))
+                .unwrap_or("inet filter input");
+            let mut rule = String::new();
+            if let Some(iface) = &p.iface {
+                rule.push_str(&format!("iifname \"{}\" ", iface));
+            }
+            if port.is_empty() || !has_ports(&p.proto) {
+                rule.push_str(&format!("meta l4proto {} accept", p.proto));
+            } else {
+                rule.push_str(&format!("{} dport {} accept", p.proto, port));
+            }
+            format!("sudo nft insert rule {} {}", chain, rule)
+        }
+        Backend::Iptables | Backend::IptablesLegacy => {
+            let mut tool = if family == Family::V6 { "ip6tables" } else { "iptables" }.to_string();
+            if rs.backend == Backend::IptablesLegacy {
+                tool.push_str("-legacy");
+            }
+            let mut cmd = format!("sudo {} -I INPUT", tool);
+            if let Some(iface) = &p.iface {
+                cmd.push_str(&format!(" -i {}", iface));
+            }
+            cmd.push_str(&format!(" -p {}", p.proto));
+            if !port.is_empty() && has_ports(&p.proto) {
+                cmd.push_str(&format!(" --dport {}", port));
+            }
+            cmd.push_str(" -j ACCEPT");
+            cmd
+        }
+    }
+}
+
+fn answer_query(report: &mut DiagnosticReport, rs: &Ruleset, p: &Packet) {
+    let port = p
+        .dport
+        .filter(|_| has_ports(&p.proto))
+        .map(|d| format!(" port {}", d))
+        .unwrap_or_default();
+    let what = format!(
+        "A new {} connection{}{}",
+        p.proto,
+        if port.is_empty() { String::new() } else { format!(" to{}", port) },
+        p.iface.as_ref().map(|i| format!(" arriving on {}", i)).unwrap_or_default()
+    );
+    for family in [Family::V4, Family::V6] {
+        let decisions = evaluate(rs, family, "input", p);
+        let blocked = decisions
+            .iter()
+            .find(|d| matches!(d.verdict, Verdict::Drop | Verdict::Reject));
+        let uncertain: Vec<String> = decisions.iter().flat_map(|d| d.uncertain.iter().cloned()).collect();
+        let caveat = if uncertain.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "\nThese rules depend on details the check cannot see (addresses, sets, marks) and may decide differently:\n  {}",
+                uncertain.join("\n  ")
+            )
+        };
+        let verdict = match blocked {
+            Some(d) => verdict_name(&d.verdict),
+            None => "accept".into(),
+        };
+        report.add_metric(Metric {
+            name: format!("{} verdict", family.name()),
+            value: MetricValue::Text(verdict),
+            unit: None,
+            threshold: None,
+        });
+        match blocked {
+            Some(d) => {
+                report.add_finding(Finding {
+                    severity: Severity::Warning,
+                    category: "firewall".into(),
+                    message: format!(
+                        "{} over {} would be {}",
+                        what,
+                        family.name(),
+                        if d.verdict == Verdict::Reject { "rejected" } else { "dropped" }
+                    ),
+                    details: Some(format!("Decided by {}.{}", d.by, caveat)),
+                });
+                // One command per blocked family, unless an inet chain blocks both the same way.
+                let command = allow_command(rs, family, &d.by, p);
+                match report.recommendations.iter_mut().find(|r| r.command.as_ref() == Some(&command)) {
+                    Some(r) => r.action = format!("Allow {}{} over IPv4 and IPv6", p.proto, port),
+                    None => report.add_recommendation(Recommendation {
+                        priority: 1,
+                        action: format!("Allow {}{} over {}", p.proto, port, family.name()),
+                        command: Some(command),
+                        explanation: "Inserts an accept rule at the top of the chain; persist it in the firewall \
+                                      configuration (nftables.conf, ufw, firewalld) once confirmed."
+                            .into(),
+                    }),
+                }
+            }
+            None => {
+                let by = decisions
+                    .last()
+                    .map(|d| d.by.clone())
+                    .unwrap_or_else(|| format!("the absence of {} input chains", family.name()));
+                report.add_finding(Finding {
+                    severity: if uncertain.is_empty() { Severity::Ok } else { Severity::Info },
+                    category: "firewall".into(),
+                    message: format!("{} over {} would be accepted", what, family.name()),
+                    details: Some(format!("Decided by {}.{}", by, caveat)),
+                });
+            }
+        }
+    }
+}
+
+/// Table usage, plus drops and early evictions (/proc/net/stat/nf_conntrack, hex per CPU).
+fn analyze_conntrack(report: &mut DiagnosticReport) {
+    let read = |name: &str| {
+        read_first_line(&Path::new("/proc/sys/net/netfilter").join(name))
+            .ok()
+            .flatten()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+    };
+    let (Some(count), Some(max)) = (read("nf_conntrack_count"), read("nf_conntrack_max")) else {
+        return;
+    };
+    let pct = if max > 0 { count as f64 * 100.0 / max as f64 } else { 0.0 };
+    report.add_metric(Metric {
+        name: "Conntrack entries".into(),
+        value: MetricValue::Text(format!("{} / {} ({:.1}%)", count, max, pct)),
+        unit: None,
+        threshold: None,
+    });
+    report.add_metric(Metric {
+        name: "Conntrack usage".into(),
+        value: MetricValue::Float(pct),
+        unit: Some("%".into()),
+        threshold: Some(Threshold { warning: CONNTRACK_WARN, critical: CONNTRACK_CRIT }),
+    });
+
+    // `drop`: packets dropped because no entry could be created; `early_drop`: unassured
+    // entries evicted to make room for new ones, which costs those flows their state.
+    let counters = std::fs::read_to_string("/proc/net/stat/nf_conntrack").ok().and_then(|stat| {
+        let mut lines = stat.lines();
+        let header: Vec<&str> = lines.next()?.split_whitespace().collect();
+        let col = |name: &str| header.iter().position(|h| *h == name);
+        let (drop, early) = (col("drop")?, col("early_drop")?);
+        let (mut drops, mut evictions) = (0u64, 0u64);
+        for line in lines {
+            let f: Vec<&str> = line.split_whitespace().collect();
+            let value = |i: usize| f.get(i).and_then(|v| u64::from_str_radix(v, 16).ok()).unwrap_or(0);
+            drops += value(drop);
+            evictions += value(early);
+        }
+        Some((drops, evictions))
+    });
+    let (drops, evictions) = counters.unwrap_or_default();
+    let metrics = [("Conntrack drops since boot", drops), ("Conntrack early drops since boot", evictions)];
+    for (name, n) in metrics {
+        if n > 0 {
+            report.add_metric(Metric {
+                name: name.into(),
+                value: MetricValue::Integer(n as i64),
+                unit: None,
+                threshold: None,
+            });
+        }
+    }
+
+    if pct >= CONNTRACK_WARN {
+        report.add_finding(Finding {
+            severity: if pct >= CONNTRACK_CRIT { Severity::Critical } else { Severity::Warning },
+            category: "conntrack".into(),
+            message: format!("Connection tracking table is {:.0}% full ({} of {})", pct, count, max),
+            details: Some(
+                "When it fills up the kernel drops new connections (\"nf_conntrack: table full, dropping \
+                 packet\" in dmesg): sites time out at random while established connections keep working."
+                    .into(),
+            ),
+        });
+        report.add_recommendation(Recommendation {
+            priority: 1,
+            action: "Raise the conntrack table limit".into(),
+            command: Some(format!("sudo sysctl -w net.netfilter.nf_conntrack_max={}", max.max(65536) * 2)),
+            explanation: "Each entry takes about 300 bytes; persist it in /etc/sysctl.d/. Also check for a \
+                          connection flood with `conntrack -L | awk '{print $5}' | sort | uniq -c | sort -rn | head`."
+                .into(),
+        });
+    }
+    // The counters only grow, so both may date from a peak long ago.
+    if drops > 0 {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "conntrack".into(),
+            message: format!(
+                "{} packet(s) were dropped since boot because no conntrack entry could be created",
+                drops
+            ),
+            details: Some(format!(
+                "The table is {:.0}% full now. If the counter keeps growing, look for \"nf_conntrack: table \
+                 full\" in dmesg and raise net.netfilter.nf_conntrack_max.",
+                pct
+            )),
+        });
+    }
+    if evictions > 0 {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "conntrack".into(),
+            message: format!("{} conntrack entries were evicted early since boot to make room", evictions),
+            details: Some(
+                "The table was full and the kernel freed entries of not yet established flows, which \
+                 then lost their state. Persistent evictions mean the table is too small for the load."
+                    .into(),
+            ),
+        });
+    }
+}
+
+#[async_trait]
+impl DiagnosticModule for FirewallModule {
+    fn name(&self) -> &'static str {
+        "firewall"
+    }
+
+    fn description(&self) -> &'static str {
+        "Inspect nftables/iptables rules and conntrack usage"
+    }
+
+    async fn run(&self, config: &ModuleConfig) -> Result<DiagnosticReport> {
+        let mut report = DiagnosticReport::new("firewall", "Firewall rules and connection tracking");
+
+        let query = Packet {
+            proto: config.extra_args.get("proto").cloned().unwrap_or_else(|| "tcp".into()).to_lowercase(),
+            dport: config.extra_args.get("port").and_then(|p| p.parse().ok()),
+            iface: config.extra_args.get("iface").cloned(),
+        };
+        let asked = query.dport.is_some() || query.iface.is_some();
+
+        if !command_exists("nft") && !SAVE_TOOLS.iter().any(|(_, tools)| command_exists(tools[0].0)) {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "firewall".into(),
+                message: "Neither nft nor iptables-save is installed".into(),
+                details: Some("Without the tools no rules can be loaded from userspace, so none are likely active.".into()),
+            });
+        } else {
+            match load_ruleset() {
+                Ok(rs) => {
+                    report.add_metric(Metric {
+                        name: "Ruleset".into(),
+                        value: MetricValue::Text(format!(
+                            "{} ({} chains, {} rules)",
+                            rs.backend.name(),
+                            rs.chains.len(),
+                            rs.rule_count()
+                        )),
+                        unit: None,
+                        threshold: None,
+                    });
+                    report_policies(&mut report, &rs);
+                    if asked {
+                        answer_query(&mut report, &rs, &query);
+                    }
+                }
+                Err(e) => {
+                    report.add_finding(Finding {
+                        severity: Severity::Info,
+                        category: "firewall".into(),
+                        message: "Could not read the firewall ruleset".into(),
+                        details: Some(if is_root() {
+                            format!("{:#}", e)
+                        } else {
+                            "Listing rules needs CAP_NET_ADMIN; run as root.".into()
+                        }),
+                    });
+                }
+            }
+        }
+
+        analyze_conntrack(&mut report);
+        if report.findings.is_empty() {
+            report.add_finding(Finding {
+                severity: Severity::Ok,
+                category: "firewall".into(),
+                message: "No firewall or connection tracking problems found".into(),
+                details: None,
+            });
+        }
+        report.compute_overall_severity();
+        Ok(report)
+    }
+}
@@ -0,0 +1,205 @@
+//! `nft -j list ruleset`: chains with their hook, type, priority and policy, and rules reduced
+//! to the matches the evaluator understands (protocol, destination port, input interface,
+//! conntrack state). Everything else becomes `Match::Other`.
+
+use super::{Backend, Chain, Match, Rule, Ruleset, Verdict};
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+pub fn parse(json: &str) -> Result<Ruleset> {
+    let root: Value = serde_json::from_str(json).context("invalid nft JSON output")?;
+    let items = root["nftables"].as_array().context("no \"nftables\" array in nft output")?;
+
+    let mut chains: Vec<Chain> = Vec::new();
+    for item in items {
+        if let Some(c) = item.get("chain") {
+            chains.push(Chain {
+                family: str_of(&c["family"]),
+                table: str_of(&c["table"]),
+                name: str_of(&c["name"]),
+                hook: c["hook"].as_str().map(str::to_string),
+                kind: c["type"].as_str().unwrap_or("filter").to_string(),
+                priority: c["prio"].as_i64().unwrap_or(0),
+                policy: c["policy"].as_str().and_then(verdict_keyword),
+                rules: Vec::new(),
+            });
+        } else if let Some(r) = item.get("rule") {
+            let (family, table, name) = (str_of(&r["family"]), str_of(&r["table"]), str_of(&r["chain"]));
+            let rule = parse_rule(r["expr"].as_array().map(Vec::as_slice).unwrap_or_default());
+            if let Some(chain) = chains
+                .iter_mut()
+                .find(|c| c.family == family && c.table == table && c.name == name)
+            {
+                chain.rules.push(rule);
+            }
+        }
+    }
+    Ok(Ruleset { backend: Backend::Nft, chains })
+}
+
+fn str_of(v: &Value) -> String {
+    v.as_str().unwrap_or_default().to_string()
+}
+
+fn verdict_keyword(s: &str) -> Option<Verdict> {
+    match s {
+        "accept" => Some(Verdict::Accept),
+        "drop" => Some(Verdict::Drop),
+        "reject" => Some(Verdict::Reject),
+        "return" => Some(Verdict::Return),
+        _ => None,
+    }
+}
+
+fn parse_rule(exprs: &[Value]) -> Rule {
+    let mut matches = Vec::new();
+    let mut verdict = None;
+    let mut text = Vec::new();
+    for expr in exprs {
+        let Some((key, body)) = expr.as_object().and_then(|o| o.iter().next()) else {
+            continue;
+        };
+        match key.as_str() {
+            "match" => {
+                let m = parse_match(body);
+                text.push(describe(&m));
+                matches.push(m);
+            }
+            "accept" | "drop" | "reject" | "return" => {
+                verdict = verdict_keyword(key);
+                text.push(key.clone());
+            }
+            "jump" | "goto" => {
+                let target = str_of(&body["target"]);
+                text.push(format!("{} {}", key, target));
+                verdict = Some(if key == "jump" { Verdict::Jump(target) } else { Verdict::Goto(target) });
+            }
+            // Statements that do not affect the verdict.
+            "counter" | "log" | "limit" | "comment" | "mangle" | "notrack" => {}
+            other => {
+                text.push(other.to_string());
+                matches.push(Match::Other(other.to_string()));
+            }
+        }
+    }
+    Rule { matches, verdict, text: text.join(" ") }
+}
+
+/// The strings or numbers on the right-hand side: a scalar, a list, or an anonymous set.
+fn values(right: &Value) -> Option<Vec<&Value>> {
+    match right {
+        Value::Array(items) => Some(items.iter().collect()),
+        Value::Object(o) if o.contains_key("set") => o["set"].as_array().map(|s| s.iter().collect()),
+        // "@named_set" and other expressions
+        Value::String(s) if s.starts_with('@') => None,
+        Value::Object(o) if !o.contains_key("range") => None,
+        v => Some(vec![v]),
+    }
+}
+
+fn port_ranges(right: &Value) -> Option<Vec<(u16, u16)>> {
+    values(right)?
+        .into_iter()
+        .map(|v| match v {
+            Value::Object(o) => {
+                let r = o.get("range")?.as_array()?;
+                Some((u16::try_from(r.first()?.as_u64()?).ok()?, u16::try_from(r.get(1)?.as_u64()?).ok()?))
+            }
+            v => {
+                let p = u16::try_from(v.as_u64()?).ok()?;
+                Some((p, p))
+            }
+        })
+        .collect()
+}
+
+fn strings(right: &Value) -> Option<Vec<String>> {
+    values(right)?
+        .into_iter()
+        .map(|v| v.as_str().map(str::to_string))
+        .collect()
+}
+
+fn parse_match(m: &Value) -> Match {
+    let negate = m["op"].as_str() == Some("!=");
+    let (left, right) = (&m["left"], &m["right"]);
+    let other = || Match::Other(format!("{}{} {}", left_name(left), if negate { " !=" } else { "" }, right_name(right)));
+
+    if let Some(payload) = left.get("payload") {
+        let protocol = payload["protocol"].as_str().unwrap_or_default();
+        return match payload["field"].as_str() {
+            Some("dport") => match port_ranges(right) {
+                // "th dport" matches any transport protocol with ports.
+                Some(ranges) => Match::DPort {
+                    ranges,
+                    proto: (protocol != "th").then(|| protocol.to_string()),
+                    negate,
+                },
+                None => other(),
+            },
+            Some("protocol") if protocol == "ip" => proto_match(right, negate).unwrap_or_else(other),
+            Some("nexthdr") if protocol == "ip6" => proto_match(right, negate).unwrap_or_else(other),
+            _ => other(),
+        };
+    }
+    if let Some(meta) = left.get("meta") {
+        return match meta["key"].as_str() {
+            Some("l4proto") => proto_match(right, negate).unwrap_or_else(other),
+            Some("iifname") | Some("iif") => match strings(right) {
+                Some(names) => Match::InIface { names, negate },
+                None => other(),
+            },
+            _ => other(),
+        };
+    }
+    if left.get("ct").is_some_and(|ct| ct["key"].as_str() == Some("state")) {
+        if let Some(states) = strings(right) {
+            return Match::CtState { states, negate };
+        }
+    }
+    other()
+}
+
+/// `ip saddr`, `meta mark`, `ct mark`... for messages.
+fn left_name(left: &Value) -> String {
+    let Some((kind, body)) = left.as_object().and_then(|o| o.iter().next()) else {
+        return left.to_string();
+    };
+    match kind.as_str() {
+        "payload" => format!("{} {}", str_of(&body["protocol"]), str_of(&body["field"])),
+        "meta" | "ct" => format!("{} {}", kind, str_of(&body["key"])),
+        other => other.to_string(),
+    }
+}
+
+fn right_name(right: &Value) -> String {
+    match right {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Object(o) if o.contains_key("prefix") => {
+            format!("{}/{}", str_of(&o["prefix"]["addr"]), o["prefix"]["len"])
+        }
+        _ => "{…}".into(),
+    }
+}
+
+fn proto_match(right: &Value, negate: bool) -> Option<Match> {
+    Some(Match::Proto { protos: strings(right)?, negate })
+}
+
+fn describe(m: &Match) -> String {
+    let not = |negate: &bool| if *negate { "!= " } else { "" };
+    match m {
+        Match::Proto { protos, negate } => format!("meta l4proto {}{}", not(negate), protos.join(",")),
+        Match::DPort { ranges, proto, negate } => {
+            let ports: Vec<String> = ranges
+                .iter()
+                .map(|(lo, hi)| if lo == hi { lo.to_string() } else { format!("{}-{}", lo, hi) })
+                .collect();
+            format!("{} dport {}{}", proto.as_deref().unwrap_or("th"), not(negate), ports.join(","))
+        }
+        Match::InIface { names, negate } => format!("iifname {}{}", not(negate), names.join(",")),
+        Match::CtState { states, negate } => format!("ct state {}{}", not(negate), states.join(",")),
+        Match::Other(s) => s.clone(),
+    }
+}
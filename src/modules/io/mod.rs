@@ -1,9 +1,14 @@
-//! Disk I/O explanation (iowhy) - /proc/diskstats, per-process I/O.
+//! Disk I/O explanation (iowhy) - /proc/diskstats, per-process I/O, dirty page writeback,
+//! block queue configuration, open files of the busiest processes.
+
+mod files;
+mod queue;
+mod writeback;
 
 use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
 use crate::core::severity::Severity;
 use crate::core::traits::{DiagnosticModule, ModuleConfig};
-use crate::utils::format_bytes;
+use crate::utils::{format_bytes, read_mounts};
 use anyhow::Result;
 use async_trait::async_trait;
 use std::path::Path;
@@ -88,6 +93,9 @@ impl DiagnosticModule for IoModule {
             }
         }
 
+        writeback::analyze(&mut report, device_filter).await;
+        queue::analyze(&mut report, device_filter);
+
         let proc_path = Path::new("/proc");
         let mut process_io: Vec<(u32, String, u64, u64)> = Vec::new();
         if let Ok(entries) = std::fs::read_dir(proc_path) {
@@ -106,13 +114,34 @@ impl DiagnosticModule for IoModule {
                 }
             }
         }
-        process_io.sort_by(|a, b| (b.2 + b.3).cmp(&(a.2 + a.3)));
+        process_io.sort_by_key(|b| std::cmp::Reverse(b.2 + b.3));
+        let mounts = read_mounts().unwrap_or_default();
         for (pid, comm, r, w) in process_io.into_iter().take(config.top_n) {
+            let open = files::open_files(pid, &mounts);
+            let busiest = open
+                .as_ref()
+                .and_then(|f| f.first())
+                .map(|f| format!(" – busiest file {} on {}", f.path.display(), f.device))
+                .unwrap_or_default();
+            let details = match &open {
+                Some(f) if !f.is_empty() => {
+                    format!("Cumulative I/O since process start. Open files:\n{}", files::describe(f))
+                }
+                Some(_) => "Cumulative I/O since process start; no regular files open right now.".into(),
+                None => "Cumulative I/O since process start; open files not readable (run as root).".into(),
+            };
             report.add_finding(Finding {
                 severity: Severity::Info,
                 category: "process".into(),
-                message: format!("{} (PID {}) – read {}, write {}", comm, pid, format_bytes(r), format_bytes(w)),
-                details: Some("Cumulative I/O since process start.".into()),
+                message: format!(
+                    "{} (PID {}) – read {}, write {}{}",
+                    comm,
+                    pid,
+                    format_bytes(r),
+                    format_bytes(w),
+                    busiest
+                ),
+                details: Some(details),
             });
         }
 
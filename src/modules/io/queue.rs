@@ -0,0 +1,284 @@
+//! Block device queue configuration: scheduler, rotational flag, queue depth, read-ahead,
+//! discard, write cache, block sizes and partition alignment from /sys/block/<dev>/queue.
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use crate::utils::{list_dir, read_first_line, read_mounts};
+use std::path::{Path, PathBuf};
+
+/// Where systemd records the last run of the persistent fstrim.timer.
+const FSTRIM_STAMP: &str = "/var/lib/systemd/timers/stamp-fstrim.timer";
+
+/// Queue settings of one whole disk.
+#[derive(Debug, Clone)]
+pub struct QueueInfo {
+    pub name: String,
+    /// Active scheduler (the bracketed entry) and all available ones.
+    pub scheduler: Option<String>,
+    pub available_schedulers: Vec<String>,
+    pub rotational: bool,
+    pub nr_requests: Option<u64>,
+    pub read_ahead_kb: Option<u64>,
+    pub discard: bool,
+    pub write_cache: Option<String>,
+    pub logical_block: u64,
+    pub physical_block: u64,
+    pub partitions: Vec<Partition>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Partition {
+    pub name: String,
+    /// Start in 512-byte sectors.
+    pub start: u64,
+    pub alignment_offset: u64,
+}
+
+fn read_attr(path: &Path) -> Option<String> {
+    read_first_line(path).ok().flatten().map(|s| s.trim().to_string())
+}
+
+fn read_num(path: &Path) -> Option<u64> {
+    read_attr(path)?.parse().ok()
+}
+
+/// Split "none [mq-deadline] kyber bfq" into the active and the available schedulers.
+fn parse_scheduler(line: &str) -> (Option<String>, Vec<String>) {
+    let mut active = None;
+    let all = line
+        .split_whitespace()
+        .map(|s| {
+            if let Some(name) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                active = Some(name.to_string());
+                name.to_string()
+            } else {
+                s.to_string()
+            }
+        })
+        .collect();
+    (active, all)
+}
+
+/// Physical disks and virtual disks backed by real storage; skips loop, ram, zram, md and dm.
+fn is_disk(name: &str) -> bool {
+    !["loop", "ram", "zram", "md", "dm-", "sr", "fd", "nbd"]
+        .iter()
+        .any(|p| name.starts_with(p))
+}
+
+pub fn read_queues() -> Vec<QueueInfo> {
+    let mut out = Vec::new();
+    for dir in list_dir(Path::new("/sys/block")).unwrap_or_default() {
+        let Some(name) = dir.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        if !is_disk(&name) {
+            continue;
+        }
+        let queue = dir.join("queue");
+        let (scheduler, available_schedulers) = read_attr(&queue.join("scheduler"))
+            .map(|s| parse_scheduler(&s))
+            .unwrap_or_default();
+        let partitions = list_dir(&dir)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| p.join("partition").exists())
+            .filter_map(|p| {
+                Some(Partition {
+                    name: p.file_name()?.to_string_lossy().into_owned(),
+                    start: read_num(&p.join("start"))?,
+                    alignment_offset: read_num(&p.join("alignment_offset")).unwrap_or(0),
+                })
+            })
+            .collect();
+        out.push(QueueInfo {
+            scheduler,
+            available_schedulers,
+            rotational: read_num(&queue.join("rotational")) == Some(1),
+            nr_requests: read_num(&queue.join("nr_requests")),
+            read_ahead_kb: read_num(&queue.join("read_ahead_kb")),
+            discard: read_num(&queue.join("discard_max_bytes")).unwrap_or(0) > 0,
+            write_cache: read_attr(&queue.join("write_cache")),
+            logical_block: read_num(&queue.join("logical_block_size")).unwrap_or(512),
+            physical_block: read_num(&queue.join("physical_block_size")).unwrap_or(512),
+            partitions,
+            name,
+        });
+    }
+    out.sort_by(|a, b| a.name.cmp(&b.name));
+    out
+}
+
+/// Whole disks underneath a block device: the parent of a partition, the slaves of dm/md.
+fn disks_of(block: &str, depth: usize) -> Vec<String> {
+    let class = Path::new("/sys/class/block").join(block);
+    if depth > 8 {
+        return Vec::new();
+    }
+    if class.join("partition").exists() {
+        return std::fs::canonicalize(&class)
+            .ok()
+            .and_then(|p| p.parent().and_then(|d| d.file_name()).map(|n| vec![n.to_string_lossy().into_owned()]))
+            .unwrap_or_default();
+    }
+    let slaves: Vec<PathBuf> = list_dir(&class.join("slaves")).unwrap_or_default();
+    if slaves.is_empty() {
+        return vec![block.to_string()];
+    }
+    slaves
+        .iter()
+        .filter_map(|s| s.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .flat_map(|s| disks_of(&s, depth + 1))
+        .collect()
+}
+
+/// True when a filesystem on `disk` is mounted with online discard.
+fn mounted_with_discard(disk: &str) -> bool {
+    read_mounts().unwrap_or_default().iter().any(|m| {
+        m.options.iter().any(|o| o == "discard")
+            && std::fs::canonicalize(&m.device)
+                .ok()
+                .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+                .is_some_and(|block| disks_of(&block, 0).iter().any(|d| d == disk))
+    })
+}
+
+fn fstrim_scheduled() -> bool {
+    Path::new(FSTRIM_STAMP).exists()
+        || Path::new("/etc/systemd/system/timers.target.wants/fstrim.timer").exists()
+        || Path::new("/etc/cron.weekly/fstrim").exists()
+}
+
+fn describe(q: &QueueInfo) -> String {
+    let mut parts = vec![
+        format!("scheduler {}", q.scheduler.as_deref().unwrap_or("?")),
+        (if q.rotational { "rotational" } else { "non-rotational" }).to_string(),
+    ];
+    if let Some(n) = q.nr_requests {
+        parts.push(format!("nr_requests {}", n));
+    }
+    if let Some(ra) = q.read_ahead_kb {
+        parts.push(format!("read-ahead {} KiB", ra));
+    }
+    parts.push(format!("discard {}", if q.discard { "yes" } else { "no" }));
+    if let Some(cache) = &q.write_cache {
+        parts.push(format!("cache {}", cache));
+    }
+    parts.push(format!("blocks {}/{}", q.logical_block, q.physical_block));
+    parts.join(", ")
+}
+
+/// Add a queue summary per disk and findings for common misconfigurations.
+pub fn analyze(report: &mut DiagnosticReport, device_filter: Option<&str>) {
+    let trim_scheduled = fstrim_scheduled();
+    for q in read_queues() {
+        if device_filter.is_some_and(|d| !q.name.contains(d)) {
+            continue;
+        }
+        let dev = format!("/dev/{}", q.name);
+        report.add_metric(Metric {
+            name: format!("{} queue", q.name),
+            value: MetricValue::Text(describe(&q)),
+            unit: None,
+            threshold: None,
+        });
+
+        let scheduler = q.scheduler.as_deref().unwrap_or("none");
+        if q.name.starts_with("nvme") && scheduler == "bfq" {
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "scheduler".into(),
+                message: format!("{} is an NVMe drive using the bfq scheduler", dev),
+                details: Some(
+                    "bfq's per-request bookkeeping caps NVMe throughput and adds CPU time; \
+                     multi-queue NVMe hardware schedules well on its own."
+                        .into(),
+                ),
+            });
+            report.add_recommendation(Recommendation {
+                priority: 2,
+                action: format!("Switch {} to the none scheduler", dev),
+                command: Some(format!("echo none | sudo tee /sys/block/{}/queue/scheduler", q.name)),
+                explanation: "Make it persistent with a udev rule setting ATTR{queue/scheduler}.".into(),
+            });
+        } else if q.rotational && scheduler == "none" && !q.available_schedulers.is_empty() {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "scheduler".into(),
+                message: format!("{} is a rotational disk without an I/O scheduler", dev),
+                details: Some("Without request sorting, mixed workloads seek far more than needed.".into()),
+            });
+            let better = if q.available_schedulers.iter().any(|s| s == "mq-deadline") {
+                "mq-deadline"
+            } else {
+                "bfq"
+            };
+            report.add_recommendation(Recommendation {
+                priority: 3,
+                action: format!("Use {} on {}", better, dev),
+                command: Some(format!("echo {} | sudo tee /sys/block/{}/queue/scheduler", better, q.name)),
+                explanation: "Virtual disks reporting rotational=1 can ignore this.".into(),
+            });
+        }
+
+        if q.rotational && q.read_ahead_kb == Some(0) {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "readahead".into(),
+                message: format!("{} has read-ahead disabled", dev),
+                details: Some("Sequential reads from a spinning disk become seek-bound.".into()),
+            });
+        }
+
+        if !q.rotational && q.discard && !trim_scheduled && !mounted_with_discard(&q.name) {
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "discard".into(),
+                message: format!("{} supports TRIM but is never trimmed", dev),
+                details: Some(
+                    "Neither fstrim.timer nor the discard mount option is in use. Without TRIM the \
+                     SSD cannot tell free blocks apart, so write speed and endurance degrade as it fills."
+                        .into(),
+                ),
+            });
+            report.add_recommendation(Recommendation {
+                priority: 2,
+                action: "Enable periodic TRIM".into(),
+                command: Some("sudo systemctl enable --now fstrim.timer".into()),
+                explanation: "A weekly batched fstrim is preferred over the discard mount option.".into(),
+            });
+        }
+
+        let align = q.physical_block.max(q.logical_block);
+        for part in &q.partitions {
+            let misaligned = part.alignment_offset != 0 || (part.start * 512) % align != 0;
+            if !misaligned {
+                continue;
+            }
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "alignment".into(),
+                message: format!(
+                    "Partition /dev/{} starts at sector {}, not aligned to {}-byte physical blocks",
+                    part.name, part.start, align
+                ),
+                details: Some(
+                    "Every write straddling two physical blocks becomes a read-modify-write; typical \
+                     result is a 2-10x slowdown for small writes. Usually left over from old tools \
+                     starting partitions at sector 63."
+                        .into(),
+                ),
+            });
+            report.add_recommendation(Recommendation {
+                priority: 3,
+                action: format!("Re-create /dev/{} on a 1 MiB boundary", part.name),
+                command: Some(format!(
+                    "sudo parted /dev/{} align-check optimal {}",
+                    q.name,
+                    part.name.trim_start_matches(&q.name).trim_start_matches('p')
+                )),
+                explanation: "Alignment cannot be fixed in place; back up, repartition and restore.".into(),
+            });
+        }
+    }
+}
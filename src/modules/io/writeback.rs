@@ -0,0 +1,444 @@
+//! Dirty page writeback: /proc/meminfo, vm.dirty_* sysctls and per-BDI writeback state.
+//!
+//! Per-BDI dirty and bandwidth figures come from debugfs (`/sys/kernel/debug/bdi`, root only);
+//! without it, disks with writes in flight and their write rate are sampled from /proc/diskstats,
+//! and only the system-wide backlog is known.
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use crate::utils::{format_bytes, format_duration, list_dir, parse_key_value_as, read_file_optional, read_first_line};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// Dirty + writeback below this is normal background noise.
+const BACKLOG_REPORT_BYTES: u64 = 256 * 1024 * 1024;
+/// A backlog taking longer than this to flush causes visible stalls (sync, umount, eject).
+const FLUSH_WARNING_SECS: f64 = 30.0;
+/// How long to sample diskstats when debugfs bandwidth estimates are unavailable.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// Suggested limits for systems writing to slow devices.
+const SUGGESTED_DIRTY_BYTES: u64 = 256 * 1024 * 1024;
+const SUGGESTED_BACKGROUND_BYTES: u64 = 64 * 1024 * 1024;
+
+/// The vm.dirty_* knobs; `*_bytes` take precedence over `*_ratio` when non-zero.
+#[derive(Debug, Clone, Default)]
+pub struct DirtySettings {
+    pub ratio: u64,
+    pub background_ratio: u64,
+    pub bytes: u64,
+    pub background_bytes: u64,
+    pub expire_centisecs: u64,
+}
+
+impl DirtySettings {
+    /// Point at which writers are throttled, given the dirtyable memory.
+    pub fn limit(&self, dirtyable: u64) -> u64 {
+        if self.bytes > 0 {
+            self.bytes
+        } else {
+            dirtyable / 100 * self.ratio
+        }
+    }
+
+    /// Point at which the flusher threads start background writeback.
+    pub fn background_limit(&self, dirtyable: u64) -> u64 {
+        if self.background_bytes > 0 {
+            self.background_bytes
+        } else {
+            dirtyable / 100 * self.background_ratio
+        }
+    }
+}
+
+/// Writeback state of one backing device (a disk or an NFS/FUSE mount).
+#[derive(Debug, Clone)]
+pub struct BdiState {
+    pub name: String,
+    pub label: String,
+    /// Dirty bytes waiting to be written (BdiReclaimable).
+    pub dirty: u64,
+    /// Bytes currently under writeback.
+    pub writeback: u64,
+    /// Estimated write bandwidth in bytes per second.
+    pub bandwidth: Option<u64>,
+    pub slow_media: bool,
+}
+
+impl BdiState {
+    pub fn backlog(&self) -> u64 {
+        self.dirty + self.writeback
+    }
+
+    pub fn flush_secs(&self) -> Option<f64> {
+        self.bandwidth
+            .filter(|b| *b > 0)
+            .map(|b| self.backlog() as f64 / b as f64)
+    }
+}
+
+fn read_sysctl(name: &str) -> u64 {
+    read_first_line(&Path::new("/proc/sys/vm").join(name))
+        .ok()
+        .flatten()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+pub fn dirty_settings() -> DirtySettings {
+    DirtySettings {
+        ratio: read_sysctl("dirty_ratio"),
+        background_ratio: read_sysctl("dirty_background_ratio"),
+        bytes: read_sysctl("dirty_bytes"),
+        background_bytes: read_sysctl("dirty_background_bytes"),
+        expire_centisecs: read_sysctl("dirty_expire_centisecs"),
+    }
+}
+
+/// /proc/meminfo values in bytes.
+fn meminfo() -> HashMap<String, u64> {
+    read_file_optional(Path::new("/proc/meminfo"))
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+        .lines()
+        .filter_map(parse_key_value_as::<u64>)
+        .map(|(k, kb)| (k.to_string(), kb * 1024))
+        .collect()
+}
+
+/// "maj:min" -> "mountpoint (fstype)" for mounts without a block device (NFS, FUSE, ...).
+fn anonymous_mounts() -> HashMap<String, String> {
+    read_file_optional(Path::new("/proc/self/mountinfo"))
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let sep = fields.iter().position(|f| *f == "-")?;
+            let dev = fields.get(2)?;
+            if !dev.starts_with("0:") {
+                return None;
+            }
+            Some((dev.to_string(), format!("{} ({})", fields.get(4)?, fields.get(sep + 1)?)))
+        })
+        .collect()
+}
+
+/// Kernel block device name for "maj:min", e.g. "8:16" -> "sdb".
+fn block_name(dev: &str) -> Option<String> {
+    std::fs::read_link(Path::new("/sys/dev/block").join(dev))
+        .ok()?
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+}
+
+/// USB sticks, SD cards and network filesystems are orders of magnitude slower than local disks.
+fn is_slow_media(block: Option<&str>, label: &str) -> bool {
+    match block {
+        Some(name) => {
+            let sys = Path::new("/sys/block").join(name);
+            let removable = read_first_line(&sys.join("removable")).ok().flatten().is_some_and(|s| s.trim() == "1");
+            let usb = std::fs::canonicalize(&sys).is_ok_and(|p| p.to_string_lossy().contains("/usb"));
+            removable || usb || name.starts_with("mmcblk")
+        }
+        None => label.contains("(nfs") || label.contains("(cifs") || label.contains("(smb") || label.contains("(fuse"),
+    }
+}
+
+fn label_for(dev: &str, mounts: &HashMap<String, String>) -> (Option<String>, String) {
+    let block = block_name(dev);
+    let label = match (&block, mounts.get(dev)) {
+        (Some(name), _) => name.clone(),
+        (None, Some(mount)) => mount.clone(),
+        (None, None) => dev.to_string(),
+    };
+    (block, label)
+}
+
+/// Per-BDI state from debugfs, or `None` when debugfs is not readable.
+fn bdi_from_debugfs(mounts: &HashMap<String, String>) -> Option<Vec<BdiState>> {
+    let dirs = list_dir(Path::new("/sys/kernel/debug/bdi")).ok()?;
+    let mut out = Vec::new();
+    for dir in dirs {
+        let Some(content) = read_file_optional(&dir.join("stats")).ok().flatten() else {
+            continue;
+        };
+        let stats: HashMap<&str, u64> = content.lines().filter_map(parse_key_value_as::<u64>).collect();
+        let name = dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let (block, label) = label_for(&name, mounts);
+        out.push(BdiState {
+            slow_media: is_slow_media(block.as_deref(), &label),
+            name,
+            label,
+            dirty: stats.get("BdiReclaimable").copied().unwrap_or(0) * 1024,
+            writeback: stats.get("BdiWriteback").copied().unwrap_or(0) * 1024,
+            bandwidth: stats.get("BdiWriteBandwidth").map(|kbps| kbps * 1024),
+        });
+    }
+    Some(out)
+}
+
+/// "maj:min" -> (device, sectors written, writes in flight) per disk from /proc/diskstats.
+fn disk_writes() -> HashMap<String, (String, u64, u64)> {
+    read_file_optional(Path::new("/proc/diskstats"))
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 14 {
+                return None;
+            }
+            Some((
+                format!("{}:{}", parts[0], parts[1]),
+                (parts[2].to_string(), parts[9].parse().ok()?, parts[11].parse().ok()?),
+            ))
+        })
+        .collect()
+}
+
+/// A whole disk with writes in flight, from the diskstats fallback.
+#[derive(Debug, Clone)]
+pub struct BusyDisk {
+    /// BDI name ("maj:min").
+    pub bdi: String,
+    pub name: String,
+    /// Measured write rate in bytes per second.
+    pub rate: u64,
+    pub slow_media: bool,
+}
+
+/// Fallback without debugfs: whole disks with writes in flight and their current write rate.
+/// The kernel does not expose dirty pages per device outside debugfs, so no per-device
+/// backlog is known here.
+async fn busy_disks() -> Vec<BusyDisk> {
+    let before = disk_writes();
+    let busy: Vec<&String> = before
+        .iter()
+        .filter(|(_, (name, _, inflight))| *inflight > 0 && Path::new("/sys/block").join(name).exists())
+        .map(|(dev, _)| dev)
+        .collect();
+    if busy.is_empty() {
+        return Vec::new();
+    }
+    tokio::time::sleep(SAMPLE_INTERVAL).await;
+    let after = disk_writes();
+    busy.into_iter()
+        .map(|dev| {
+            let (name, sectors, _) = &before[dev];
+            let written = after.get(dev).map(|a| a.1).unwrap_or(0).saturating_sub(*sectors);
+            BusyDisk {
+                bdi: dev.clone(),
+                name: name.clone(),
+                rate: written * 512 / SAMPLE_INTERVAL.as_secs().max(1),
+                slow_media: is_slow_media(Some(name), name),
+            }
+        })
+        .collect()
+}
+
+fn cap_device_recommendation(report: &mut DiagnosticReport, bdi: &str, label: &str) {
+    report.add_recommendation(Recommendation {
+        priority: 3,
+        action: format!("Cap the dirty cache for {} only", label),
+        command: Some(format!(
+            "echo 1 | sudo tee /sys/class/bdi/{0}/strict_limit && echo {1} | sudo tee /sys/class/bdi/{0}/max_bytes",
+            bdi, SUGGESTED_DIRTY_BYTES
+        )),
+        explanation: "A per-device limit keeps fast disks unaffected (max_bytes needs Linux 6.2; \
+                      use max_ratio on older kernels)."
+            .into(),
+    });
+}
+
+fn stall_details(slow_media: bool, limit: u64) -> String {
+    format!(
+        "{}Page cache absorbs writes at memory speed, so copies appear to finish instantly; \
+         sync, umount or eject then block until the device catches up, and processes \
+         writing to it stall once the dirty limit ({}) is reached.",
+        if slow_media { "This is slow or removable media. " } else { "" },
+        format_bytes(limit)
+    )
+}
+
+/// Per-device findings from debugfs. Returns whether any device is slow to flush, or `None`
+/// when no device holds part of the backlog.
+fn report_bdis(
+    report: &mut DiagnosticReport,
+    mut bdis: Vec<BdiState>,
+    device_filter: Option<&str>,
+    limit: u64,
+) -> Option<bool> {
+    if let Some(dev) = device_filter {
+        bdis.retain(|b| b.label.contains(dev));
+    }
+    bdis.retain(|b| b.backlog() > 0);
+    bdis.sort_by_key(|b| std::cmp::Reverse(b.backlog()));
+    if bdis.is_empty() {
+        return None;
+    }
+
+    let mut stalled = false;
+    for bdi in &bdis {
+        let flush = bdi.flush_secs();
+        let slow = flush.is_some_and(|s| s >= FLUSH_WARNING_SECS);
+        if !slow && bdi.backlog() < BACKLOG_REPORT_BYTES {
+            continue;
+        }
+        stalled |= slow;
+        let rate = bdi
+            .bandwidth
+            .map(|b| format!(" at {}/s", format_bytes(b)))
+            .unwrap_or_default();
+        report.add_finding(Finding {
+            severity: if slow { Severity::Warning } else { Severity::Info },
+            category: "writeback".into(),
+            message: match flush {
+                Some(secs) => format!(
+                    "{} queued for {}{}: about {} to flush",
+                    format_bytes(bdi.backlog()),
+                    bdi.label,
+                    rate,
+                    format_duration(Duration::from_secs(secs.round() as u64))
+                ),
+                None => format!("{} queued for {}", format_bytes(bdi.backlog()), bdi.label),
+            },
+            details: Some(stall_details(bdi.slow_media, limit)),
+        });
+        if bdi.slow_media {
+            cap_device_recommendation(report, &bdi.name, &bdi.label);
+        }
+    }
+    Some(stalled)
+}
+
+/// Findings from the diskstats fallback: which disks are being written and a flush estimate for
+/// the system-wide backlog at their combined rate. Returns whether that estimate is slow, or
+/// `None` when no disk has writes in flight.
+fn report_busy_disks(
+    report: &mut DiagnosticReport,
+    mut disks: Vec<BusyDisk>,
+    device_filter: Option<&str>,
+    backlog: u64,
+    limit: u64,
+) -> Option<bool> {
+    if let Some(dev) = device_filter {
+        disks.retain(|d| d.name.contains(dev));
+    }
+    disks.sort_by_key(|d| std::cmp::Reverse(d.rate));
+    if disks.is_empty() {
+        return None;
+    }
+
+    let total_rate: u64 = disks.iter().map(|d| d.rate).sum();
+    let flush = (total_rate > 0).then(|| backlog as f64 / total_rate as f64);
+    let slow = flush.is_some_and(|s| s >= FLUSH_WARNING_SECS);
+    let names = disks
+        .iter()
+        .map(|d| format!("{} ({}/s)", d.name, format_bytes(d.rate)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    report.add_finding(Finding {
+        severity: if slow { Severity::Warning } else { Severity::Info },
+        category: "writeback".into(),
+        message: match flush {
+            Some(secs) => format!(
+                "{} of dirty data with writes in flight to {}: roughly {} to flush at the current rate",
+                format_bytes(backlog),
+                names,
+                format_duration(Duration::from_secs(secs.round() as u64))
+            ),
+            None => format!("{} of dirty data with writes in flight to {}", format_bytes(backlog), names),
+        },
+        details: Some(format!(
+            "The backlog is system-wide: without debugfs the kernel does not say how much of it \
+             belongs to each device, so the estimate assumes all of it goes to these disks. {}",
+            stall_details(disks.iter().any(|d| d.slow_media), limit)
+        )),
+    });
+    for disk in disks.iter().filter(|d| d.slow_media) {
+        cap_device_recommendation(report, &disk.bdi, &disk.name);
+    }
+    Some(slow)
+}
+
+/// Report the dirty page backlog, the thresholds and which devices it is waiting on.
+pub async fn analyze(report: &mut DiagnosticReport, device_filter: Option<&str>) {
+    let mem = meminfo();
+    let get = |k: &str| mem.get(k).copied().unwrap_or(0);
+    let dirty = get("Dirty");
+    let writeback = get("Writeback");
+    let settings = dirty_settings();
+    // The kernel's "dirtyable" memory is free plus reclaimable page cache; MemAvailable is close.
+    let dirtyable = get("MemAvailable");
+    let limit = settings.limit(dirtyable);
+    let background = settings.background_limit(dirtyable);
+
+    report.add_metric(Metric {
+        name: "Dirty pages".into(),
+        value: MetricValue::Text(format_bytes(dirty)),
+        unit: None,
+        threshold: None,
+    });
+    report.add_metric(Metric {
+        name: "Under writeback".into(),
+        value: MetricValue::Text(format_bytes(writeback)),
+        unit: None,
+        threshold: None,
+    });
+    report.add_metric(Metric {
+        name: "Dirty limit (background / throttle)".into(),
+        value: MetricValue::Text(format!(
+            "{} / {}{}",
+            format_bytes(background),
+            format_bytes(limit),
+            if settings.bytes > 0 { "" } else { " (ratio of available memory)" }
+        )),
+        unit: None,
+        threshold: None,
+    });
+
+    let backlog = dirty + writeback;
+    if backlog < BACKLOG_REPORT_BYTES {
+        return;
+    }
+
+    let mounts = anonymous_mounts();
+    let attributed = match bdi_from_debugfs(&mounts) {
+        Some(bdis) => report_bdis(report, bdis, device_filter, limit),
+        None => report_busy_disks(report, busy_disks().await, device_filter, backlog, limit),
+    };
+    let stalled = attributed == Some(true);
+
+    if stalled || backlog >= limit / 2 {
+        if attributed.is_none() {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "writeback".into(),
+                message: format!("{} of dirty data waiting for writeback", format_bytes(backlog)),
+                details: Some(format!(
+                    "Writers are throttled at {}; pages older than {}s are flushed. Run as root with \
+                     debugfs mounted to see which device it is queued for.",
+                    format_bytes(limit),
+                    settings.expire_centisecs / 100
+                )),
+            });
+        }
+        if settings.bytes == 0 || settings.bytes > SUGGESTED_DIRTY_BYTES {
+            report.add_recommendation(Recommendation {
+                priority: 2,
+                action: "Lower the global dirty page limits so writeback starts earlier".into(),
+                command: Some(format!(
+                    "sudo sysctl -w vm.dirty_background_bytes={} vm.dirty_bytes={}",
+                    SUGGESTED_BACKGROUND_BYTES, SUGGESTED_DIRTY_BYTES
+                )),
+                explanation: "Ratio-based limits scale with RAM and let gigabytes pile up for a slow \
+                              device; fixed byte limits keep the backlog short. Persist in /etc/sysctl.d/."
+                    .into(),
+            });
+        }
+    }
+}
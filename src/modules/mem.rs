@@ -127,7 +127,7 @@ impl DiagnosticModule for MemModule {
         let mut sys = System::new_all();
         sys.refresh_all();
         let mut processes: Vec<_> = sys.processes().iter().collect();
-        processes.sort_by(|a, b| b.1.memory().cmp(&a.1.memory()));
+        processes.sort_by_key(|b| std::cmp::Reverse(b.1.memory()));
         let top_n = config.top_n;
         for (pid, proc_ref) in processes.into_iter().take(top_n) {
             let rss = proc_ref.memory();
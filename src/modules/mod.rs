@@ -3,15 +3,20 @@
 mod batt;
 mod boot;
 mod cpu;
-mod disk;
+pub mod disk;
 mod fan;
+pub mod firewall;
 mod gpu;
 mod io;
 mod mem;
 mod mount;
-mod net;
+pub mod net;
+mod port;
+pub mod raid;
 mod sleep;
+pub mod smart;
 mod temp;
+pub mod time;
 mod usb;
 
 pub use batt::module as batt_module;
@@ -19,13 +24,18 @@ pub use boot::module as boot_module;
 pub use cpu::module as cpu_module;
 pub use disk::module as disk_module;
 pub use fan::module as fan_module;
+pub use firewall::module as firewall_module;
 pub use gpu::module as gpu_module;
 pub use io::module as io_module;
 pub use mem::module as mem_module;
 pub use mount::module as mount_module;
 pub use net::module as net_module;
+pub use port::module as port_module;
+pub use raid::module as raid_module;
 pub use sleep::module as sleep_module;
+pub use smart::module as smart_module;
 pub use temp::module as temp_module;
+pub use time::module as time_module;
 pub use usb::module as usb_module;
 
 use crate::core::traits::DiagnosticModule;
@@ -39,7 +49,12 @@ pub fn get_module(name: &str) -> Option<Arc<dyn DiagnosticModule>> {
         "mem" => Some(mem_module()),
         "disk" => Some(disk_module()),
         "io" => Some(io_module()),
+        "smart" => Some(smart_module()),
+        "raid" => Some(raid_module()),
         "net" => Some(net_module()),
+        "port" => Some(port_module()),
+        "firewall" => Some(firewall_module()),
+        "time" => Some(time_module()),
         "fan" => Some(fan_module()),
         "temp" => Some(temp_module()),
         "gpu" => Some(gpu_module()),
@@ -59,7 +74,12 @@ pub fn all_modules() -> Vec<Arc<dyn DiagnosticModule>> {
         mem_module(),
         disk_module(),
         io_module(),
+        smart_module(),
+        raid_module(),
         net_module(),
+        port_module(),
+        firewall_module(),
+        time_module(),
         fan_module(),
         temp_module(),
         gpu_module(),
@@ -0,0 +1,277 @@
+//! Per-process bandwidth: TCP byte counters per socket (`bytes_acked`/`bytes_received` from
+//! tcp_info, via `ss`'s sock_diag dump) sampled twice and summed per owning process. Falls
+//! back to systemd's per-cgroup IP accounting when `ss` is not available.
+
+use super::sockets;
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use crate::utils::{command_exists, format_bytes, process_name, run_cmd};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Rate at which the top process is called out in a finding.
+const BUSY_RATE: f64 = 1024.0 * 1024.0;
+
+/// Cumulative byte counters of one TCP socket.
+#[derive(Debug, Clone)]
+pub struct TcpBytes {
+    pub local: String,
+    pub peer: String,
+    pub inode: u64,
+    /// First process listed in `users:(...)`, when `ss -p` could see it.
+    pub pid: Option<u32>,
+    pub acked: u64,
+    pub received: u64,
+}
+
+impl TcpBytes {
+    fn key(&self) -> (u64, &str, &str) {
+        (self.inode, &self.local, &self.peer)
+    }
+
+    /// Local IPC over 127.0.0.1/::1 never leaves the machine.
+    fn is_loopback(&self) -> bool {
+        let host = self.peer.rsplit_once(':').map_or(self.peer.as_str(), |(h, _)| h);
+        match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(IpAddr::V6(v6)) => v6.is_loopback() || v6.to_ipv4_mapped().is_some_and(|v4| v4.is_loopback()),
+            Ok(ip) => ip.is_loopback(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Parse `ss -tinpHe`: a socket line followed by an indented tcp_info line per socket.
+pub fn parse_ss(output: &str) -> Vec<TcpBytes> {
+    let mut out: Vec<TcpBytes> = Vec::new();
+    for line in output.lines() {
+        if line.starts_with(char::is_whitespace) {
+            let Some(sock) = out.last_mut() else {
+                continue;
+            };
+            for token in line.split_whitespace() {
+                if let Some(v) = token.strip_prefix("bytes_acked:") {
+                    sock.acked = v.parse().unwrap_or(0);
+                } else if let Some(v) = token.strip_prefix("bytes_received:") {
+                    sock.received = v.parse().unwrap_or(0);
+                }
+            }
+            continue;
+        }
+        let f: Vec<&str> = line.split_whitespace().collect();
+        if f.len() < 5 {
+            continue;
+        }
+        let field = |prefix: &str| f[5..].iter().find_map(|t| t.strip_prefix(prefix));
+        out.push(TcpBytes {
+            local: f[3].to_string(),
+            peer: f[4].to_string(),
+            inode: field("ino:").and_then(|v| v.parse().ok()).unwrap_or(0),
+            pid: line
+                .split_once("pid=")
+                .and_then(|(_, rest)| rest.split(|c: char| !c.is_ascii_digit()).next())
+                .and_then(|p| p.parse().ok()),
+            acked: 0,
+            received: 0,
+        });
+    }
+    out
+}
+
+fn read_tcp_bytes() -> Option<Vec<TcpBytes>> {
+    run_cmd(&["ss", "-tinpHe"]).ok().map(|o| parse_ss(&o))
+}
+
+/// Traffic attributed to one process (or cgroup) over the sample.
+#[derive(Debug, Clone, Default)]
+pub struct Usage {
+    pub name: String,
+    pub rx: u64,
+    pub tx: u64,
+}
+
+/// Byte deltas per PID, loopback excluded. Sockets opened during the interval count in full;
+/// closed ones are lost.
+pub fn per_process(before: &[TcpBytes], after: &[TcpBytes], owners: &HashMap<u64, Vec<u32>>) -> HashMap<u32, (u64, u64)> {
+    let earlier: HashMap<_, &TcpBytes> = before.iter().map(|s| (s.key(), s)).collect();
+    let mut usage: HashMap<u32, (u64, u64)> = HashMap::new();
+    for sock in after.iter().filter(|s| !s.is_loopback()) {
+        let (rx, tx) = match earlier.get(&sock.key()) {
+            Some(prev) => (sock.received.saturating_sub(prev.received), sock.acked.saturating_sub(prev.acked)),
+            None => (sock.received, sock.acked),
+        };
+        if rx == 0 && tx == 0 {
+            continue;
+        }
+        let pid = sock.pid.or_else(|| owners.get(&sock.inode).and_then(|p| p.first().copied()));
+        let Some(pid) = pid else {
+            continue;
+        };
+        let entry = usage.entry(pid).or_default();
+        entry.0 += rx;
+        entry.1 += tx;
+    }
+    usage
+}
+
+/// Parse `systemctl show -p Id -p IPIngressBytes -p IPEgressBytes <units>`: blank-line
+/// separated blocks. Units without IPAccounting report "[not set]" or u64::MAX.
+pub fn parse_ip_accounting(output: &str) -> HashMap<String, (u64, u64)> {
+    let mut out = HashMap::new();
+    for block in output.split("\n\n") {
+        let mut id = None;
+        let (mut ingress, mut egress) = (None, None);
+        for line in block.lines() {
+            let counter = |v: &str| v.parse::<u64>().ok().filter(|b| *b != u64::MAX);
+            match line.split_once('=') {
+                Some(("Id", v)) => id = Some(v.to_string()),
+                Some(("IPIngressBytes", v)) => ingress = counter(v),
+                Some(("IPEgressBytes", v)) => egress = counter(v),
+                _ => {}
+            }
+        }
+        if let (Some(id), Some(rx), Some(tx)) = (id, ingress, egress) {
+            out.insert(id, (rx, tx));
+        }
+    }
+    out
+}
+
+fn read_ip_accounting() -> HashMap<String, (u64, u64)> {
+    let Ok(units) = run_cmd(&[
+        "systemctl",
+        "list-units",
+        "--type=service,scope",
+        "--state=running",
+        "--plain",
+        "--no-legend",
+        "--no-pager",
+    ]) else {
+        return HashMap::new();
+    };
+    let units: Vec<&str> = units.lines().filter_map(|l| l.split_whitespace().next()).collect();
+    if units.is_empty() {
+        return HashMap::new();
+    }
+    let mut args = vec!["systemctl", "show", "-p", "Id", "-p", "IPIngressBytes", "-p", "IPEgressBytes"];
+    args.extend(units);
+    run_cmd(&args).map(|o| parse_ip_accounting(&o)).unwrap_or_default()
+}
+
+fn rate(bytes: u64, secs: f64) -> String {
+    format!("{}/s", format_bytes((bytes as f64 / secs) as u64))
+}
+
+fn report_top(report: &mut DiagnosticReport, mut usage: Vec<Usage>, secs: f64, top_n: usize, source: &str) {
+    usage.sort_by_key(|u| std::cmp::Reverse(u.rx + u.tx));
+    let total: u64 = usage.iter().map(|u| u.rx + u.tx).sum();
+    for u in usage.iter().take(top_n) {
+        report.add_metric(Metric {
+            name: format!("Bandwidth {}", u.name),
+            value: MetricValue::Text(format!("rx {}, tx {}", rate(u.rx, secs), rate(u.tx, secs))),
+            unit: None,
+            threshold: None,
+        });
+    }
+    let Some(top) = usage.first() else {
+        report.add_metric(Metric {
+            name: "Bandwidth".into(),
+            value: MetricValue::Text(format!("no {} traffic during the {:.0} s sample", source, secs)),
+            unit: None,
+            threshold: None,
+        });
+        return;
+    };
+    let top_rate = (top.rx + top.tx) as f64 / secs;
+    if top_rate >= BUSY_RATE {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "bandwidth".into(),
+            message: format!(
+                "{} is the top network user: {} down, {} up ({:.0}% of attributed {} traffic)",
+                top.name,
+                rate(top.rx, secs),
+                rate(top.tx, secs),
+                (top.rx + top.tx) as f64 * 100.0 / total as f64,
+                source
+            ),
+            details: Some(
+                "If it saturates the uplink, other connections see high latency (bufferbloat) even \
+                 though they barely transfer anything."
+                    .into(),
+            ),
+        });
+    }
+}
+
+/// Sample per-socket TCP counters over `interval` and report the top `top_n` processes.
+pub async fn analyze(report: &mut DiagnosticReport, interval: Duration, top_n: usize) {
+    if let Some(before) = command_exists("ss").then(read_tcp_bytes).flatten() {
+        let start = Instant::now();
+        tokio::time::sleep(interval).await;
+        let after = read_tcp_bytes().unwrap_or_default();
+        let secs = start.elapsed().as_secs_f64();
+        // Only needed for sockets whose owner ss could not name (other users without root).
+        let owners = if after.iter().any(|s| s.pid.is_none()) {
+            sockets::socket_owners()
+        } else {
+            HashMap::new()
+        };
+        let usage = per_process(&before, &after, &owners)
+            .into_iter()
+            .map(|(pid, (rx, tx))| Usage {
+                name: format!("{} (PID {})", process_name(pid).unwrap_or_else(|_| "?".into()), pid),
+                rx,
+                tx,
+            })
+            .collect();
+        report_top(report, usage, secs, top_n, "TCP");
+        report.add_recommendation(Recommendation {
+            priority: 3,
+            action: "Check UDP traffic (QUIC, video calls, games) with nethogs".into(),
+            command: Some("sudo nethogs".into()),
+            explanation: "The kernel keeps per-socket byte counters only for TCP; nethogs captures packets instead.".into(),
+        });
+        return;
+    }
+
+    // Per-cgroup accounting for services and scopes with IPAccounting=yes.
+    let before = read_ip_accounting();
+    if before.is_empty() {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "bandwidth".into(),
+            message: "No per-process traffic counters available".into(),
+            details: Some(
+                "`ss` from iproute2 provides per-socket TCP counters; systemd units report their \
+                 traffic when IPAccounting is enabled."
+                    .into(),
+            ),
+        });
+        report.add_recommendation(Recommendation {
+            priority: 3,
+            action: "Enable IP accounting for all services".into(),
+            command: Some(
+                "sudo mkdir -p /etc/systemd/system.conf.d && \
+                 printf '[Manager]\\nDefaultIPAccounting=yes\\n' | \
+                 sudo tee /etc/systemd/system.conf.d/ipaccounting.conf"
+                    .into(),
+            ),
+            explanation: "Takes effect for units started after `systemctl daemon-reexec`.".into(),
+        });
+        return;
+    }
+    let start = Instant::now();
+    tokio::time::sleep(interval).await;
+    let after = read_ip_accounting();
+    let secs = start.elapsed().as_secs_f64();
+    let usage = after
+        .into_iter()
+        .filter_map(|(unit, (rx, tx))| {
+            let (rx0, tx0) = before.get(&unit).copied().unwrap_or((0, 0));
+            Some(Usage { name: unit, rx: rx.checked_sub(rx0)?, tx: tx.checked_sub(tx0)? })
+        })
+        .filter(|u| u.rx + u.tx > 0)
+        .collect();
+    report_top(report, usage, secs, top_n, "cgroup");
+}
@@ -0,0 +1,613 @@
+//! Resolver diagnosis: /etc/resolv.conf and nsswitch.conf, systemd-resolved stub mode,
+//! per-nameserver A/AAAA timing with direct UDP queries, and system resolution via getaddrinfo.
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation, Threshold};
+use crate::core::severity::Severity;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+/// Upstream servers of systemd-resolved, written next to the stub file.
+const RESOLVED_UPSTREAM: &str = "/run/systemd/resolve/resolv.conf";
+const NSSWITCH: &str = "/etc/nsswitch.conf";
+const RESOLVED_STUB: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 53));
+/// glibc only ever uses the first MAXNS nameservers.
+const MAXNS: usize = 3;
+/// Name resolved through getaddrinfo when the target is an IP address.
+const FORWARD_PROBE: &str = "google.com";
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+const SLOW_MS: f64 = 200.0;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_AAAA: u16 = 28;
+
+/// The parts of resolv.conf(5) that change lookup behavior.
+#[derive(Debug, Clone)]
+pub struct ResolvConf {
+    pub nameservers: Vec<IpAddr>,
+    pub search: Vec<String>,
+    pub ndots: usize,
+    /// Per-server timeout in seconds and rounds over the server list.
+    pub timeout: u64,
+    pub attempts: u64,
+    pub options: Vec<String>,
+}
+
+impl Default for ResolvConf {
+    fn default() -> Self {
+        Self { nameservers: Vec::new(), search: Vec::new(), ndots: 1, timeout: 5, attempts: 2, options: Vec::new() }
+    }
+}
+
+pub fn parse_resolv_conf(content: &str) -> ResolvConf {
+    let mut conf = ResolvConf::default();
+    for line in content.lines() {
+        let line = line.split(['#', ';']).next().unwrap_or("").trim();
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("nameserver") => {
+                // Link-local IPv6 servers carry a %scope suffix.
+                if let Some(ip) = words.next().and_then(|w| w.split('%').next()?.parse().ok()) {
+                    conf.nameservers.push(ip);
+                }
+            }
+            // The last of `domain` and `search` wins.
+            Some("search") | Some("domain") => {
+                conf.search = words.map(|w| w.trim_end_matches('.').to_string()).collect();
+            }
+            Some("options") => {
+                for opt in words {
+                    if let Some(n) = opt.strip_prefix("ndots:").and_then(|n| n.parse().ok()) {
+                        conf.ndots = n;
+                    } else if let Some(n) = opt.strip_prefix("timeout:").and_then(|n| n.parse().ok()) {
+                        conf.timeout = n;
+                    } else if let Some(n) = opt.strip_prefix("attempts:").and_then(|n| n.parse().ok()) {
+                        conf.attempts = n;
+                    }
+                    conf.options.push(opt.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    conf
+}
+
+/// Sources of the `hosts:` database in order, without `[STATUS=action]` items.
+pub fn parse_nsswitch_hosts(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("hosts:"))
+        .map(|rest| {
+            rest.split('#')
+                .next()
+                .unwrap_or("")
+                .split_whitespace()
+                .filter(|w| !w.starts_with('[') && !w.ends_with(']'))
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Names the stub resolver tries for `name`, in order (resolv.conf(5) ndots rule).
+pub fn candidates(name: &str, conf: &ResolvConf) -> Vec<String> {
+    if let Some(absolute) = name.strip_suffix('.') {
+        return vec![absolute.to_string()];
+    }
+    let searched = conf.search.iter().map(|s| format!("{}.{}", name, s));
+    if name.matches('.').count() >= conf.ndots {
+        std::iter::once(name.to_string()).chain(searched).collect()
+    } else {
+        searched.chain(std::iter::once(name.to_string())).collect()
+    }
+}
+
+/// in-addr.arpa / ip6.arpa name for a reverse lookup.
+fn ptr_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => {
+            let o = v4.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+        }
+        IpAddr::V6(v6) => {
+            let nibbles: Vec<String> = v6
+                .octets()
+                .iter()
+                .rev()
+                .flat_map(|b| [b & 0xf, b >> 4])
+                .map(|n| format!("{:x}", n))
+                .collect();
+            format!("{}.ip6.arpa", nibbles.join("."))
+        }
+    }
+}
+
+fn build_query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+    // Header: id, flags (RD), QDCOUNT=1, AN/NS/ARCOUNT=0.
+    let mut pkt = Vec::with_capacity(name.len() + 18);
+    pkt.extend_from_slice(&id.to_be_bytes());
+    pkt.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        pkt.push(label.len().min(63) as u8);
+        pkt.extend_from_slice(&label.as_bytes()[..label.len().min(63)]);
+    }
+    pkt.push(0);
+    pkt.extend_from_slice(&qtype.to_be_bytes());
+    pkt.extend_from_slice(&1u16.to_be_bytes());
+    pkt
+}
+
+/// Outcome of one direct query.
+#[derive(Debug, Clone)]
+pub enum QueryOutcome {
+    Answer { ms: f64, rcode: u8, answers: u16 },
+    Timeout,
+    Error(String),
+}
+
+impl QueryOutcome {
+    fn resolved(&self) -> bool {
+        matches!(self, QueryOutcome::Answer { rcode: 0, answers, .. } if *answers > 0)
+    }
+}
+
+fn rcode_name(rcode: u8) -> &'static str {
+    match rcode {
+        0 => "NOERROR",
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        _ => "error",
+    }
+}
+
+pub async fn query(server: IpAddr, name: &str, qtype: u16) -> QueryOutcome {
+    let id = (std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0)
+        ^ std::process::id()
+        ^ u32::from(qtype)) as u16;
+    let bind: SocketAddr = if server.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" }.parse().expect("valid address");
+    let exchange = async {
+        let sock = UdpSocket::bind(bind).await?;
+        sock.connect((server, 53)).await?;
+        let start = Instant::now();
+        sock.send(&build_query(id, name, qtype)).await?;
+        let mut buf = [0u8; 1500];
+        loop {
+            let n = sock.recv(&mut buf).await?;
+            if n >= 12 && u16::from_be_bytes([buf[0], buf[1]]) == id {
+                return Ok::<_, std::io::Error>(QueryOutcome::Answer {
+                    ms: start.elapsed().as_secs_f64() * 1000.0,
+                    rcode: buf[3] & 0x0f,
+                    answers: u16::from_be_bytes([buf[6], buf[7]]),
+                });
+            }
+        }
+    };
+    match tokio::time::timeout(QUERY_TIMEOUT, exchange).await {
+        Ok(Ok(outcome)) => outcome,
+        Ok(Err(e)) => QueryOutcome::Error(e.to_string()),
+        Err(_) => QueryOutcome::Timeout,
+    }
+}
+
+/// True when /etc/resolv.conf hands every lookup to the systemd-resolved stub listener.
+fn stub_mode(conf: &ResolvConf) -> bool {
+    conf.nameservers == [RESOLVED_STUB]
+        || std::fs::read_link(RESOLV_CONF)
+            .is_ok_and(|t| t.to_string_lossy().contains("systemd/resolve/stub-resolv.conf"))
+}
+
+fn add_ms_metric(report: &mut DiagnosticReport, name: String, outcome: &QueryOutcome) {
+    let value = match outcome {
+        QueryOutcome::Answer { ms, rcode, .. } if *rcode == 0 || *rcode == 3 => MetricValue::Float(*ms),
+        QueryOutcome::Answer { rcode, .. } => MetricValue::Text(rcode_name(*rcode).into()),
+        QueryOutcome::Timeout => MetricValue::Text("timeout".into()),
+        QueryOutcome::Error(_) => MetricValue::Text("error".into()),
+    };
+    let unit = matches!(value, MetricValue::Float(_)).then(|| "ms".to_string());
+    report.add_metric(Metric {
+        name,
+        value,
+        unit,
+        threshold: Some(Threshold { warning: SLOW_MS, critical: 1000.0 }),
+    });
+}
+
+/// Time every nameserver for `probe` and report unreachable, failing or slow ones.
+/// Returns how many servers answered at all and how many resolved `probe`.
+async fn time_servers(
+    report: &mut DiagnosticReport,
+    servers: &[IpAddr],
+    probe: &str,
+    reverse: bool,
+    conf: &ResolvConf,
+    stub: bool,
+) -> (usize, usize) {
+    let handles: Vec<_> = servers
+        .iter()
+        .map(|&server| {
+            let probe = probe.to_string();
+            tokio::spawn(async move {
+                if reverse {
+                    (query(server, &probe, TYPE_PTR).await, None)
+                } else {
+                    let (a, aaaa) = tokio::join!(query(server, &probe, TYPE_A), query(server, &probe, TYPE_AAAA));
+                    (a, Some(aaaa))
+                }
+            })
+        })
+        .collect();
+
+    let (mut answering, mut resolving) = (0, 0);
+    for (&server, handle) in servers.iter().zip(handles) {
+        let Ok((primary, aaaa)) = handle.await else {
+            continue;
+        };
+        let qtype = if reverse { "PTR" } else { "A" };
+        add_ms_metric(report, format!("DNS {} ({})", server, qtype), &primary);
+        if let Some(aaaa) = &aaaa {
+            add_ms_metric(report, format!("DNS {} (AAAA)", server), aaaa);
+        }
+        match &primary {
+            QueryOutcome::Timeout => report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "dns".into(),
+                message: format!("Nameserver {} does not answer ({} s timeout)", server, QUERY_TIMEOUT.as_secs()),
+                details: Some(format!(
+                    "Every lookup that reaches this server waits timeout:{} s before the resolver moves on \
+                     (attempts:{}). Remove it from the configuration or fix the path to it.",
+                    conf.timeout, conf.attempts
+                )),
+            }),
+            QueryOutcome::Error(e) => report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "dns".into(),
+                message: format!("Nameserver {} is unreachable: {}", server, e),
+                details: Some("\"Connection refused\" means nothing listens on port 53 there.".into()),
+            }),
+            QueryOutcome::Answer { ms, rcode, .. } => {
+                answering += 1;
+                if primary.resolved() {
+                    resolving += 1;
+                }
+                if matches!(rcode, 2 | 5) {
+                    report.add_finding(Finding {
+                        severity: Severity::Warning,
+                        category: "dns".into(),
+                        message: format!("Nameserver {} answers {} for {}", server, rcode_name(*rcode), probe),
+                        details: Some(
+                            "SERVFAIL usually means the server cannot reach its upstream or DNSSEC \
+                             validation failed; REFUSED means it does not serve this client."
+                                .into(),
+                        ),
+                    });
+                } else if *ms > SLOW_MS {
+                    report.add_finding(Finding {
+                        severity: Severity::Warning,
+                        category: "dns".into(),
+                        message: format!("Nameserver {} is slow ({:.0} ms for {})", server, ms, probe),
+                        details: Some(
+                            "Uncached lookups add this delay to every new connection. A closer or \
+                             caching resolver helps."
+                                .into(),
+                        ),
+                    });
+                }
+                if matches!(aaaa, Some(QueryOutcome::Timeout)) {
+                    report.add_finding(Finding {
+                        severity: Severity::Warning,
+                        category: "dns".into(),
+                        message: format!("AAAA queries to {} time out while A queries answer", server),
+                        details: Some(if stub {
+                            "systemd-resolved forwards AAAA lookups to its upstream servers and waits for them; \
+                             something on the path drops AAAA queries or their answers."
+                                .into()
+                        } else {
+                            format!(
+                                "glibc sends A and AAAA together; when a firewall or broken router drops \
+                                 the AAAA query, every lookup stalls for timeout:{} s before succeeding.",
+                                conf.timeout
+                            )
+                        }),
+                    });
+                    // resolv.conf options only affect glibc's queries to the stub, not resolved's upstream.
+                    report.add_recommendation(if stub {
+                        Recommendation {
+                            priority: 2,
+                            action: "Point systemd-resolved at an upstream DNS server that answers AAAA".into(),
+                            command: Some(
+                                "sudo mkdir -p /etc/systemd/resolved.conf.d && \
+                                 printf '[Resolve]\\nDNS=<server>\\n' | \
+                                 sudo tee /etc/systemd/resolved.conf.d/dns.conf && \
+                                 sudo systemctl restart systemd-resolved"
+                                    .into(),
+                            ),
+                            explanation: "single-request-reopen in resolv.conf does not help in stub mode; \
+                                          fixing the device that drops AAAA is the real cure."
+                                .into(),
+                        }
+                    } else {
+                        Recommendation {
+                            priority: 2,
+                            action: "Send A and AAAA queries from separate sockets".into(),
+                            command: Some(format!("echo 'options single-request-reopen' | sudo tee -a {}", RESOLV_CONF)),
+                            explanation: "Works around middleboxes that drop the second query of a pair; \
+                                          fixing the device that drops AAAA is the real cure."
+                                .into(),
+                        }
+                    });
+                }
+            }
+        }
+    }
+    (answering, resolving)
+}
+
+/// Which name the search list turns `name` into, by querying `server` like the stub resolver.
+async fn effective_name(server: IpAddr, name: &str, conf: &ResolvConf) -> Option<(String, usize)> {
+    for (i, candidate) in candidates(name, conf).into_iter().enumerate() {
+        if query(server, &candidate, TYPE_A).await.resolved() {
+            return Some((candidate, i + 1));
+        }
+    }
+    None
+}
+
+fn analyze_config(report: &mut DiagnosticReport, conf: &ResolvConf, nss_hosts: &[String], stub: bool) {
+    report.add_metric(Metric {
+        name: "Resolver".into(),
+        value: MetricValue::Text(if stub { "systemd-resolved stub".into() } else { "direct".into() }),
+        unit: None,
+        threshold: None,
+    });
+    if !nss_hosts.is_empty() {
+        report.add_metric(Metric {
+            name: "NSS hosts order".into(),
+            value: MetricValue::Text(nss_hosts.join(" ")),
+            unit: None,
+            threshold: None,
+        });
+    }
+    if !conf.search.is_empty() {
+        report.add_metric(Metric {
+            name: "Search domains".into(),
+            value: MetricValue::List(conf.search.clone()),
+            unit: None,
+            threshold: None,
+        });
+    }
+
+    if !nss_hosts.is_empty() && !nss_hosts.iter().any(|s| s == "dns" || s == "resolve") {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "dns".into(),
+            message: format!("{} never consults DNS for host names", NSSWITCH),
+            details: Some(format!("hosts: {} – only these sources are used by getaddrinfo.", nss_hosts.join(" "))),
+        });
+    }
+    if conf.nameservers.len() > MAXNS {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "dns".into(),
+            message: format!(
+                "{} lists {} nameservers; only the first {} are used",
+                RESOLV_CONF,
+                conf.nameservers.len(),
+                MAXNS
+            ),
+            details: None,
+        });
+    }
+    if conf.ndots > 1 && !conf.search.is_empty() {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "dns".into(),
+            message: format!(
+                "options ndots:{} sends names with fewer than {} dots through {} search domain(s) first",
+                conf.ndots,
+                conf.ndots,
+                conf.search.len()
+            ),
+            details: Some(
+                "Each external lookup then costs one failed query per search domain before the real \
+                 name is tried. Append a trailing dot to fully qualified names or lower ndots."
+                    .into(),
+            ),
+        });
+    }
+}
+
+/// Full resolver analysis for `host` (a name, or an IP that gets a reverse lookup).
+pub async fn analyze(report: &mut DiagnosticReport, host: &str) {
+    let Ok(content) = std::fs::read_to_string(RESOLV_CONF) else {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "dns".into(),
+            message: format!("{} is missing or unreadable", RESOLV_CONF),
+            details: Some("glibc then falls back to a nameserver on 127.0.0.1.".into()),
+        });
+        return;
+    };
+    let conf = parse_resolv_conf(&content);
+    let nss_hosts = std::fs::read_to_string(NSSWITCH)
+        .map(|c| parse_nsswitch_hosts(&c))
+        .unwrap_or_default();
+    let stub = stub_mode(&conf);
+    analyze_config(report, &conf, &nss_hosts, stub);
+
+    let mut servers: Vec<IpAddr> = conf.nameservers.iter().take(MAXNS).copied().collect();
+    if stub {
+        let upstream = std::fs::read_to_string(RESOLVED_UPSTREAM)
+            .map(|c| parse_resolv_conf(&c).nameservers)
+            .unwrap_or_default();
+        if upstream.is_empty() {
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "dns".into(),
+                message: "systemd-resolved has no upstream DNS servers".into(),
+                details: Some(format!("{} lists none; the stub can only answer from its cache.", RESOLVED_UPSTREAM)),
+            });
+            report.add_recommendation(Recommendation {
+                priority: 2,
+                action: "Check which links provide DNS servers to systemd-resolved".into(),
+                command: Some("resolvectl status".into()),
+                explanation: "DNS servers come from DHCP, NetworkManager or DNS= in resolved.conf.".into(),
+            });
+        }
+        for server in upstream {
+            if !servers.contains(&server) {
+                servers.push(server);
+            }
+        }
+    }
+    if servers.is_empty() {
+        report.add_finding(Finding {
+            severity: Severity::Critical,
+            category: "dns".into(),
+            message: format!("No nameservers configured in {}", RESOLV_CONF),
+            details: Some("Only /etc/hosts and other non-DNS sources can resolve names.".into()),
+        });
+        return;
+    }
+
+    let target_ip = host.parse::<IpAddr>().ok();
+    let probe = match target_ip {
+        Some(ip) => ptr_name(ip),
+        None => host.trim_end_matches('.').to_string(),
+    };
+    let (answering, resolving) = time_servers(report, &servers, &probe, target_ip.is_some(), &conf, stub).await;
+    if answering == 0 {
+        report.add_finding(Finding {
+            severity: Severity::Critical,
+            category: "dns".into(),
+            message: "No configured nameserver answers".into(),
+            details: Some(format!("Tried {}.", servers.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(", "))),
+        });
+    }
+
+    if target_ip.is_none() && answering > 0 && !conf.search.is_empty() {
+        if let Some((name, tries)) = effective_name(servers[0], host, &conf).await {
+            if name != host.trim_end_matches('.') {
+                report.add_finding(Finding {
+                    severity: Severity::Info,
+                    category: "dns".into(),
+                    message: format!("{} resolves through the search list as {}", host, name),
+                    details: Some(format!(
+                        "Found after {} quer{}. If you meant the public name, write it with a trailing dot ({}.).",
+                        tries,
+                        if tries == 1 { "y" } else { "ies" },
+                        host
+                    )),
+                });
+            } else if tries > 1 {
+                report.add_finding(Finding {
+                    severity: Severity::Info,
+                    category: "dns".into(),
+                    message: format!("Resolving {} took {} queries because of the search list", host, tries),
+                    details: Some(format!("Tried in order: {}.", candidates(host, &conf).join(", "))),
+                });
+            }
+        }
+    }
+
+    // What applications actually get: getaddrinfo through NSS. An IP target needs no forward
+    // lookup, so a well-known name stands in for it.
+    let name = if target_ip.is_some() { FORWARD_PROBE } else { host };
+    let lookup = name.to_string();
+    let start = Instant::now();
+    let system = tokio::task::spawn_blocking(move || dns_lookup::lookup_host(&lookup)).await;
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+    report.add_metric(Metric {
+        name: "System resolution".into(),
+        value: MetricValue::Float(elapsed),
+        unit: Some("ms".into()),
+        threshold: Some(Threshold { warning: 1000.0, critical: 5000.0 }),
+    });
+    match system {
+        Ok(Ok(ips)) if !ips.is_empty() => {
+            if elapsed > 1000.0 {
+                report.add_finding(Finding {
+                    severity: Severity::Warning,
+                    category: "dns".into(),
+                    message: format!("Resolving {} through getaddrinfo took {:.1} s", name, elapsed / 1000.0),
+                    details: Some(
+                        "Slower than any single nameserver suggests resolver timeouts (a dead first \
+                         nameserver, dropped AAAA queries) or a slow NSS module."
+                            .into(),
+                    ),
+                });
+            }
+            report.add_finding(Finding {
+                severity: Severity::Ok,
+                category: "dns".into(),
+                message: format!("DNS resolution for {} OK", name),
+                details: Some(ips.iter().map(|ip| ip.to_string()).collect::<Vec<_>>().join(", ")),
+            });
+        }
+        _ => {
+            let details = if target_ip.is_some() {
+                if answering == 0 {
+                    "No nameserver answered either.".into()
+                } else if query(servers[0], FORWARD_PROBE, TYPE_A).await.resolved() {
+                    format!(
+                        "Nameservers resolve {} directly but getaddrinfo fails; check the hosts: line in {}.",
+                        FORWARD_PROBE, NSSWITCH
+                    )
+                } else {
+                    format!(
+                        "Nameservers answer but do not resolve {}; they may have no upstream access.",
+                        FORWARD_PROBE
+                    )
+                }
+            } else if resolving > 0 {
+                format!(
+                    "Nameservers resolve {} directly but getaddrinfo fails; check the hosts: line in {}.",
+                    probe, NSSWITCH
+                )
+            } else if answering > 0 {
+                format!("Nameservers answer but have no A/AAAA record for {}.", probe)
+            } else {
+                "No nameserver answered either.".into()
+            };
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "dns".into(),
+                message: format!("System name resolution for {} failed", name),
+                details: Some(details),
+            });
+        }
+    }
+
+    let Some(ip) = target_ip else {
+        return;
+    };
+    let reverse = tokio::task::spawn_blocking(move || dns_lookup::lookup_addr(&ip)).await;
+    // Without NI_NAMEREQD getnameinfo returns the numeric address when there is no PTR record.
+    match reverse {
+        Ok(Ok(rname)) if rname.parse::<IpAddr>().ok() != Some(ip) => report.add_metric(Metric {
+            name: format!("Reverse DNS of {}", ip),
+            value: MetricValue::Text(rname),
+            unit: None,
+            threshold: None,
+        }),
+        _ => report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "dns".into(),
+            message: format!("No reverse DNS name for {}", ip),
+            details: Some(if answering > 0 {
+                format!("Nameservers answer but have no PTR record for {}.", probe)
+            } else {
+                "No nameserver answered.".into()
+            }),
+        }),
+    }
+}
+
@@ -0,0 +1,481 @@
+//! HTTP(S) reachability for `net --full`: DNS, connect, TLS and first-byte timings over a
+//! direct connection, certificate validity and expiry, proxy variables in effect, and a
+//! captive portal check against a URL with known content.
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation, Threshold};
+use crate::core::severity::Severity;
+use anyhow::{anyhow, Context, Result};
+use reqwest::tls::TlsInfo;
+use reqwest::Url;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, Instant};
+use tower::Service;
+use url::Host;
+
+pub const DEFAULT_URL: &str = "https://www.google.com/";
+/// Answers 204 with an empty body; anything else means something intercepted the request.
+pub const DEFAULT_PORTAL_URL: &str = "http://connectivitycheck.gstatic.com/generate_204";
+const TIMEOUT: Duration = Duration::from_secs(5);
+const CERT_EXPIRY_WARN_DAYS: i64 = 14;
+
+/// Server certificate of the probed URL.
+#[derive(Debug, Clone)]
+pub struct CertInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub not_after: String,
+    pub days_left: i64,
+    /// Why the chain or host name did not verify, if it did not.
+    pub verify_error: Option<String>,
+}
+
+/// Phase timings of one direct request.
+#[derive(Debug, Clone)]
+pub struct Timings {
+    pub addr: SocketAddr,
+    pub dns: Duration,
+    pub connect: Duration,
+    pub tls: Option<Duration>,
+    /// From the connection being ready to the response headers.
+    pub first_byte: Duration,
+    pub status: u16,
+    pub cert: Option<CertInfo>,
+}
+
+/// Which phase a failed probe stopped in.
+#[derive(Debug)]
+pub struct ProbeError {
+    pub phase: &'static str,
+    pub error: anyhow::Error,
+}
+
+fn cert_info(der: &[u8], verify_error: Option<String>) -> Result<CertInfo> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der)?;
+    let name = |n: &x509_parser::x509::X509Name| {
+        n.iter_common_name()
+            .next()
+            .or_else(|| n.iter_organization().next())
+            .and_then(|a| a.as_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let not_after = cert.validity().not_after;
+    // Floor, so a certificate that expired an hour ago counts as expired.
+    let days_left = (not_after.timestamp() - chrono::Utc::now().timestamp()).div_euclid(86400);
+    Ok(CertInfo {
+        subject: name(cert.subject()),
+        issuer: name(cert.issuer()),
+        not_after: not_after.to_string(),
+        days_left,
+        verify_error,
+    })
+}
+
+/// Connector wrapper that records when reqwest's connector hands over a ready connection,
+/// i.e. after the TCP connect and, for https, the TLS handshake.
+#[derive(Clone)]
+struct Stamp<S> {
+    inner: S,
+    ready: Arc<Mutex<Option<Instant>>>,
+}
+
+impl<S, R> Service<R> for Stamp<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = std::result::Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut TaskContext<'_>) -> Poll<std::result::Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let connecting = self.inner.call(req);
+        let ready = self.ready.clone();
+        Box::pin(async move {
+            let conn = connecting.await;
+            *ready.lock().unwrap() = Some(Instant::now());
+            conn
+        })
+    }
+}
+
+/// One request through reqwest, pinned to `addr` and bypassing any proxy.
+struct Exchange {
+    status: u16,
+    /// From sending to the connection being ready.
+    setup: Duration,
+    first_byte: Duration,
+    /// DER of the server's leaf certificate.
+    cert: Option<Vec<u8>>,
+}
+
+async fn exchange(url: &Url, addr: SocketAddr, insecure: bool) -> reqwest::Result<Exchange> {
+    let ready = Arc::new(Mutex::new(None));
+    let stamp = ready.clone();
+    let mut builder = reqwest::Client::builder()
+        .no_proxy()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(TIMEOUT)
+        .pool_max_idle_per_host(0)
+        .user_agent(concat!("rustwhy/", env!("CARGO_PKG_VERSION")))
+        .tls_info(true)
+        .danger_accept_invalid_certs(insecure)
+        .danger_accept_invalid_hostnames(insecure)
+        .connector_layer(tower::layer::layer_fn(move |inner| Stamp { inner, ready: stamp.clone() }));
+    if let Some(Host::Domain(name)) = url.host() {
+        builder = builder.resolve(name, addr);
+    }
+    let client = builder.build()?;
+
+    let start = Instant::now();
+    let response = client.get(url.clone()).send().await?;
+    let headers = Instant::now();
+    let ready = ready.lock().unwrap().unwrap_or(start);
+    let cert = response
+        .extensions()
+        .get::<TlsInfo>()
+        .and_then(TlsInfo::peer_certificate)
+        .map(<[u8]>::to_vec);
+    Ok(Exchange {
+        status: response.status().as_u16(),
+        setup: ready - start,
+        first_byte: headers - ready,
+        cert,
+    })
+}
+
+/// Time each phase of a direct (proxy-less) request to `url`.
+///
+/// reqwest reports no per-phase timings, so DNS and a bare TCP connect to the same address
+/// are timed first, the request then goes through reqwest pinned to that address, and the
+/// TLS handshake is its connection setup minus the bare connect.
+pub async fn probe(url: &Url) -> std::result::Result<Timings, ProbeError> {
+    let fail = |phase, error| ProbeError { phase, error };
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let start = Instant::now();
+    let addr = match url.host() {
+        Some(Host::Ipv4(ip)) => SocketAddr::new(ip.into(), port),
+        Some(Host::Ipv6(ip)) => SocketAddr::new(ip.into(), port),
+        Some(Host::Domain(name)) => tokio::time::timeout(TIMEOUT, tokio::net::lookup_host((name, port)))
+            .await
+            .map_err(|_| anyhow!("timed out after {:?}", TIMEOUT))
+            .and_then(|r| r.context("cannot resolve host"))
+            .and_then(|mut a| a.next().ok_or_else(|| anyhow!("no addresses")))
+            .map_err(|e| fail("dns", e))?,
+        None => return Err(fail("dns", anyhow!("URL has no host"))),
+    };
+    let dns = start.elapsed();
+
+    let start = Instant::now();
+    tokio::time::timeout(TIMEOUT, tokio::net::TcpStream::connect(addr))
+        .await
+        .map_err(|_| anyhow!("timed out after {:?}", TIMEOUT))
+        .and_then(|r| r.map_err(Into::into))
+        .map_err(|e| fail("connect", e))?;
+    let connect = start.elapsed();
+
+    let https = url.scheme() == "https";
+    let (exchanged, verify_error) = match exchange(url, addr, false).await {
+        Ok(x) => (x, None),
+        // The bare connect worked, so a connect error here is the TLS handshake.
+        Err(e) if https && e.is_connect() => {
+            let reason = format!("{:#}", anyhow::Error::new(e));
+            // Repeat without verification to still learn what certificate was presented.
+            let x = exchange(url, addr, true).await.map_err(|_| fail("tls", anyhow!("{}", reason)))?;
+            (x, Some(reason))
+        }
+        Err(e) => {
+            let phase = if e.is_connect() { "connect" } else { "response" };
+            return Err(fail(phase, e.into()));
+        }
+    };
+    let cert = exchanged.cert.as_deref().and_then(|der| cert_info(der, verify_error).ok());
+    Ok(Timings {
+        addr,
+        dns,
+        connect,
+        tls: https.then(|| exchanged.setup.saturating_sub(connect)),
+        first_byte: exchanged.first_byte,
+        status: exchanged.status,
+        cert,
+    })
+}
+
+/// Proxy variables that apply to `url`, with credentials masked.
+pub fn proxy_env(url: &Url) -> Vec<(String, String)> {
+    let scheme_var = if url.scheme() == "https" { "https_proxy" } else { "http_proxy" };
+    let host = url.host_str().unwrap_or_default();
+    let mut out = Vec::new();
+    for var in [scheme_var, "all_proxy", "no_proxy"] {
+        for name in [var.to_string(), var.to_uppercase()] {
+            let Ok(value) = std::env::var(&name) else {
+                continue;
+            };
+            if value.is_empty() {
+                continue;
+            }
+            let shown = match Url::parse(&value) {
+                Ok(mut u) if !u.username().is_empty() => {
+                    let _ = u.set_password(None);
+                    let _ = u.set_username("***");
+                    u.to_string()
+                }
+                _ => value.clone(),
+            };
+            if var == "no_proxy" {
+                let bypassed = value.split(',').map(str::trim).any(|d| {
+                    d == "*" || (!d.is_empty() && (host == d.trim_start_matches('.') || host.ends_with(&format!(".{}", d.trim_start_matches('.')))))
+                });
+                if bypassed {
+                    out.push((name, format!("{} (bypasses {})", shown, host)));
+                }
+            } else {
+                out.push((name, shown));
+            }
+            break;
+        }
+    }
+    out
+}
+
+fn ms_metric(name: &str, d: Duration, warning: f64) -> Metric {
+    Metric {
+        name: name.into(),
+        value: MetricValue::Float(d.as_secs_f64() * 1000.0),
+        unit: Some("ms".into()),
+        threshold: Some(Threshold { warning, critical: warning * 4.0 }),
+    }
+}
+
+fn analyze_timings(report: &mut DiagnosticReport, url: &Url, t: &Timings) {
+    report.add_metric(Metric {
+        name: "HTTP status".into(),
+        value: MetricValue::Text(format!("{} from {}", t.status, t.addr)),
+        unit: None,
+        threshold: None,
+    });
+    let mut phases = vec![("DNS lookup", t.dns, 500.0), ("TCP connect", t.connect, 500.0)];
+    if let Some(tls) = t.tls {
+        phases.push(("TLS handshake", tls, 1000.0));
+    }
+    phases.push(("Time to first byte", t.first_byte, 2000.0));
+    for (name, d, warning) in &phases {
+        report.add_metric(ms_metric(name, *d, *warning));
+    }
+    if let Some((name, d, _)) = phases.iter().filter(|(_, d, w)| d.as_secs_f64() * 1000.0 > *w).max_by_key(|(_, d, _)| *d) {
+        let why = match *name {
+            "DNS lookup" => "the resolver is slow or the first nameserver times out (run `rustwhy net --dns-only`)",
+            "TCP connect" => "the path to the server is slow or SYNs are being retransmitted",
+            "TLS handshake" => "the server is far away or a TLS-inspecting proxy sits in the path",
+            _ => "the server itself is slow to answer",
+        };
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "http".into(),
+            message: format!("{} to {} takes {:.0} ms", name, url.host_str().unwrap_or_default(), d.as_secs_f64() * 1000.0),
+            details: Some(format!("The slowest phase; usually {}.", why)),
+        });
+    }
+    if t.status >= 500 {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "http".into(),
+            message: format!("{} answers HTTP {}", url, t.status),
+            details: Some("The network works; the server side fails.".into()),
+        });
+    }
+
+    let Some(cert) = &t.cert else {
+        return;
+    };
+    report.add_metric(Metric {
+        name: "Certificate".into(),
+        value: MetricValue::Text(format!("{} (issued by {}, expires {})", cert.subject, cert.issuer, cert.not_after)),
+        unit: None,
+        threshold: None,
+    });
+    report.add_metric(Metric {
+        name: "Certificate expires in".into(),
+        value: MetricValue::Integer(cert.days_left),
+        unit: Some(" days".into()),
+        threshold: None,
+    });
+    if cert.days_left < 0 {
+        report.add_finding(Finding {
+            severity: Severity::Critical,
+            category: "tls".into(),
+            message: format!("Certificate of {} expired {} day(s) ago", url.host_str().unwrap_or_default(), -cert.days_left),
+            details: Some(format!(
+                "Valid until {}. If other sites fail the same way, check the system clock (`rustwhy time`).",
+                cert.not_after
+            )),
+        });
+    } else if let Some(err) = &cert.verify_error {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "tls".into(),
+            message: format!("Certificate of {} is not trusted", url.host_str().unwrap_or_default()),
+            details: Some(format!(
+                "{}\nPresented: {} issued by {}. An unexpected issuer usually means a TLS-inspecting \
+                 proxy, antivirus or captive portal is intercepting HTTPS.",
+                err, cert.subject, cert.issuer
+            )),
+        });
+    } else if cert.days_left < CERT_EXPIRY_WARN_DAYS {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "tls".into(),
+            message: format!("Certificate of {} expires in {} day(s)", url.host_str().unwrap_or_default(), cert.days_left),
+            details: Some(format!("Valid until {}.", cert.not_after)),
+        });
+    }
+}
+
+/// Fetch `portal_url` without following redirects; anything but an empty 204 is intercepted.
+async fn check_portal(report: &mut DiagnosticReport, portal_url: &str) {
+    let client = match reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(TIMEOUT)
+        .build()
+    {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    let response = match client.get(portal_url).send().await {
+        Ok(r) => r,
+        Err(e) => {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "portal".into(),
+                message: format!("Captive portal check could not reach {}", portal_url),
+                details: Some(e.to_string()),
+            });
+            return;
+        }
+    };
+    let status = response.status();
+    let location = response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|l| l.to_str().ok())
+        .map(String::from);
+    let body = response.text().await.unwrap_or_default();
+    if status == reqwest::StatusCode::NO_CONTENT && body.is_empty() {
+        return;
+    }
+    let title = body
+        .find("<title>")
+        .and_then(|i| body[i + 7..].split("</title>").next())
+        .map(|t| format!(" (page title \"{}\")", t.trim()))
+        .unwrap_or_default();
+    let how = match (&location, status.is_redirection()) {
+        (Some(loc), true) => format!("redirected ({}) to {}", status.as_u16(), loc),
+        _ => format!("answered {} with {} bytes instead of an empty 204{}", status.as_u16(), body.len(), title),
+    };
+    report.add_finding(Finding {
+        severity: Severity::Warning,
+        category: "portal".into(),
+        message: format!("Captive portal or filtering proxy detected: {} was {}", portal_url, how),
+        details: Some(
+            "Hotel, airport and guest networks intercept traffic until you log in or accept their \
+             terms; HTTPS then fails with certificate errors and apps time out."
+                .into(),
+        ),
+    });
+    report.add_recommendation(Recommendation {
+        priority: 1,
+        action: "Open the portal login page in a browser".into(),
+        command: location.or_else(|| Some(portal_url.to_string())),
+        explanation: "Once logged in, connectivity works normally.".into(),
+    });
+}
+
+/// HTTP probe of `url` plus the captive portal check.
+pub async fn analyze(report: &mut DiagnosticReport, url: &str, portal_url: &str) {
+    let parsed = match Url::parse(url) {
+        Ok(u) if matches!(u.scheme(), "http" | "https") => u,
+        _ => {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "http".into(),
+                message: format!("Not an http(s) URL: {}", url),
+                details: None,
+            });
+            return;
+        }
+    };
+    report.add_metric(Metric {
+        name: "HTTP probe".into(),
+        value: MetricValue::Text(url.to_string()),
+        unit: None,
+        threshold: None,
+    });
+
+    let proxies = proxy_env(&parsed);
+    let proxied = proxies.iter().any(|(n, v)| !n.eq_ignore_ascii_case("no_proxy") && !v.is_empty())
+        && !proxies.iter().any(|(n, _)| n.eq_ignore_ascii_case("no_proxy"));
+    if !proxies.is_empty() {
+        report.add_metric(Metric {
+            name: "Proxy".into(),
+            value: MetricValue::List(proxies.iter().map(|(n, v)| format!("{}={}", n, v)).collect()),
+            unit: None,
+            threshold: None,
+        });
+    }
+
+    match probe(&parsed).await {
+        Ok(t) => analyze_timings(report, &parsed, &t),
+        Err(ProbeError { phase, error }) => {
+            let mut details = format!("{:#}", error);
+            if proxied {
+                details.push_str(
+                    "\nA proxy is configured for this URL; applications using it may still work even \
+                     though the direct connection fails.",
+                );
+            }
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "http".into(),
+                message: format!("HTTP probe of {} failed during {}", url, phase),
+                details: Some(details),
+            });
+        }
+    }
+
+    if proxied {
+        // What applications honoring the proxy variables get.
+        let start = Instant::now();
+        let via_proxy = async {
+            let client = reqwest::Client::builder().timeout(TIMEOUT).build()?;
+            let status = client.get(parsed.clone()).send().await?.status();
+            Ok::<_, reqwest::Error>(status)
+        }
+        .await;
+        match via_proxy {
+            Ok(status) => report.add_metric(Metric {
+                name: "Via proxy".into(),
+                value: MetricValue::Text(format!("HTTP {} in {:.0} ms", status.as_u16(), start.elapsed().as_secs_f64() * 1000.0)),
+                unit: None,
+                threshold: None,
+            }),
+            Err(e) => report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "http".into(),
+                message: format!("Request to {} through the configured proxy fails", url),
+                details: Some(e.to_string()),
+            }),
+        }
+    }
+
+    check_portal(report, portal_url).await;
+}
+
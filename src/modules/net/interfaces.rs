@@ -0,0 +1,374 @@
+//! Interface health: /proc/net/dev error, drop, FIFO and carrier counters sampled over an
+//! interval, link state, speed, duplex, MTU and driver from /sys/class/net/<if>.
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use super::route;
+use crate::utils::{command_exists, read_first_line, run_cmd};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const IFF_UP: u32 = 0x1;
+/// Cumulative error share of all packets worth mentioning even when not rising.
+const ERROR_RATIO: f64 = 0.001;
+/// carrier_changes above this (up and down transitions) counts as a flapping link.
+const FLAP_CHANGES: u64 = 20;
+
+/// Counters of one interface from /proc/net/dev.
+#[derive(Debug, Clone, Default)]
+pub struct IfCounters {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_dropped: u64,
+    pub rx_fifo: u64,
+    pub rx_frame: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_dropped: u64,
+    pub tx_fifo: u64,
+    pub collisions: u64,
+    pub tx_carrier: u64,
+}
+
+impl IfCounters {
+    fn errors(&self) -> u64 {
+        self.rx_errors + self.tx_errors
+    }
+}
+
+pub fn parse_net_dev(content: &str) -> Vec<(String, IfCounters)> {
+    content
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, rest) = line.split_once(':')?;
+            let v: Vec<u64> = rest.split_whitespace().filter_map(|n| n.parse().ok()).collect();
+            if v.len() < 16 {
+                return None;
+            }
+            Some((
+                name.trim().to_string(),
+                IfCounters {
+                    rx_bytes: v[0],
+                    rx_packets: v[1],
+                    rx_errors: v[2],
+                    rx_dropped: v[3],
+                    rx_fifo: v[4],
+                    rx_frame: v[5],
+                    tx_bytes: v[8],
+                    tx_packets: v[9],
+                    tx_errors: v[10],
+                    tx_dropped: v[11],
+                    tx_fifo: v[12],
+                    collisions: v[13],
+                    tx_carrier: v[14],
+                },
+            ))
+        })
+        .collect()
+}
+
+fn read_counters() -> HashMap<String, IfCounters> {
+    std::fs::read_to_string("/proc/net/dev")
+        .map(|c| parse_net_dev(&c).into_iter().collect())
+        .unwrap_or_default()
+}
+
+/// Link attributes from /sys/class/net/<if>.
+#[derive(Debug, Clone, Default)]
+pub struct LinkInfo {
+    /// Administratively up (IFF_UP).
+    pub admin_up: bool,
+    pub operstate: Option<String>,
+    /// `None` when the interface is down and the kernel refuses to report it.
+    pub carrier: Option<bool>,
+    /// Negotiated speed in Mb/s; `None` when unknown (virtual, Wi-Fi, no link).
+    pub speed: Option<u32>,
+    pub duplex: Option<String>,
+    pub mtu: Option<u32>,
+    pub driver: Option<String>,
+    /// Backed by a device (PCI, USB, virtio) rather than purely virtual.
+    pub physical: bool,
+    pub carrier_changes: Option<u64>,
+}
+
+fn sys_attr(name: &str, attr: &str) -> Option<String> {
+    read_first_line(&Path::new("/sys/class/net").join(name).join(attr))
+        .ok()
+        .flatten()
+        .map(|s| s.trim().to_string())
+}
+
+pub fn read_link(name: &str) -> LinkInfo {
+    let dir = Path::new("/sys/class/net").join(name);
+    let flags = sys_attr(name, "flags")
+        .and_then(|f| u32::from_str_radix(f.trim_start_matches("0x"), 16).ok())
+        .unwrap_or(0);
+    LinkInfo {
+        admin_up: flags & IFF_UP != 0,
+        operstate: sys_attr(name, "operstate"),
+        carrier: sys_attr(name, "carrier").map(|c| c == "1"),
+        speed: sys_attr(name, "speed").and_then(|s| s.parse::<i64>().ok()).filter(|s| *s > 0).map(|s| s as u32),
+        duplex: sys_attr(name, "duplex").filter(|d| d != "unknown"),
+        mtu: sys_attr(name, "mtu").and_then(|m| m.parse().ok()),
+        driver: std::fs::read_link(dir.join("device/driver"))
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned())),
+        physical: dir.join("device").exists(),
+        carrier_changes: sys_attr(name, "carrier_changes").and_then(|c| c.parse().ok()),
+    }
+}
+
+/// Fastest speed in a block of ethtool link modes such as "100baseT/Full 1000baseT/Full".
+fn max_mode_speed(modes: &str) -> Option<u32> {
+    modes
+        .split_whitespace()
+        .filter_map(|m| m.split_once("base")?.0.parse().ok())
+        .max()
+}
+
+/// Highest speed the NIC supports and the link partner advertises, from `ethtool <if>`.
+pub fn parse_ethtool_modes(output: &str) -> (Option<u32>, Option<u32>) {
+    let mut sections: HashMap<&str, String> = HashMap::new();
+    let mut current: Option<&str> = None;
+    for line in output.lines() {
+        let line = line.trim();
+        if let Some((key, value)) = line.split_once(':') {
+            current = match key {
+                "Supported link modes" => Some("supported"),
+                "Link partner advertised link modes" => Some("partner"),
+                _ => None,
+            };
+            if let Some(section) = current {
+                sections.entry(section).or_default().push_str(value);
+            }
+        } else if let Some(section) = current {
+            sections.entry(section).or_default().push(' ');
+            sections.get_mut(section).expect("inserted above").push_str(line);
+        }
+    }
+    (
+        sections.get("supported").and_then(|s| max_mode_speed(s)),
+        sections.get("partner").and_then(|s| max_mode_speed(s)),
+    )
+}
+
+fn describe_link(link: &LinkInfo) -> String {
+    let mut parts = vec![link.operstate.clone().unwrap_or_else(|| "unknown".into())];
+    if let Some(speed) = link.speed {
+        parts.push(format!("{} Mb/s", speed));
+    }
+    if let Some(duplex) = &link.duplex {
+        parts.push(format!("{} duplex", duplex));
+    }
+    if let Some(mtu) = link.mtu {
+        parts.push(format!("MTU {}", mtu));
+    }
+    if let Some(driver) = &link.driver {
+        parts.push(format!("driver {}", driver));
+    }
+    parts.join(", ")
+}
+
+/// Interfaces with an IP address or a route: the ones traffic is expected to use.
+fn configured_interfaces() -> HashSet<String> {
+    let mut names: HashSet<String> = nix::ifaddrs::getifaddrs()
+        .map(|addrs| {
+            addrs
+                .filter(|a| {
+                    a.address
+                        .is_some_and(|sa| sa.as_sockaddr_in().is_some() || sa.as_sockaddr_in6().is_some())
+                })
+                .map(|a| a.interface_name)
+                .collect()
+        })
+        .unwrap_or_default();
+    let v4 = std::fs::read_to_string("/proc/net/route")
+        .map(|c| route::parse_ipv4_routes(&c))
+        .unwrap_or_default();
+    let v6 = std::fs::read_to_string("/proc/net/ipv6_route")
+        .map(|c| route::parse_ipv6_routes(&c))
+        .unwrap_or_default();
+    names.extend(v4.into_iter().chain(v6).map(|r| r.iface));
+    names
+}
+
+fn counter_metric(name: String, value: u64) -> Metric {
+    Metric { name, value: MetricValue::Integer(value as i64), unit: None, threshold: None }
+}
+
+/// Per-interface link and counter metrics (all counters when `detailed`), plus findings for
+/// rising errors, missing carrier and degraded negotiation.
+pub async fn analyze(report: &mut DiagnosticReport, detailed: bool) {
+    let before = read_counters();
+    tokio::time::sleep(SAMPLE_INTERVAL).await;
+    let after = read_counters();
+
+    let configured = configured_interfaces();
+    let mut names: Vec<&String> = after.keys().filter(|n| n.as_str() != "lo").collect();
+    names.sort();
+    for name in names {
+        let now = &after[name];
+        let link = read_link(name);
+        let active = now.rx_bytes > 0 || now.tx_bytes > 0;
+        if detailed || active {
+            report.add_metric(Metric {
+                name: format!("{} rx", name),
+                value: MetricValue::Integer(now.rx_bytes as i64),
+                unit: Some("bytes".into()),
+                threshold: None,
+            });
+            report.add_metric(Metric {
+                name: format!("{} tx", name),
+                value: MetricValue::Integer(now.tx_bytes as i64),
+                unit: Some("bytes".into()),
+                threshold: None,
+            });
+        }
+        if detailed {
+            report.add_metric(Metric {
+                name: format!("{} link", name),
+                value: MetricValue::Text(describe_link(&link)),
+                unit: None,
+                threshold: None,
+            });
+            for (label, value) in [
+                ("rx errors", now.rx_errors),
+                ("tx errors", now.tx_errors),
+                ("rx dropped", now.rx_dropped),
+                ("tx dropped", now.tx_dropped),
+                ("rx fifo", now.rx_fifo),
+                ("tx fifo", now.tx_fifo),
+                ("rx frame", now.rx_frame),
+                ("collisions", now.collisions),
+                ("tx carrier", now.tx_carrier),
+            ] {
+                report.add_metric(counter_metric(format!("{} {}", name, label), value));
+            }
+        }
+
+        if link.physical && link.admin_up && link.carrier == Some(false) {
+            // Spare NICs are often left up and unplugged; only a configured one loses traffic.
+            let in_use = configured.contains(name);
+            report.add_finding(Finding {
+                severity: if in_use { Severity::Warning } else { Severity::Info },
+                category: "interface".into(),
+                message: format!("{} is up but has no carrier", name),
+                details: Some(if in_use {
+                    "Nothing is connected at the physical layer: unplugged or broken cable, switch \
+                     port disabled, or (for Wi-Fi) not associated. It has an address or route, so \
+                     traffic meant for it is lost."
+                        .into()
+                } else {
+                    "Nothing is connected, but it has no address or route, so it is probably unused."
+                        .into()
+                }),
+            });
+            continue;
+        }
+
+        if let Some(prev) = before.get(name) {
+            let new_errors = now.errors().saturating_sub(prev.errors());
+            let new_packets = (now.rx_packets + now.tx_packets).saturating_sub(prev.rx_packets + prev.tx_packets);
+            if new_errors > 0 {
+                report.add_finding(Finding {
+                    severity: Severity::Warning,
+                    category: "interface".into(),
+                    message: format!(
+                        "{} error counters are rising: {} new error(s) in {} s ({} packets)",
+                        name,
+                        new_errors,
+                        SAMPLE_INTERVAL.as_secs(),
+                        new_packets
+                    ),
+                    details: Some(format!(
+                        "rx errors {} (frame/CRC {}), tx errors {} (carrier {}). Rising rx/frame \
+                         errors point at the cable, connector or a duplex mismatch; carrier errors \
+                         at the link itself.",
+                        now.rx_errors, now.rx_frame, now.tx_errors, now.tx_carrier
+                    )),
+                });
+            }
+            let new_fifo = (now.rx_fifo + now.tx_fifo).saturating_sub(prev.rx_fifo + prev.tx_fifo);
+            if new_fifo > 0 {
+                report.add_finding(Finding {
+                    severity: Severity::Warning,
+                    category: "interface".into(),
+                    message: format!("{} is overrunning its ring buffers ({} FIFO errors in {} s)", name, new_fifo, SAMPLE_INTERVAL.as_secs()),
+                    details: Some("The NIC receives faster than the kernel drains it.".into()),
+                });
+                report.add_recommendation(Recommendation {
+                    priority: 2,
+                    action: format!("Enlarge the ring buffers of {}", name),
+                    command: Some(format!("ethtool -g {0}; sudo ethtool -G {0} rx 4096", name)),
+                    explanation: "Use the maximum shown by `ethtool -g`.".into(),
+                });
+            }
+        }
+        let packets = now.rx_packets + now.tx_packets;
+        if packets > 0 && now.errors() as f64 / packets as f64 > ERROR_RATIO {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "interface".into(),
+                message: format!(
+                    "{} has {} errors in {} packets since boot ({:.2}%)",
+                    name,
+                    now.errors(),
+                    packets,
+                    now.errors() as f64 * 100.0 / packets as f64
+                ),
+                details: None,
+            });
+        }
+
+        // Each drop and each return of the link counts once.
+        if let Some(changes) = link.carrier_changes.filter(|c| *c > FLAP_CHANGES) {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "interface".into(),
+                message: format!("{} lost its link {} times since boot", name, changes / 2),
+                details: Some("A flapping link resets connections; check the cable, dock or Wi-Fi roaming.".into()),
+            });
+        }
+        if link.duplex.as_deref() == Some("half") {
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "interface".into(),
+                message: format!("{} runs at half duplex", name),
+                details: Some(
+                    "Usually a duplex mismatch from one side being forced while the other \
+                     autonegotiates; throughput collapses and collisions/CRC errors climb."
+                        .into(),
+                ),
+            });
+        }
+        if link.physical && link.speed.is_some_and(|s| s < 1000) && command_exists("ethtool") {
+            let (supported, partner) = run_cmd(&["ethtool", name.as_str()])
+                .map(|o| parse_ethtool_modes(&o))
+                .unwrap_or_default();
+            let speed = link.speed.unwrap_or_default();
+            if let Some(max) = supported.filter(|max| *max > speed) {
+                let cause = match partner {
+                    Some(p) if p <= speed => "The link partner only advertises this speed: check the switch port.",
+                    _ => "Both ends support more, which points at a cable with broken pairs (gigabit needs all four) or a damaged port.",
+                };
+                report.add_finding(Finding {
+                    severity: Severity::Warning,
+                    category: "interface".into(),
+                    message: format!("{} negotiated {} Mb/s but supports {} Mb/s", name, speed, max),
+                    details: Some(cause.into()),
+                });
+                report.add_recommendation(Recommendation {
+                    priority: 2,
+                    action: format!("Replace the cable of {} or try another switch port", name),
+                    command: Some(format!("ethtool {}", name)),
+                    explanation: "Compare supported and link partner advertised modes.".into(),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Network diagnostics (netwhy) - ping, path MTU, DNS, routing, interfaces, Wi-Fi, TCP/UDP stack counters,
+//! with `--full` HTTP(S) reachability and captive portal checks, and with `--bandwidth` traffic
+//! per process.
+
+mod bandwidth;
+mod dns;
+mod http;
+mod interfaces;
+mod mtu;
+mod ping;
+mod route;
+pub(crate) mod sockets;
+mod stack;
+pub mod wifi;
+
+use crate::core::report::{DiagnosticReport, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use crate::core::traits::{DiagnosticModule, ModuleConfig};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+pub fn module() -> Arc<dyn DiagnosticModule> {
+    Arc::new(NetModule)
+}
+
+struct NetModule;
+
+#[async_trait]
+impl DiagnosticModule for NetModule {
+    fn name(&self) -> &'static str {
+        "net"
+    }
+
+    fn description(&self) -> &'static str {
+        "Diagnose network issues: connectivity, DNS, interfaces"
+    }
+
+    async fn run(&self, config: &ModuleConfig) -> Result<DiagnosticReport> {
+        let host = config.extra_args.get("host").map(String::as_str).unwrap_or("8.8.8.8");
+        let mut report = DiagnosticReport::new("net", "Network diagnostics");
+
+        report.add_metric(Metric {
+            name: "Target host".into(),
+            value: MetricValue::Text(host.to_string()),
+            unit: None,
+            threshold: None,
+        });
+
+        if config.extra_args.get("dns_only").is_some_and(|s| s == "true") {
+            report.summary = "DNS resolver diagnostics".into();
+            dns::analyze(&mut report, host).await;
+            report.compute_overall_severity();
+            return Ok(report);
+        }
+
+        let count = config
+            .extra_args
+            .get("count")
+            .and_then(|c| c.parse::<usize>().ok())
+            .unwrap_or(5)
+            .max(1);
+        let mut target_addr = None;
+        let mut reachable = None;
+        match ping::resolve(host).await {
+            Ok(target) => {
+                target_addr = Some(target);
+                match ping::ping(target, count).await {
+                    Ok(stats) => {
+                        reachable = Some(stats.received() > 0);
+                        ping::analyze(&mut report, host, &stats);
+                    }
+                    Err(e) => ping::report_error(&mut report, host, &e),
+                }
+            }
+            Err(e) => ping::report_error(&mut report, host, &e),
+        }
+        route::analyze(&mut report, target_addr.map(|t| t.ip()), reachable).await;
+        if let (Some(target), Some(true)) = (target_addr, reachable) {
+            if !target.ip().is_loopback() {
+                match mtu::probe(target).await {
+                    Ok(result) => mtu::analyze(&mut report, host, target.ip(), &result),
+                    Err(e) => mtu::report_error(&mut report, host, &e),
+                }
+            }
+        }
+        dns::analyze(&mut report, host).await;
+
+        let detailed = config.extra_args.get("interfaces").is_some_and(|s| s == "true");
+        // Both sample over the same interval.
+        let ((), sample) = tokio::join!(interfaces::analyze(&mut report, detailed), stack::sample());
+        stack::analyze(&mut report, &sample);
+        wifi::analyze(&mut report);
+
+        if config.extra_args.get("bandwidth").is_some_and(|s| s == "true") {
+            let interval = std::time::Duration::from_secs(config.interval.max(1));
+            bandwidth::analyze(&mut report, interval, config.top_n).await;
+        }
+
+        if config.extra_args.get("full").is_some_and(|s| s == "true") {
+            let url = config.extra_args.get("url").map(String::as_str).unwrap_or(http::DEFAULT_URL);
+            let portal_url = config
+                .extra_args
+                .get("portal_url")
+                .map(String::as_str)
+                .unwrap_or(http::DEFAULT_PORTAL_URL);
+            http::analyze(&mut report, url, portal_url).await;
+        }
+
+        if report.overall_severity == Severity::Ok {
+            report.add_recommendation(Recommendation {
+                priority: 3,
+                action: "For deeper diagnosis use: ip addr, ip route, nmcli, traceroute.".into(),
+                command: Some("ip addr show".into()),
+                explanation: "Check interfaces and routing.".into(),
+            });
+        }
+
+        report.compute_overall_severity();
+        Ok(report)
+    }
+}
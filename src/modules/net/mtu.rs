@@ -0,0 +1,305 @@
+//! Path MTU probe: ICMP echo requests with the Don't Fragment bit set, first at the size of
+//! the outgoing interface's MTU and, if those vanish, binary search down to the largest size
+//! that still gets an answer. The kernel's cached PMTU for the target tells whether routers
+//! report the bottleneck (ICMP "fragmentation needed") or silently drop (a PMTUD black hole).
+
+use super::route::{self, Route};
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use crate::utils::read_first_line;
+use anyhow::{Context, Result};
+use nix::libc;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::Path;
+use std::time::Duration;
+use surge_ping::{Client, Config, PingIdentifier, PingSequence, SurgeError, ICMP};
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+/// surge-ping reads replies into a 2048-byte buffer; larger echoes cannot be matched.
+const MAX_PROBE: u32 = 2048;
+const ICMP_HEADER: u32 = 8;
+
+/// Outcome of the probe towards one target.
+#[derive(Debug, Clone)]
+pub struct PathMtu {
+    pub iface: String,
+    pub iface_mtu: u32,
+    /// Largest packet (IP header included) answered with DF set; `None` when even the
+    /// smallest size went unanswered.
+    pub path_mtu: Option<u32>,
+    /// What the kernel currently assumes for this destination (IP_MTU/IPV6_MTU).
+    pub kernel_pmtu: Option<u32>,
+}
+
+fn ip_header(ip: IpAddr) -> u32 {
+    if ip.is_ipv4() {
+        20
+    } else {
+        40
+    }
+}
+
+/// Smallest MTU every link must carry: 576 for IPv4, 1280 for IPv6.
+fn min_mtu(ip: IpAddr) -> u32 {
+    if ip.is_ipv4() {
+        576
+    } else {
+        1280
+    }
+}
+
+/// TCP MSS for a given MTU (IP and 20-byte TCP header subtracted).
+pub fn mss_for(mtu: u32, ip: IpAddr) -> u32 {
+    mtu - ip_header(ip) - 20
+}
+
+fn set_int_opt(fd: RawFd, level: libc::c_int, name: libc::c_int, value: libc::c_int) -> std::io::Result<()> {
+    // SAFETY: fd is an open socket and value outlives the call.
+    let rc = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+fn get_int_opt(fd: RawFd, level: libc::c_int, name: libc::c_int) -> std::io::Result<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: value and len are valid for writes of the sizes passed.
+    let rc = unsafe { libc::getsockopt(fd, level, name, &mut value as *mut libc::c_int as *mut libc::c_void, &mut len) };
+    if rc == 0 {
+        Ok(value)
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Set DF on every packet, ignoring (and not updating from) the cached PMTU, so that sizes
+/// above a previously learned PMTU can still be sent.
+fn set_dont_fragment(fd: RawFd, ip: IpAddr) -> std::io::Result<()> {
+    if ip.is_ipv4() {
+        set_int_opt(fd, libc::IPPROTO_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_PROBE)
+    } else {
+        set_int_opt(fd, libc::IPPROTO_IPV6, libc::IPV6_MTU_DISCOVER, libc::IPV6_PMTUDISC_PROBE)
+    }
+}
+
+/// The kernel's path MTU for `ip`, read from a connected UDP socket.
+fn kernel_pmtu(ip: IpAddr) -> Option<u32> {
+    let socket = UdpSocket::bind(if ip.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }).ok()?;
+    socket.connect(SocketAddr::new(ip, 9)).ok()?;
+    let fd = socket.as_raw_fd();
+    let mtu = if ip.is_ipv4() {
+        get_int_opt(fd, libc::IPPROTO_IP, libc::IP_MTU)
+    } else {
+        get_int_opt(fd, libc::IPPROTO_IPV6, libc::IPV6_MTU)
+    };
+    mtu.ok().and_then(|m| u32::try_from(m).ok())
+}
+
+/// Outgoing interface for `ip` from the main routing table.
+fn route_to(ip: IpAddr) -> Option<Route> {
+    let routes = if ip.is_ipv4() {
+        std::fs::read_to_string("/proc/net/route").map(|c| route::parse_ipv4_routes(&c))
+    } else {
+        std::fs::read_to_string("/proc/net/ipv6_route").map(|c| route::parse_ipv6_routes(&c))
+    }
+    .ok()?;
+    route::lookup(&routes, ip).cloned()
+}
+
+struct Prober {
+    pinger: surge_ping::Pinger,
+    ip: IpAddr,
+    seq: u16,
+    // Keeps the socket (and its receive task) alive.
+    _client: Client,
+}
+
+impl Prober {
+    async fn new(target: SocketAddr) -> Result<Self> {
+        let kind = if target.is_ipv6() { ICMP::V6 } else { ICMP::V4 };
+        let client = Client::new(&Config::builder().kind(kind).build()).context("cannot open an ICMP socket")?;
+        set_dont_fragment(client.get_socket().get_native_sock(), target.ip()).context("cannot set Don't Fragment")?;
+        // A different identifier than the latency ping so replies are not confused.
+        let ident = PingIdentifier((std::process::id() as u16).wrapping_add(1));
+        let mut pinger = client.pinger(target.ip(), ident).await;
+        if let SocketAddr::V6(v6) = target {
+            pinger.scope_id(v6.scope_id());
+        }
+        pinger.timeout(TIMEOUT);
+        Ok(Self { pinger, ip: target.ip(), seq: 0, _client: client })
+    }
+
+    /// Whether a DF packet of `mtu` bytes (IP header included) gets an echo reply within
+    /// `tries` attempts. Sizes the local interface cannot send fail immediately (EMSGSIZE).
+    async fn fits(&mut self, mtu: u32, tries: u32) -> bool {
+        let payload = vec![0u8; (mtu - ip_header(self.ip) - ICMP_HEADER) as usize];
+        for _ in 0..tries {
+            self.seq = self.seq.wrapping_add(1);
+            match self.pinger.ping(PingSequence(self.seq), &payload).await {
+                Ok(_) => return true,
+                Err(SurgeError::Timeout { .. }) => continue,
+                Err(_) => return false,
+            }
+        }
+        false
+    }
+}
+
+/// Probe the path MTU to `target`. Fast when the full interface MTU gets through (one
+/// round trip); otherwise about one timeout per halving of the search range.
+pub async fn probe(target: SocketAddr) -> Result<PathMtu> {
+    let ip = target.ip();
+    let route = route_to(ip).context("no route to target")?;
+    let iface_mtu = read_first_line(Path::new(&format!("/sys/class/net/{}/mtu", route.iface)))?
+        .and_then(|s| s.trim().parse::<u32>().ok())
+        .context("cannot read interface MTU")?;
+    let mut prober = Prober::new(target).await?;
+
+    let hi = iface_mtu.min(MAX_PROBE);
+    let lo = min_mtu(ip).min(hi);
+    let path_mtu = if prober.fits(hi, 2).await {
+        Some(hi)
+    } else if !prober.fits(lo, 2).await {
+        None
+    } else {
+        // Invariant: lo fits, hi does not.
+        let (mut lo, mut hi) = (lo, hi);
+        // A router that reported "fragmentation needed" has told the kernel the answer already.
+        if let Some(learned) = kernel_pmtu(ip).filter(|&m| lo < m && m < hi) {
+            if prober.fits(learned, 1).await {
+                lo = learned;
+                if !prober.fits(learned + 1, 1).await {
+                    hi = learned + 1;
+                }
+            }
+        }
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            if prober.fits(mid, 1).await {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        Some(lo)
+    };
+
+    Ok(PathMtu { iface: route.iface, iface_mtu, path_mtu, kernel_pmtu: kernel_pmtu(ip) })
+}
+
+fn int_metric(name: String, value: u32) -> Metric {
+    Metric { name, value: MetricValue::Integer(i64::from(value)), unit: Some("bytes".into()), threshold: None }
+}
+
+pub fn report_error(report: &mut DiagnosticReport, host: &str, err: &anyhow::Error) {
+    report.add_finding(Finding {
+        severity: Severity::Info,
+        category: "mtu".into(),
+        message: format!("Path MTU to {} not probed", host),
+        details: Some(format!("{:#}", err)),
+    });
+}
+
+pub fn analyze(report: &mut DiagnosticReport, host: &str, ip: IpAddr, result: &PathMtu) {
+    report.add_metric(int_metric(format!("Interface MTU ({})", result.iface), result.iface_mtu));
+    let Some(path_mtu) = result.path_mtu else {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "mtu".into(),
+            message: format!("Path MTU to {} unknown: no answer even at {} bytes with DF set", host, min_mtu(ip)),
+            details: Some("The target answered normal pings; it or a firewall drops larger or DF-flagged echo requests.".into()),
+        });
+        return;
+    };
+    let shown = if path_mtu == MAX_PROBE && result.iface_mtu > MAX_PROBE {
+        format!("≥ {}", MAX_PROBE)
+    } else {
+        path_mtu.to_string()
+    };
+    report.add_metric(Metric {
+        name: format!("Path MTU to {}", host),
+        value: MetricValue::Text(format!("{} bytes", shown)),
+        unit: None,
+        threshold: None,
+    });
+    if let Some(cached) = result.kernel_pmtu.filter(|&m| m < result.iface_mtu) {
+        report.add_metric(int_metric("Kernel PMTU cache".into(), cached));
+    }
+    if path_mtu >= result.iface_mtu.min(MAX_PROBE) {
+        return;
+    }
+
+    let learned = result.kernel_pmtu.is_some_and(|m| m <= path_mtu);
+    let mss = mss_for(path_mtu, ip);
+    let (severity, details) = if learned {
+        (
+            Severity::Info,
+            format!(
+                "Routers report the bottleneck (ICMP \"fragmentation needed\") and the kernel has lowered \
+                 its PMTU for {} accordingly, so TCP adapts. UDP-based tunnels and protocols that set DF \
+                 themselves may still stall.",
+                ip
+            ),
+        )
+    } else {
+        (
+            Severity::Warning,
+            format!(
+                "Packets larger than {} bytes are dropped without an ICMP \"fragmentation needed\" reply \
+                 (a PMTUD black hole). TCP connections open and small requests work, but transfers hang \
+                 as soon as full-size segments are sent: typical behind VPNs, PPPoE and tunnels.",
+                path_mtu
+            ),
+        )
+    };
+    report.add_finding(Finding {
+        severity,
+        category: "mtu".into(),
+        message: format!(
+            "Path MTU to {} is {} bytes, below the {} MTU of {}",
+            host, path_mtu, result.iface, result.iface_mtu
+        ),
+        details: Some(details),
+    });
+    if learned {
+        return;
+    }
+    report.add_recommendation(Recommendation {
+        priority: 1,
+        action: format!("Lower the MTU of {} to {}", result.iface, path_mtu),
+        command: Some(format!("sudo ip link set dev {} mtu {}", result.iface, path_mtu)),
+        explanation: "Makes this host send packets that fit the path. Persist it in the connection profile \
+                      (NetworkManager, systemd-networkd, or the VPN configuration's MTU setting)."
+            .into(),
+    });
+    report.add_recommendation(Recommendation {
+        priority: 2,
+        action: format!("Or clamp the TCP MSS to {} for traffic leaving {}", mss, result.iface),
+        command: Some(format!(
+            "sudo {} -t mangle -A POSTROUTING -o {} -p tcp --tcp-flags SYN,RST SYN -j TCPMSS --set-mss {}",
+            if ip.is_ipv6() { "ip6tables" } else { "iptables" },
+            result.iface,
+            mss
+        )),
+        explanation: format!(
+            "On routers and VPN gateways this fixes TCP for every client behind them. The nftables \
+             equivalent, for IPv4 and IPv6 alike and clamping to the route MTU: `nft add rule inet \
+             mangle postrouting oifname \"{}\" tcp flags syn tcp option maxseg size set rt mtu`. \
+             It needs an `inet mangle` table with a `postrouting` chain (`type filter hook \
+             postrouting priority mangle`); create them first if `nft list ruleset` has none.",
+            result.iface
+        ),
+    });
+}
@@ -0,0 +1,236 @@
+//! In-process ICMP echo via surge-ping: unprivileged datagram sockets where
+//! `net.ipv4.ping_group_range` allows them, raw sockets otherwise. IPv4 and IPv6.
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation, Threshold};
+use crate::core::severity::Severity;
+use anyhow::{anyhow, Context, Result};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use surge_ping::{Client, Config, PingIdentifier, PingSequence, SurgeError, ICMP};
+
+/// Gap between echo requests; ping(8) allows 200 ms as the unprivileged minimum.
+const INTERVAL: Duration = Duration::from_millis(500);
+const TIMEOUT: Duration = Duration::from_secs(2);
+const PAYLOAD: [u8; 56] = [0; 56];
+
+/// Round-trip times of one ping run, in milliseconds; `None` for lost packets.
+#[derive(Debug, Clone)]
+pub struct PingStats {
+    pub target: IpAddr,
+    pub rtts: Vec<Option<f64>>,
+    /// Last send/receive error other than a timeout, e.g. "Network is unreachable".
+    pub error: Option<String>,
+}
+
+impl PingStats {
+    pub fn sent(&self) -> usize {
+        self.rtts.len()
+    }
+
+    fn replies(&self) -> impl Iterator<Item = f64> + '_ {
+        self.rtts.iter().flatten().copied()
+    }
+
+    pub fn received(&self) -> usize {
+        self.replies().count()
+    }
+
+    pub fn loss_percent(&self) -> f64 {
+        if self.rtts.is_empty() {
+            return 0.0;
+        }
+        (self.sent() - self.received()) as f64 * 100.0 / self.sent() as f64
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        self.replies().reduce(f64::min)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        self.replies().reduce(f64::max)
+    }
+
+    pub fn avg(&self) -> Option<f64> {
+        let n = self.received();
+        (n > 0).then(|| self.replies().sum::<f64>() / n as f64)
+    }
+
+    /// Standard deviation as printed by ping(8): sqrt(mean(rtt²) - mean(rtt)²).
+    pub fn mdev(&self) -> Option<f64> {
+        let avg = self.avg()?;
+        let sq = self.replies().map(|r| r * r).sum::<f64>() / self.received() as f64;
+        Some((sq - avg * avg).max(0.0).sqrt())
+    }
+
+    /// Mean absolute difference between consecutive replies (RFC 3550 style, unsmoothed).
+    pub fn jitter(&self) -> Option<f64> {
+        let replies: Vec<f64> = self.replies().collect();
+        if replies.len() < 2 {
+            return None;
+        }
+        let total: f64 = replies.windows(2).map(|w| (w[1] - w[0]).abs()).sum();
+        Some(total / (replies.len() - 1) as f64)
+    }
+}
+
+/// Resolve `host` to an address; literal IPs are used as-is, names take the first result.
+pub async fn resolve(host: &str) -> Result<SocketAddr> {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, 0));
+    }
+    tokio::net::lookup_host((host, 0))
+        .await
+        .with_context(|| format!("cannot resolve {}", host))?
+        .next()
+        .ok_or_else(|| anyhow!("{} has no addresses", host))
+}
+
+/// Send `count` echo requests to `target`. Fails only when no ICMP socket can be opened.
+pub async fn ping(target: SocketAddr, count: usize) -> Result<PingStats> {
+    let kind = if target.is_ipv6() { ICMP::V6 } else { ICMP::V4 };
+    let client = Client::new(&Config::builder().kind(kind).build())
+        .context("cannot open an ICMP socket")?;
+    let mut pinger = client.pinger(target.ip(), PingIdentifier(std::process::id() as u16)).await;
+    if let SocketAddr::V6(v6) = target {
+        pinger.scope_id(v6.scope_id());
+    }
+    pinger.timeout(TIMEOUT);
+
+    let mut stats = PingStats { target: target.ip(), rtts: Vec::with_capacity(count), error: None };
+    for seq in 0..count {
+        if seq > 0 {
+            tokio::time::sleep(INTERVAL).await;
+        }
+        match pinger.ping(PingSequence(seq as u16), &PAYLOAD).await {
+            Ok((_, rtt)) => stats.rtts.push(Some(rtt.as_secs_f64() * 1000.0)),
+            Err(SurgeError::Timeout { .. }) => stats.rtts.push(None),
+            Err(e) => {
+                stats.rtts.push(None);
+                stats.error = Some(e.to_string());
+            }
+        }
+    }
+    Ok(stats)
+}
+
+fn ms_metric(name: &str, value: f64, threshold: Option<Threshold>) -> Metric {
+    Metric {
+        name: name.into(),
+        value: MetricValue::Float(value),
+        unit: Some("ms".into()),
+        threshold,
+    }
+}
+
+/// Add latency/loss metrics and findings for a finished run.
+pub fn analyze(report: &mut DiagnosticReport, host: &str, stats: &PingStats) {
+    let loss = stats.loss_percent();
+    report.add_metric(Metric {
+        name: "Packets sent/received".into(),
+        value: MetricValue::Text(format!("{}/{}", stats.sent(), stats.received())),
+        unit: None,
+        threshold: None,
+    });
+    report.add_metric(Metric {
+        name: "Packet loss".into(),
+        value: MetricValue::Float(loss),
+        unit: Some("%".into()),
+        threshold: Some(Threshold { warning: 1.0, critical: 20.0 }),
+    });
+
+    let Some(avg) = stats.avg() else {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "connectivity".into(),
+            message: format!("Ping to {} failed; host may be unreachable.", host),
+            details: Some(match &stats.error {
+                Some(e) => format!("{} ({}). Check firewall, routing, and DNS.", e, stats.target),
+                None => format!(
+                    "No reply from {} within {} s per packet. Check firewall, routing, and DNS; \
+                     some hosts drop ICMP echo entirely.",
+                    stats.target,
+                    TIMEOUT.as_secs()
+                ),
+            }),
+        });
+        return;
+    };
+
+    report.add_metric(ms_metric("Ping latency (min)", stats.min().unwrap_or(avg), None));
+    report.add_metric(ms_metric(
+        "Ping latency (avg)",
+        avg,
+        Some(Threshold { warning: 100.0, critical: 500.0 }),
+    ));
+    report.add_metric(ms_metric("Ping latency (max)", stats.max().unwrap_or(avg), None));
+    report.add_metric(ms_metric("Ping latency (mdev)", stats.mdev().unwrap_or(0.0), None));
+    if let Some(jitter) = stats.jitter() {
+        report.add_metric(ms_metric(
+            "Jitter",
+            jitter,
+            Some(Threshold { warning: 30.0, critical: 100.0 }),
+        ));
+        if jitter > 30.0 {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "latency".into(),
+                message: format!("Unstable latency to {} ({:.0} ms jitter)", host, jitter),
+                details: Some(
+                    "Large swings between replies usually come from Wi-Fi interference or a \
+                     saturated uplink (bufferbloat); voice and video calls suffer first."
+                        .into(),
+                ),
+            });
+        }
+    }
+
+    if avg > 200.0 {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "latency".into(),
+            message: format!("High latency to {} ({:.0} ms avg)", host, avg),
+            details: Some("Check WiFi, cable, or ISP.".into()),
+        });
+    }
+    if loss > 0.0 {
+        report.add_finding(Finding {
+            severity: if loss >= 20.0 { Severity::Critical } else { Severity::Warning },
+            category: "connectivity".into(),
+            message: format!(
+                "{:.0}% packet loss to {} ({} of {} lost)",
+                loss,
+                host,
+                stats.sent() - stats.received(),
+                stats.sent()
+            ),
+            details: Some(
+                "Loss forces TCP retransmits and stalls; check cabling, Wi-Fi signal and \
+                 interface error counters."
+                    .into(),
+            ),
+        });
+    }
+}
+
+/// Finding for a host that cannot be pinged at all (resolution or socket failure).
+pub fn report_error(report: &mut DiagnosticReport, host: &str, err: &anyhow::Error) {
+    let permission = err
+        .chain()
+        .filter_map(|e| e.downcast_ref::<std::io::Error>())
+        .any(|e| e.kind() == std::io::ErrorKind::PermissionDenied);
+    report.add_finding(Finding {
+        severity: Severity::Info,
+        category: "connectivity".into(),
+        message: format!("Could not ping {}: {:#}", host, err),
+        details: None,
+    });
+    if permission {
+        report.add_recommendation(Recommendation {
+            priority: 3,
+            action: "Allow unprivileged ICMP sockets for all groups".into(),
+            command: Some("sudo sysctl -w net.ipv4.ping_group_range='0 2147483647'".into()),
+            explanation: "rustwhy pings in-process; without this sysctl or root it cannot open an ICMP socket."
+                .into(),
+        });
+    }
+}
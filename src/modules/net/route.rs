@@ -0,0 +1,472 @@
+//! Routing analysis: /proc/net/route, /proc/net/ipv6_route, policy rules from `ip rule`,
+//! default gateways per family, VPN routes that capture all traffic, and the neighbor
+//! (ARP/NDP) state of the first hop.
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use crate::utils::{command_exists, read_first_line, run_cmd};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, UdpSocket};
+use std::path::Path;
+use std::time::Duration;
+
+const RTF_UP: u32 = 0x0001;
+const RTF_GATEWAY: u32 = 0x0002;
+const RTF_REJECT: u32 = 0x0200;
+/// Interface name prefixes of common VPN and tunnel drivers.
+const VPN_PREFIXES: [&str; 10] = ["tun", "tap", "wg", "ppp", "tailscale", "utun", "ipsec", "vpn", "nordlynx", "zt"];
+
+/// One route of the main table (IPv4) or of any table (IPv6, as the kernel lists them).
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub iface: String,
+    pub dest: IpAddr,
+    pub prefix: u8,
+    pub gateway: Option<IpAddr>,
+    pub metric: u32,
+}
+
+impl Route {
+    pub fn is_default(&self) -> bool {
+        self.prefix == 0
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.dest, ip) {
+            (IpAddr::V4(d), IpAddr::V4(a)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+                u32::from(d) & mask == u32::from(a) & mask
+            }
+            (IpAddr::V6(d), IpAddr::V6(a)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+                u128::from(d) & mask == u128::from(a) & mask
+            }
+            _ => false,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.gateway {
+            Some(gw) => format!("via {} dev {} (metric {})", gw, self.iface, self.metric),
+            None => format!("dev {} (on-link, metric {})", self.iface, self.metric),
+        }
+    }
+}
+
+fn is_vpn(iface: &str) -> bool {
+    VPN_PREFIXES.iter().any(|p| iface.starts_with(p))
+}
+
+/// Parse /proc/net/route; addresses are little-endian hex.
+pub fn parse_ipv4_routes(content: &str) -> Vec<Route> {
+    let hex = |s: &str| u32::from_str_radix(s, 16).ok();
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let f: Vec<&str> = line.split_whitespace().collect();
+            if f.len() < 8 {
+                return None;
+            }
+            let flags = hex(f[3])?;
+            if flags & RTF_UP == 0 || flags & RTF_REJECT != 0 {
+                return None;
+            }
+            let gateway = Ipv4Addr::from(hex(f[2])?.to_le_bytes());
+            Some(Route {
+                iface: f[0].to_string(),
+                dest: IpAddr::V4(Ipv4Addr::from(hex(f[1])?.to_le_bytes())),
+                prefix: hex(f[7])?.count_ones() as u8,
+                gateway: (flags & RTF_GATEWAY != 0).then_some(IpAddr::V4(gateway)),
+                metric: f[6].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Parse /proc/net/ipv6_route: dest, dest_len, src, src_len, next hop, metric, refcnt, use, flags, iface.
+pub fn parse_ipv6_routes(content: &str) -> Vec<Route> {
+    let addr = |s: &str| u128::from_str_radix(s, 16).ok().map(Ipv6Addr::from);
+    content
+        .lines()
+        .filter_map(|line| {
+            let f: Vec<&str> = line.split_whitespace().collect();
+            if f.len() < 10 {
+                return None;
+            }
+            let flags = u32::from_str_radix(f[8], 16).ok()?;
+            if flags & RTF_UP == 0 || flags & RTF_REJECT != 0 || f[9] == "lo" {
+                return None;
+            }
+            Some(Route {
+                iface: f[9].to_string(),
+                dest: IpAddr::V6(addr(f[0])?),
+                prefix: u8::from_str_radix(f[1], 16).ok()?,
+                gateway: (flags & RTF_GATEWAY != 0).then(|| addr(f[4]).map(IpAddr::V6)).flatten(),
+                metric: u32::from_str_radix(f[5], 16).ok()?,
+            })
+        })
+        .collect()
+}
+
+/// The route the kernel picks for `ip`: longest prefix, then lowest metric.
+pub fn lookup(routes: &[Route], ip: IpAddr) -> Option<&Route> {
+    routes
+        .iter()
+        .filter(|r| r.contains(ip))
+        .max_by_key(|r| (r.prefix, std::cmp::Reverse(r.metric)))
+}
+
+/// A policy routing rule from `ip rule show`.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub priority: u32,
+    pub selector: String,
+    pub table: String,
+}
+
+/// Parse `ip rule show` lines such as "32765:\tnot from all fwmark 0xca6c lookup 51820".
+pub fn parse_rules(output: &str) -> Vec<Rule> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (prio, rest) = line.split_once(':')?;
+            let (selector, table) = rest.trim().split_once(" lookup ")?;
+            Some(Rule {
+                priority: prio.trim().parse().ok()?,
+                selector: selector.trim().to_string(),
+                table: table.split_whitespace().next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Neighbor cache state of `ip` on `iface` (REACHABLE, STALE, FAILED, INCOMPLETE, ...).
+fn neighbor_state(ip: IpAddr, iface: &str) -> Option<String> {
+    if command_exists("ip") {
+        let out = run_cmd(&["ip", "neigh", "show", &ip.to_string(), "dev", iface]).ok()?;
+        return out.split_whitespace().last().map(String::from);
+    }
+    // /proc/net/arp only covers IPv4; flag 0x2 is ATF_COM (resolved).
+    let content = std::fs::read_to_string("/proc/net/arp").ok()?;
+    content.lines().skip(1).find_map(|line| {
+        let f: Vec<&str> = line.split_whitespace().collect();
+        (f.len() >= 6 && f[0] == ip.to_string() && f[5] == iface).then(|| {
+            let flags = u32::from_str_radix(f[2].trim_start_matches("0x"), 16).unwrap_or(0);
+            if flags & 0x2 != 0 { "REACHABLE" } else { "INCOMPLETE" }.to_string()
+        })
+    })
+}
+
+fn neighbor_answers(state: Option<&str>) -> bool {
+    !matches!(state, None | Some("FAILED") | Some("INCOMPLETE"))
+}
+
+/// Neighbor state of `ip`, after prompting the kernel to resolve it when the cache has
+/// nothing useful: a datagram to the discard port forces an ARP/NDP solicitation.
+async fn probe_neighbor(ip: IpAddr, iface: &str) -> Option<String> {
+    let state = neighbor_state(ip, iface);
+    if neighbor_answers(state.as_deref()) {
+        return state;
+    }
+    let bind = if ip.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+    if let Ok(sock) = UdpSocket::bind(bind) {
+        let _ = sock.send_to(b"", (ip, 9));
+    }
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    neighbor_state(ip, iface).or(state)
+}
+
+fn iface_down(iface: &str) -> bool {
+    read_first_line(&Path::new("/sys/class/net").join(iface).join("operstate"))
+        .ok()
+        .flatten()
+        .is_some_and(|s| s.trim() == "down")
+}
+
+fn family(ip: &IpAddr) -> &'static str {
+    if ip.is_ipv4() {
+        "IPv4"
+    } else {
+        "IPv6"
+    }
+}
+
+/// True when some interface has a global-scope IPv6 address (/proc/net/if_inet6, scope 00).
+fn has_global_ipv6() -> bool {
+    std::fs::read_to_string("/proc/net/if_inet6")
+        .unwrap_or_default()
+        .lines()
+        .any(|l| l.split_whitespace().nth(3) == Some("00"))
+}
+
+/// Default routes of one family: metric, plus conflicts between equal-metric defaults.
+/// `missing` is the severity of having none at all.
+fn analyze_defaults(
+    report: &mut DiagnosticReport,
+    routes: &[Route],
+    family: &str,
+    missing: Option<Severity>,
+) {
+    if let Some(severity) = missing.filter(|_| !routes.iter().any(|r| r.is_default())) {
+        report.add_finding(Finding {
+            severity,
+            category: "routing".into(),
+            message: format!("No {} default route", family),
+            details: Some(if severity == Severity::Info {
+                "No interface has a global IPv6 address either, so IPv6 is probably not in use.".into()
+            } else {
+                "Only directly attached networks are reachable.".into()
+            }),
+        });
+    }
+    let mut defaults: Vec<&Route> = routes.iter().filter(|r| r.is_default() && r.gateway.is_some()).collect();
+    defaults.sort_by_key(|r| r.metric);
+    let Some(best) = defaults.first() else {
+        return;
+    };
+    report.add_metric(Metric {
+        name: format!("Default gateway ({})", family),
+        value: MetricValue::Text(best.describe()),
+        unit: None,
+        threshold: None,
+    });
+    let ties: Vec<&&Route> = defaults
+        .iter()
+        .filter(|r| r.metric == best.metric && r.iface != best.iface)
+        .collect();
+    if !ties.is_empty() {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "routing".into(),
+            message: format!(
+                "{} default routes share metric {}: {}",
+                family,
+                best.metric,
+                std::iter::once(best)
+                    .chain(ties.iter().copied())
+                    .map(|r| r.iface.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            details: Some(
+                "The kernel picks whichever was added first, so traffic can leave through the \
+                 wrong uplink and replies may be dropped by reverse-path filtering."
+                    .into(),
+            ),
+        });
+        report.add_recommendation(Recommendation {
+            priority: 2,
+            action: "Give each uplink a distinct route metric".into(),
+            command: Some("ip route show default".into()),
+            explanation: "NetworkManager: nmcli connection modify <conn> ipv4.route-metric <n>.".into(),
+        });
+    } else if defaults.len() > 1 {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "routing".into(),
+            message: format!(
+                "{} {} default routes; {} wins with metric {}",
+                defaults.len(),
+                family,
+                best.iface,
+                best.metric
+            ),
+            details: Some(defaults.iter().map(|r| r.describe()).collect::<Vec<_>>().join("\n")),
+        });
+    }
+}
+
+/// VPNs that take over all traffic: a default via a tunnel, the OpenVPN def1 halves,
+/// or a policy rule pointing at a table whose default goes through a tunnel (wg-quick).
+fn analyze_vpn(report: &mut DiagnosticReport, routes: &[Route], rules: &[Rule]) {
+    let mut capture: Vec<String> = Vec::new();
+    for r in routes.iter().filter(|r| is_vpn(&r.iface)) {
+        let def1 = r.prefix == 1 && routes.iter().any(|o| o.prefix == 1 && o.iface == r.iface && o.dest != r.dest);
+        if (r.is_default() || def1) && !capture.iter().any(|c| c.contains(&r.iface)) {
+            capture.push(format!("{} {}", family(&r.dest), r.iface));
+        }
+    }
+    for rule in rules.iter().filter(|r| !matches!(r.table.as_str(), "local" | "main" | "default")) {
+        let Ok(out) = run_cmd(&["ip", "route", "show", "table", &rule.table]) else {
+            continue;
+        };
+        let Some(dev) = out
+            .lines()
+            .filter(|l| l.starts_with("default"))
+            .find_map(|l| l.split_whitespace().skip_while(|w| *w != "dev").nth(1))
+        else {
+            continue;
+        };
+        if is_vpn(dev) && !capture.iter().any(|c| c.contains(dev)) {
+            capture.push(format!("{} (rule {}: {} lookup {})", dev, rule.priority, rule.selector, rule.table));
+        }
+    }
+    if !capture.is_empty() {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "routing".into(),
+            message: format!("A VPN captures all traffic: {}", capture.join(", ")),
+            details: Some(
+                "Every connection, including to the local network, leaves through the tunnel. If \
+                 hosts are unreachable, check the VPN first: disconnect it or exclude the target."
+                    .into(),
+            ),
+        });
+    }
+}
+
+/// Routing checks, plus which hop fails for `target` when `reachable` says the ping failed.
+pub async fn analyze(report: &mut DiagnosticReport, target: Option<IpAddr>, reachable: Option<bool>) {
+    let v4 = std::fs::read_to_string("/proc/net/route")
+        .map(|c| parse_ipv4_routes(&c))
+        .unwrap_or_default();
+    let v6 = std::fs::read_to_string("/proc/net/ipv6_route")
+        .map(|c| parse_ipv6_routes(&c))
+        .unwrap_or_default();
+    let rules = if command_exists("ip") {
+        run_cmd(&["ip", "rule", "show"]).map(|o| parse_rules(&o)).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    // An unroutable target gets the hop-0 finding below instead.
+    let unroutable = target
+        .filter(|t| !t.is_loopback())
+        .is_some_and(|t| lookup(if t.is_ipv4() { &v4 } else { &v6 }, t).is_none());
+    let check = |is_target_family: bool, severity: Severity| {
+        (!(unroutable && is_target_family)).then_some(severity)
+    };
+    let v6_missing = if has_global_ipv6() { Severity::Warning } else { Severity::Info };
+    analyze_defaults(report, &v4, "IPv4", check(target.is_some_and(|t| t.is_ipv4()), Severity::Warning));
+    analyze_defaults(report, &v6, "IPv6", check(target.is_some_and(|t| t.is_ipv6()), v6_missing));
+    let all: Vec<Route> = v4.iter().chain(v6.iter()).cloned().collect();
+    analyze_vpn(report, &all, &rules);
+
+    let Some(target) = target.filter(|t| !t.is_loopback()) else {
+        return;
+    };
+    let table = if target.is_ipv4() { &v4 } else { &v6 };
+    let Some(route) = lookup(table, target) else {
+        report.add_finding(Finding {
+            severity: Severity::Critical,
+            category: "routing".into(),
+            message: format!(
+                "No route to {}: hop 0 (this host) has no {} default route",
+                target,
+                family(&target)
+            ),
+            details: Some("Packets are rejected locally with \"Network is unreachable\".".into()),
+        });
+        report.add_recommendation(Recommendation {
+            priority: 1,
+            action: "Renew the network configuration or add a default route".into(),
+            command: Some("ip route show; sudo dhclient -v".into()),
+            explanation: "A missing default route usually means DHCP did not complete.".into(),
+        });
+        return;
+    };
+    report.add_metric(Metric {
+        name: format!("Route to {}", target),
+        value: MetricValue::Text(route.describe()),
+        unit: None,
+        threshold: None,
+    });
+    if iface_down(&route.iface) {
+        report.add_finding(Finding {
+            severity: Severity::Critical,
+            category: "routing".into(),
+            message: format!("Hop 0 fails: the route to {} uses {}, which is down", target, route.iface),
+            details: None,
+        });
+        return;
+    }
+    if is_vpn(&route.iface) {
+        if reachable == Some(false) {
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "routing".into(),
+                message: format!(
+                    "Hop 1 is the VPN tunnel {}; {} is lost inside or beyond the tunnel",
+                    route.iface, target
+                ),
+                details: Some("Check the VPN connection state and whether it routes this destination.".into()),
+            });
+        }
+        return;
+    }
+
+    let neighbor = route.gateway.unwrap_or(target);
+    let state = probe_neighbor(neighbor, &route.iface).await;
+    let proto = if neighbor.is_ipv4() { "ARP" } else { "NDP" };
+    report.add_metric(Metric {
+        name: format!("{} neighbor {}", proto, neighbor),
+        value: MetricValue::Text(state.clone().unwrap_or_else(|| "no entry".into())),
+        unit: None,
+        threshold: None,
+    });
+    if !neighbor_answers(state.as_deref()) {
+        let what = if route.gateway.is_some() {
+            format!("gateway {}", neighbor)
+        } else {
+            format!("{} (on-link)", neighbor)
+        };
+        if reachable == Some(true) {
+            // Traffic gets through, so the neighbor entry is just stale or filtered (proxy ARP, ACLs).
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "routing".into(),
+                message: format!(
+                    "{} on {} did not answer the {} probe, but {} is reachable",
+                    what, route.iface, proto, target
+                ),
+                details: Some(format!(
+                    "Neighbor state {}. Some routers rate-limit or ignore unsolicited probes.",
+                    state.as_deref().unwrap_or("missing")
+                )),
+            });
+            return;
+        }
+        report.add_finding(Finding {
+            severity: Severity::Critical,
+            category: "routing".into(),
+            message: format!("Hop 1 fails: {} on {} does not answer {}", what, route.iface, proto),
+            details: Some(format!(
+                "Neighbor state {}. The link is up but nothing at that address responds: {}.",
+                state.as_deref().unwrap_or("missing"),
+                if route.gateway.is_some() {
+                    "wrong gateway from DHCP, a different VLAN/Wi-Fi network, or the router is down"
+                } else {
+                    "the host is off, or it sits on a different network than the subnet suggests"
+                }
+            )),
+        });
+        report.add_recommendation(Recommendation {
+            priority: 1,
+            action: format!("Check the link and address configuration of {}", route.iface),
+            command: Some(format!("ip addr show dev {}; ip neigh show dev {}", route.iface, route.iface)),
+            explanation: "Reconnecting or renewing DHCP usually fixes a wrong or stale gateway.".into(),
+        });
+    } else if reachable == Some(false) {
+        if let Some(gw) = route.gateway {
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "routing".into(),
+                message: format!(
+                    "Hop 1 (gateway {}) answers {}; {} is lost beyond it",
+                    gw, proto, target
+                ),
+                details: Some(
+                    "The local network is fine. The problem lies upstream (router WAN link, ISP) or \
+                     the target filters ICMP."
+                        .into(),
+                ),
+            });
+            report.add_recommendation(Recommendation {
+                priority: 2,
+                action: "Trace where packets stop".into(),
+                command: Some(format!("traceroute -n {}", target)),
+                explanation: "The last responding hop marks where the path breaks.".into(),
+            });
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! Socket tables from /proc/net/{tcp,tcp6,udp,udp6,unix} and their owning processes via
+//! the `socket:[inode]` links in /proc/<pid>/fd. Listen backlogs come from `ss`, since
+//! /proc/net/tcp does not show them.
+
+use crate::utils::{command_exists, run_cmd};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+pub const TCP_ESTABLISHED: u8 = 0x01;
+pub const TCP_TIME_WAIT: u8 = 0x06;
+/// Also the state of an unconnected (bound) UDP socket.
+pub const TCP_CLOSE: u8 = 0x07;
+pub const TCP_CLOSE_WAIT: u8 = 0x08;
+pub const TCP_LISTEN: u8 = 0x0A;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Proto {
+    Tcp,
+    Udp,
+}
+
+impl Proto {
+    pub fn name(self) -> &'static str {
+        match self {
+            Proto::Tcp => "tcp",
+            Proto::Udp => "udp",
+        }
+    }
+}
+
+/// One row of /proc/net/{tcp,udp}[6].
+#[derive(Debug, Clone)]
+pub struct InetSocket {
+    pub proto: Proto,
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    pub state: u8,
+    pub uid: u32,
+    /// 0 for sockets without a file (TIME_WAIT, orphans).
+    pub inode: u64,
+}
+
+impl InetSocket {
+    /// Accepting connections (TCP) or bound without a peer (UDP).
+    pub fn is_listener(&self) -> bool {
+        match self.proto {
+            Proto::Tcp => self.state == TCP_LISTEN,
+            Proto::Udp => self.state == TCP_CLOSE && self.remote.port() == 0,
+        }
+    }
+}
+
+pub fn state_name(state: u8) -> &'static str {
+    match state {
+        0x01 => "ESTABLISHED",
+        0x02 => "SYN_SENT",
+        0x03 => "SYN_RECV",
+        0x04 => "FIN_WAIT1",
+        0x05 => "FIN_WAIT2",
+        0x06 => "TIME_WAIT",
+        0x07 => "CLOSE",
+        0x08 => "CLOSE_WAIT",
+        0x09 => "LAST_ACK",
+        0x0A => "LISTEN",
+        0x0B => "CLOSING",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Parse "0100007F:1F90" (IPv4) or 32 hex digits plus port (IPv6). Addresses are printed
+/// as native-endian 32-bit words, ports as plain hex.
+fn parse_addr(s: &str) -> Option<SocketAddr> {
+    let (addr, port) = s.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let word = |i: usize| u32::from_str_radix(addr.get(i * 8..i * 8 + 8)?, 16).ok().map(u32::to_ne_bytes);
+    let ip = match addr.len() {
+        8 => IpAddr::V4(Ipv4Addr::from(word(0)?)),
+        32 => {
+            let mut octets = [0u8; 16];
+            for i in 0..4 {
+                octets[i * 4..i * 4 + 4].copy_from_slice(&word(i)?);
+            }
+            let v6 = Ipv6Addr::from(octets);
+            // Dual-stack sockets show IPv4 peers as ::ffff:a.b.c.d.
+            v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(IpAddr::V6(v6))
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+/// Parse one /proc/net/{tcp,udp}[6] file.
+pub fn parse_inet(content: &str, proto: Proto) -> Vec<InetSocket> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let f: Vec<&str> = line.split_whitespace().collect();
+            if f.len() < 10 {
+                return None;
+            }
+            Some(InetSocket {
+                proto,
+                local: parse_addr(f[1])?,
+                remote: parse_addr(f[2])?,
+                state: u8::from_str_radix(f[3], 16).ok()?,
+                uid: f[7].parse().ok()?,
+                inode: f[9].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// An `ss` address: "0.0.0.0:80", "[::]:22", "127.0.0.53%lo:53" or "*:80" (all addresses,
+/// returned as both unspecified addresses).
+fn parse_ss_addr(s: &str) -> Vec<SocketAddr> {
+    let Some((host, port)) = s.rsplit_once(':') else {
+        return Vec::new();
+    };
+    let Ok(port) = port.parse() else {
+        return Vec::new();
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let host = host.split_once('%').map_or(host, |(h, _)| h);
+    if host == "*" {
+        return vec![
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port),
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port),
+        ];
+    }
+    let ip = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(v6)) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(IpAddr::V6(v6)),
+        Ok(ip) => ip,
+        Err(_) => return Vec::new(),
+    };
+    vec![SocketAddr::new(ip, port)]
+}
+
+/// Parse `ss -ltn`: for listeners Recv-Q is the accept queue length and Send-Q its limit
+/// (the listen() backlog capped by net.core.somaxconn).
+pub fn parse_ss_listeners(output: &str) -> HashMap<SocketAddr, (u64, u64)> {
+    let mut out = HashMap::new();
+    for line in output.lines() {
+        let f: Vec<&str> = line.split_whitespace().collect();
+        if f.len() < 4 || f[0] != "LISTEN" {
+            continue;
+        }
+        let (Ok(queued), Ok(limit)) = (f[1].parse(), f[2].parse()) else {
+            continue;
+        };
+        for addr in parse_ss_addr(f[3]) {
+            out.insert(addr, (queued, limit));
+        }
+    }
+    out
+}
+
+/// Accept queue length and limit of every TCP listener, empty without `ss`.
+pub fn listen_queues() -> HashMap<SocketAddr, (u64, u64)> {
+    if !command_exists("ss") {
+        return HashMap::new();
+    }
+    run_cmd(&["ss", "-ltn"])
+        .map(|o| parse_ss_listeners(&o))
+        .unwrap_or_default()
+}
+
+pub fn read_inet_sockets() -> Vec<InetSocket> {
+    let mut out = Vec::new();
+    for (file, proto) in [("tcp", Proto::Tcp), ("tcp6", Proto::Tcp), ("udp", Proto::Udp), ("udp6", Proto::Udp)] {
+        if let Ok(content) = std::fs::read_to_string(format!("/proc/net/{}", file)) {
+            out.extend(parse_inet(&content, proto));
+        }
+    }
+    out
+}
+
+/// A bound unix domain socket from /proc/net/unix.
+#[derive(Debug, Clone)]
+pub struct UnixSocket {
+    /// Filesystem path, or "@name" for the abstract namespace.
+    pub path: String,
+    pub listening: bool,
+    pub inode: u64,
+}
+
+/// __SO_ACCEPTCON in the Flags column marks a listening socket.
+const SO_ACCEPTCON: u32 = 0x0001_0000;
+
+/// Parse /proc/net/unix: Num RefCount Protocol Flags Type St Inode Path. Unnamed sockets are skipped.
+pub fn parse_unix(content: &str) -> Vec<UnixSocket> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let f: Vec<&str> = line.split_whitespace().collect();
+            let path = f.get(7)?;
+            Some(UnixSocket {
+                path: path.to_string(),
+                listening: u32::from_str_radix(f[3], 16).ok()? & SO_ACCEPTCON != 0,
+                inode: f[6].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+pub fn read_unix_sockets() -> Vec<UnixSocket> {
+    std::fs::read_to_string("/proc/net/unix")
+        .map(|c| parse_unix(&c))
+        .unwrap_or_default()
+}
+
+/// Socket inode to the PIDs holding it. Other users' processes are only visible as root.
+pub fn socket_owners() -> HashMap<u64, Vec<u32>> {
+    let mut owners: HashMap<u64, Vec<u32>> = HashMap::new();
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return owners;
+    };
+    for entry in procs.flatten() {
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Ok(target) = std::fs::read_link(fd.path()) else {
+                continue;
+            };
+            let target = target.to_string_lossy();
+            if let Some(inode) = target
+                .strip_prefix("socket:[")
+                .and_then(|s| s.strip_suffix(']'))
+                .and_then(|s| s.parse().ok())
+            {
+                let pids = owners.entry(inode).or_default();
+                if !pids.contains(&pid) {
+                    pids.push(pid);
+                }
+            }
+        }
+    }
+    owners
+}
+
+pub fn user_name(uid: u32) -> String {
+    users::get_user_by_uid(uid)
+        .map(|u| u.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| format!("uid {}", uid))
+}
@@ -0,0 +1,323 @@
+//! TCP/UDP stack health from /proc/net/snmp, /proc/net/snmp6 and /proc/net/netstat sampled
+//! over an interval: retransmits, listen queue overflows and SYN drops, UDP buffer errors
+//! and TCP memory pressure (/proc/net/sockstat against net.ipv4.tcp_mem).
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation, Threshold};
+use crate::core::severity::Severity;
+use crate::utils::format_bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// Retransmit percentage that makes transfers noticeably slow.
+const RETRANS_WARN: f64 = 2.0;
+/// Fewer segments than this in the interval make the rate meaningless.
+const MIN_SEGMENTS: i64 = 100;
+
+/// Counters keyed "Proto.Name", e.g. "Tcp.RetransSegs", "TcpExt.ListenOverflows", "Udp6.RcvbufErrors".
+pub type Counters = HashMap<String, i64>;
+
+/// Parse the header/value line pairs of /proc/net/snmp and /proc/net/netstat.
+pub fn parse_paired(content: &str, out: &mut Counters) {
+    let lines: Vec<&str> = content.lines().collect();
+    for pair in lines.chunks(2) {
+        let [names, values] = pair else {
+            continue;
+        };
+        let (Some((proto, names)), Some((_, values))) = (names.split_once(':'), values.split_once(':')) else {
+            continue;
+        };
+        for (name, value) in names.split_whitespace().zip(values.split_whitespace()) {
+            if let Ok(v) = value.parse() {
+                out.insert(format!("{}.{}", proto, name), v);
+            }
+        }
+    }
+}
+
+/// Parse the UDP part of /proc/net/snmp6 ("Udp6RcvbufErrors  0" per line).
+pub fn parse_snmp6(content: &str, out: &mut Counters) {
+    for line in content.lines() {
+        let mut words = line.split_whitespace();
+        let (Some(name), Some(value)) = (words.next(), words.next()) else {
+            continue;
+        };
+        if let (Some(rest), Ok(v)) = (name.strip_prefix("Udp6"), value.parse()) {
+            out.insert(format!("Udp6.{}", rest), v);
+        }
+    }
+}
+
+pub fn read_counters() -> Counters {
+    let mut out = Counters::new();
+    for path in ["/proc/net/snmp", "/proc/net/netstat"] {
+        if let Ok(content) = std::fs::read_to_string(path) {
+            parse_paired(&content, &mut out);
+        }
+    }
+    if let Ok(content) = std::fs::read_to_string("/proc/net/snmp6") {
+        parse_snmp6(&content, &mut out);
+    }
+    out
+}
+
+/// Two snapshots `SAMPLE_INTERVAL` apart.
+pub struct Sample {
+    pub before: Counters,
+    pub after: Counters,
+}
+
+impl Sample {
+    fn total(&self, keys: &[&str]) -> i64 {
+        keys.iter().map(|k| self.after.get(*k).copied().unwrap_or(0)).sum()
+    }
+
+    fn delta(&self, keys: &[&str]) -> i64 {
+        keys.iter()
+            .map(|k| self.after.get(*k).copied().unwrap_or(0) - self.before.get(*k).copied().unwrap_or(0))
+            .sum::<i64>()
+            .max(0)
+    }
+}
+
+pub async fn sample() -> Sample {
+    let before = read_counters();
+    tokio::time::sleep(SAMPLE_INTERVAL).await;
+    Sample { before, after: read_counters() }
+}
+
+/// TCP memory in use and the tcp_mem pressure threshold, in bytes.
+fn tcp_memory() -> Option<(u64, u64)> {
+    let sockstat = std::fs::read_to_string("/proc/net/sockstat").ok()?;
+    let pages: u64 = sockstat
+        .lines()
+        .find_map(|l| l.strip_prefix("TCP:"))?
+        .split_whitespace()
+        .skip_while(|w| *w != "mem")
+        .nth(1)?
+        .parse()
+        .ok()?;
+    let pressure: u64 = std::fs::read_to_string("/proc/sys/net/ipv4/tcp_mem")
+        .ok()?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()?;
+    let page = procfs::page_size();
+    Some((pages * page, pressure * page))
+}
+
+fn count_metric(name: &str, value: i64) -> Metric {
+    Metric { name: name.into(), value: MetricValue::Integer(value), unit: None, threshold: None }
+}
+
+pub fn analyze(report: &mut DiagnosticReport, s: &Sample) {
+    let secs = SAMPLE_INTERVAL.as_secs();
+
+    // Retransmits.
+    let out_segs = s.delta(&["Tcp.OutSegs"]);
+    let retrans = s.delta(&["Tcp.RetransSegs"]);
+    let total_out = s.total(&["Tcp.OutSegs"]);
+    let total_retrans = s.total(&["Tcp.RetransSegs"]);
+    if out_segs > 0 {
+        report.add_metric(Metric {
+            name: "TCP retransmit rate".into(),
+            value: MetricValue::Float(retrans as f64 * 100.0 / out_segs as f64),
+            unit: Some("%".into()),
+            threshold: Some(Threshold { warning: 1.0, critical: 5.0 }),
+        });
+    }
+    report.add_metric(count_metric("TCP retransmits since boot", total_retrans));
+    let interval_rate = retrans as f64 * 100.0 / out_segs.max(1) as f64;
+    let boot_rate = total_retrans as f64 * 100.0 / total_out.max(1) as f64;
+    if out_segs >= MIN_SEGMENTS && interval_rate >= RETRANS_WARN {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "tcp".into(),
+            message: format!(
+                "{:.1}% of TCP segments are being retransmitted ({} of {} in {} s)",
+                interval_rate, retrans, out_segs, secs
+            ),
+            details: Some(
+                "Every retransmit means a packet was lost and the connection waited at least one \
+                 round trip to resend it, while TCP halves its sending rate. Transfers crawl even \
+                 though the link looks idle. Usual causes: Wi-Fi loss, a faulty cable or NIC, or a \
+                 congested router on the path."
+                    .into(),
+            ),
+        });
+    } else if total_out >= 10_000 && boot_rate >= RETRANS_WARN {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "tcp".into(),
+            message: format!("{:.1}% of TCP segments since boot were retransmissions", boot_rate),
+            details: Some("Not happening right now, but the network lost packets earlier.".into()),
+        });
+    }
+    let syn_retrans = s.delta(&["TcpExt.TCPSynRetrans"]);
+    if syn_retrans > 0 {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "tcp".into(),
+            message: format!("{} connection attempt(s) had to resend their SYN in {} s", syn_retrans, secs),
+            details: Some(
+                "The remote side did not answer the first connection request: the server is \
+                 overloaded, the port is filtered, or packets are lost. Each resend adds 1 s, then 2 s, 4 s..."
+                    .into(),
+            ),
+        });
+    }
+
+    // Listen queues.
+    let overflows = s.delta(&["TcpExt.ListenOverflows"]);
+    // ListenDrops counts overflows too.
+    let syn_drops = s.delta(&["TcpExt.ListenDrops"]);
+    let reqq_drops = s.delta(&["TcpExt.TCPReqQFullDrop"]);
+    report.add_metric(count_metric("Listen overflows since boot", s.total(&["TcpExt.ListenOverflows"])));
+    report.add_metric(count_metric("SYN drops since boot", s.total(&["TcpExt.ListenDrops"])));
+    if overflows > 0 {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "tcp".into(),
+            message: format!("Listen queues overflowed {} time(s) in {} s", overflows, secs),
+            details: Some(
+                "A server is not accepting new connections as fast as they arrive. Clients whose \
+                 handshake completed are dropped and retry after a second or more, which looks like \
+                 random slow or failed connections."
+                    .into(),
+            ),
+        });
+        report.add_recommendation(Recommendation {
+            priority: 2,
+            action: "Find the listener with a full accept queue and raise its backlog".into(),
+            command: Some("ss -ltn  # Recv-Q close to Send-Q = full; then sysctl net.core.somaxconn".into()),
+            explanation: "The backlog passed to listen() is capped by net.core.somaxconn; more worker \
+                          threads help when the application itself is too slow."
+                .into(),
+        });
+    } else if s.total(&["TcpExt.ListenOverflows"]) > 0 {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "tcp".into(),
+            message: format!(
+                "Listen queues overflowed {} time(s) since boot",
+                s.total(&["TcpExt.ListenOverflows"])
+            ),
+            details: Some("Some server could not keep up with incoming connections at some point.".into()),
+        });
+    }
+    if reqq_drops > 0 || syn_drops > overflows {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "tcp".into(),
+            message: format!("{} incoming SYN(s) dropped in {} s", (syn_drops - overflows).max(reqq_drops), secs),
+            details: Some(format!(
+                "The half-open connection queue is full (SYN cookies sent: {}). Either a SYN flood \
+                 or net.ipv4.tcp_max_syn_backlog is too small for the connection rate.",
+                s.delta(&["TcpExt.SyncookiesSent"])
+            )),
+        });
+    }
+
+    // UDP buffers.
+    let rcvbuf = s.delta(&["Udp.RcvbufErrors", "Udp6.RcvbufErrors"]);
+    let total_rcvbuf = s.total(&["Udp.RcvbufErrors", "Udp6.RcvbufErrors"]);
+    let sndbuf = s.delta(&["Udp.SndbufErrors", "Udp6.SndbufErrors"]);
+    report.add_metric(count_metric("UDP receive buffer errors since boot", total_rcvbuf));
+    if rcvbuf > 0 {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "udp".into(),
+            message: format!("{} UDP datagram(s) dropped in {} s: receive buffer full", rcvbuf, secs),
+            details: Some(
+                "An application reads its UDP socket slower than data arrives, so the kernel throws \
+                 datagrams away. Video calls stutter, DNS and metrics (statsd, syslog) lose data."
+                    .into(),
+            ),
+        });
+        report.add_recommendation(Recommendation {
+            priority: 2,
+            action: "Allow larger UDP receive buffers".into(),
+            command: Some("sudo sysctl -w net.core.rmem_max=26214400 net.core.rmem_default=1048576".into()),
+            explanation: "Applications that set SO_RCVBUF are capped at rmem_max; the rest get rmem_default.".into(),
+        });
+    } else if total_rcvbuf > 0 {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "udp".into(),
+            message: format!("{} UDP datagram(s) dropped since boot because a receive buffer was full", total_rcvbuf),
+            details: None,
+        });
+    }
+    if sndbuf > 0 {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "udp".into(),
+            message: format!("{} UDP send(s) failed in {} s: send buffer full", sndbuf, secs),
+            details: Some("The application sends faster than the interface can transmit.".into()),
+        });
+    }
+
+    // TCP memory.
+    if let Some((used, pressure)) = tcp_memory() {
+        report.add_metric(Metric {
+            name: "TCP memory".into(),
+            value: MetricValue::Text(format!("{} (pressure at {})", format_bytes(used), format_bytes(pressure))),
+            unit: None,
+            threshold: None,
+        });
+        if used >= pressure {
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "tcp".into(),
+                message: format!(
+                    "TCP is under memory pressure ({} used, pressure threshold {})",
+                    format_bytes(used),
+                    format_bytes(pressure)
+                ),
+                details: Some(
+                    "The kernel shrinks socket buffers for all connections, so throughput drops \
+                     everywhere. Many connections with full buffers (slow readers) cause this."
+                        .into(),
+                ),
+            });
+            report.add_recommendation(Recommendation {
+                priority: 2,
+                action: "Raise the TCP memory limits".into(),
+                command: Some("sysctl net.ipv4.tcp_mem".into()),
+                explanation: "Values are in pages: min, pressure, max.".into(),
+            });
+        }
+    }
+    let pressures = s.delta(&["TcpExt.TCPMemoryPressures"]);
+    let aborts = s.total(&["TcpExt.TCPAbortOnMemory"]);
+    if pressures > 0 {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "tcp".into(),
+            message: format!("TCP entered memory pressure {} time(s) in {} s", pressures, secs),
+            details: None,
+        });
+    }
+    if aborts > 0 {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "tcp".into(),
+            message: format!("{} TCP connection(s) were aborted for lack of memory since boot", aborts),
+            details: Some("Seen as sudden resets under load; check net.ipv4.tcp_mem and orphan sockets.".into()),
+        });
+    }
+    let pruned = s.delta(&["TcpExt.PruneCalled"]);
+    if pruned > 0 {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "tcp".into(),
+            message: format!("Receive queues were pruned {} time(s) in {} s", pruned, secs),
+            details: Some(
+                "Sockets received more than their buffer holds because the application reads \
+                 slowly; the kernel collapses or drops queued data and the sender must retransmit."
+                    .into(),
+            ),
+        });
+    }
+}
@@ -0,0 +1,483 @@
+//! Wi-Fi link quality: signal and noise from /proc/net/wireless, station info (bitrates,
+//! retries, failures, beacon loss), band and channel width, power save, and channel
+//! congestion from the adapter's cached scan results. Everything beyond /proc/net/wireless
+//! comes from the `iw` tool; there is no native nl80211 client, so without `iw` only the
+//! /proc/net/wireless values are reported.
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation, Threshold};
+use crate::core::severity::Severity;
+use crate::utils::{command_exists, run_cmd};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// dBm thresholds: below FAIR throughput drops, below WEAK connections stall.
+const SIGNAL_FAIR: i32 = -70;
+const SIGNAL_WEAK: i32 = -78;
+const SIGNAL_UNUSABLE: i32 = -85;
+/// Signal-to-noise ratio (dB) below which high rates are impossible.
+const SNR_LOW: i32 = 20;
+/// Share of transmitted frames that needed a retry.
+const RETRY_RATE: f64 = 0.10;
+/// Other networks on overlapping 2.4 GHz channels.
+const CONGESTED_BSS: usize = 6;
+
+/// One line of /proc/net/wireless.
+#[derive(Debug, Clone, Default)]
+pub struct ProcWireless {
+    /// Link quality on the driver's scale (usually out of 70).
+    pub quality: Option<f64>,
+    pub level: Option<i32>,
+    pub noise: Option<i32>,
+    /// Frames dropped after exhausting MAC retries.
+    pub discarded_retry: u64,
+    pub missed_beacons: u64,
+}
+
+/// Parse /proc/net/wireless: "wlan0: 0000   54.  -56.  -256  0 0 0 12 0  0".
+pub fn parse_proc_wireless(content: &str) -> HashMap<String, ProcWireless> {
+    let num = |s: &str| s.trim_end_matches('.').parse::<f64>().ok();
+    content
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, rest) = line.split_once(':')?;
+            let f: Vec<&str> = rest.split_whitespace().collect();
+            if f.len() < 10 {
+                return None;
+            }
+            // Old drivers report unsigned levels (256 + dBm); -256 means "not available".
+            let dbm = |s: &str| {
+                num(s).map(|v| v as i32).map(|v| if v > 0 { v - 256 } else { v }).filter(|v| *v > -256 && *v < 0)
+            };
+            Some((
+                name.trim().to_string(),
+                ProcWireless {
+                    quality: num(f[1]),
+                    level: dbm(f[2]),
+                    noise: dbm(f[3]),
+                    discarded_retry: f[7].parse().unwrap_or(0),
+                    missed_beacons: f[9].parse().unwrap_or(0),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// "key: value" lines of `iw` output (the first occurrence of each key wins).
+pub fn parse_iw_fields(output: &str) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    for line in output.lines().skip(1) {
+        if let Some((key, value)) = line.trim().split_once(':') {
+            fields.entry(key.trim().to_string()).or_insert_with(|| value.trim().to_string());
+        }
+    }
+    fields
+}
+
+/// Leading number of a value such as "-56 [-58, -60] dBm" or "866.7 MBit/s VHT-MCS 9".
+fn leading_num(value: &str) -> Option<f64> {
+    value.split_whitespace().next()?.parse().ok()
+}
+
+/// Channel number for a center frequency in MHz.
+pub fn channel(freq: u32) -> Option<u32> {
+    match freq {
+        2484 => Some(14),
+        2412..=2472 => Some((freq - 2407) / 5),
+        5955..=7115 => Some((freq - 5950) / 5),
+        5000..=5925 => Some((freq - 5000) / 5),
+        _ => None,
+    }
+}
+
+pub fn band(freq: u32) -> &'static str {
+    match freq {
+        0..=2500 => "2.4 GHz",
+        2501..=5925 => "5 GHz",
+        _ => "6 GHz",
+    }
+}
+
+/// One entry of `iw dev <if> scan dump`.
+#[derive(Debug, Clone)]
+pub struct Bss {
+    pub bssid: String,
+    pub ssid: String,
+    pub freq: u32,
+    pub signal: Option<f64>,
+    pub associated: bool,
+}
+
+pub fn parse_scan_dump(output: &str) -> Vec<Bss> {
+    let mut out = Vec::new();
+    let mut current: Option<Bss> = None;
+    for line in output.lines() {
+        if let Some(rest) = line.strip_prefix("BSS ") {
+            out.extend(current.take());
+            current = Some(Bss {
+                bssid: rest.chars().take(17).collect(),
+                ssid: String::new(),
+                freq: 0,
+                signal: None,
+                associated: rest.contains("-- associated"),
+            });
+            continue;
+        }
+        let Some(bss) = current.as_mut() else {
+            continue;
+        };
+        // Only the top-level fields; nested IE blocks are indented further.
+        let Some(field) = line.strip_prefix('\t').filter(|l| !l.starts_with('\t')) else {
+            continue;
+        };
+        match field.split_once(':') {
+            Some(("freq", v)) => bss.freq = leading_num(v.trim()).unwrap_or(0.0) as u32,
+            Some(("signal", v)) => bss.signal = leading_num(v.trim()),
+            Some(("SSID", v)) => bss.ssid = v.trim().to_string(),
+            _ => {}
+        }
+    }
+    out.extend(current);
+    out
+}
+
+/// Everything known about one wireless interface's current association.
+#[derive(Debug, Clone, Default)]
+pub struct WifiLink {
+    pub iface: String,
+    pub connected: bool,
+    pub ssid: Option<String>,
+    pub bssid: Option<String>,
+    pub freq: Option<u32>,
+    pub width: Option<String>,
+    pub signal: Option<i32>,
+    pub noise: Option<i32>,
+    pub tx_bitrate: Option<f64>,
+    pub rx_bitrate: Option<f64>,
+    pub tx_packets: Option<u64>,
+    pub tx_retries: Option<u64>,
+    pub tx_failed: Option<u64>,
+    pub beacon_loss: Option<u64>,
+    pub power_save: Option<bool>,
+    pub proc: Option<ProcWireless>,
+}
+
+impl WifiLink {
+    pub fn snr(&self) -> Option<i32> {
+        Some(self.signal? - self.noise?)
+    }
+
+    pub fn retry_rate(&self) -> Option<f64> {
+        let sent = self.tx_packets.filter(|p| *p > 0)? as f64;
+        Some(self.tx_retries? as f64 / sent)
+    }
+}
+
+/// Interfaces with a wireless extension or nl80211 PHY in sysfs.
+fn wireless_interfaces() -> Vec<String> {
+    let mut names: Vec<String> = crate::utils::list_dir(Path::new("/sys/class/net"))
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|p| {
+            let wireless = p.join("wireless").exists() || p.join("phy80211").exists();
+            if wireless { Some(p.file_name()?.to_string_lossy().into_owned()) } else { None }
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+fn read_link(iface: &str, proc: Option<ProcWireless>) -> WifiLink {
+    let mut link = WifiLink {
+        iface: iface.to_string(),
+        connected: proc.as_ref().is_some_and(|p| p.level.is_some()),
+        signal: proc.as_ref().and_then(|p| p.level),
+        noise: proc.as_ref().and_then(|p| p.noise),
+        proc,
+        ..Default::default()
+    };
+    if command_exists("iw") {
+        read_iw(&mut link);
+    }
+    // Wireless extensions count frames given up after the retry limit and missed beacons.
+    if let Some(p) = &link.proc {
+        link.tx_failed = link.tx_failed.or(Some(p.discarded_retry));
+        link.beacon_loss = link.beacon_loss.or(Some(p.missed_beacons));
+    }
+    link
+}
+
+/// Association, station counters, channel width and power save, parsed from `iw` output.
+fn read_iw(link: &mut WifiLink) {
+    let iface = link.iface.clone();
+    let iw = |args: &[&str]| {
+        let mut cmd = vec!["iw", "dev", iface.as_str()];
+        cmd.extend_from_slice(args);
+        run_cmd(&cmd).ok()
+    };
+    if let Some(out) = iw(&["link"]) {
+        link.connected = out.starts_with("Connected to");
+        link.bssid = out.strip_prefix("Connected to ").map(|s| s.chars().take(17).collect());
+        let f = parse_iw_fields(&out);
+        link.ssid = f.get("SSID").cloned();
+        link.freq = f.get("freq").and_then(|v| leading_num(v)).map(|v| v as u32);
+        link.signal = f.get("signal").and_then(|v| leading_num(v)).map(|v| v as i32).or(link.signal);
+        link.tx_bitrate = f.get("tx bitrate").and_then(|v| leading_num(v));
+        link.rx_bitrate = f.get("rx bitrate").and_then(|v| leading_num(v));
+    }
+    if link.connected {
+        if let Some(out) = iw(&["station", "dump"]) {
+            let f = parse_iw_fields(&out);
+            let count = |k: &str| f.get(k).and_then(|v| v.parse().ok());
+            link.tx_packets = count("tx packets");
+            link.tx_retries = count("tx retries");
+            link.tx_failed = count("tx failed");
+            link.beacon_loss = count("beacon loss");
+            if let Some(avg) = f.get("signal avg").and_then(|v| leading_num(v)) {
+                link.signal = Some(avg as i32);
+            }
+        }
+    }
+    if let Some(out) = iw(&["info"]) {
+        // "channel 36 (5180 MHz), width: 80 MHz, center1: 5210 MHz"
+        link.width = out
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("channel "))
+            .and_then(|l| l.split(", ").find_map(|p| p.strip_prefix("width: ")))
+            .map(String::from);
+    }
+    if let Some(out) = iw(&["get", "power_save"]) {
+        link.power_save = out.split_once(':').map(|(_, v)| v.trim() == "on");
+    }
+}
+
+fn text_metric(name: String, value: String) -> Metric {
+    Metric { name, value: MetricValue::Text(value), unit: None, threshold: None }
+}
+
+fn analyze_link(report: &mut DiagnosticReport, link: &WifiLink) {
+    let iface = &link.iface;
+    if !link.connected {
+        report.add_metric(text_metric(format!("{} Wi-Fi", iface), "not connected".into()));
+        return;
+    }
+    let network = match (&link.ssid, &link.bssid) {
+        (Some(ssid), Some(bssid)) => format!("\"{}\" ({})", ssid, bssid),
+        (Some(ssid), None) => format!("\"{}\"", ssid),
+        (None, Some(bssid)) => bssid.clone(),
+        (None, None) => "connected".into(),
+    };
+    report.add_metric(text_metric(format!("{} Wi-Fi", iface), network));
+    if let Some(signal) = link.signal {
+        report.add_metric(Metric {
+            name: format!("{} signal", iface),
+            value: MetricValue::Integer(i64::from(signal)),
+            unit: Some("dBm".into()),
+            threshold: None,
+        });
+    }
+    if let Some(noise) = link.noise {
+        report.add_metric(Metric {
+            name: format!("{} noise", iface),
+            value: MetricValue::Integer(i64::from(noise)),
+            unit: Some("dBm".into()),
+            threshold: None,
+        });
+    }
+    if let Some(quality) = link.proc.as_ref().and_then(|p| p.quality) {
+        report.add_metric(text_metric(format!("{} link quality", iface), format!("{:.0}/70", quality)));
+    }
+    if let Some(freq) = link.freq {
+        let mut desc = format!("{} ({} MHz, {}", channel(freq).map_or("?".into(), |c| c.to_string()), freq, band(freq));
+        if let Some(width) = &link.width {
+            desc.push_str(&format!(", {} wide", width));
+        }
+        desc.push(')');
+        report.add_metric(text_metric(format!("{} channel", iface), desc));
+    }
+    if link.tx_bitrate.is_some() || link.rx_bitrate.is_some() {
+        let rate = |r: Option<f64>| r.map_or("?".into(), |r| format!("{:.1}", r));
+        report.add_metric(text_metric(
+            format!("{} bitrate", iface),
+            format!("tx {} / rx {} MBit/s", rate(link.tx_bitrate), rate(link.rx_bitrate)),
+        ));
+    }
+    if let Some(rate) = link.retry_rate() {
+        report.add_metric(Metric {
+            name: format!("{} tx retries", iface),
+            value: MetricValue::Float(rate * 100.0),
+            unit: Some("%".into()),
+            threshold: Some(Threshold { warning: RETRY_RATE * 100.0, critical: RETRY_RATE * 300.0 }),
+        });
+    }
+    if let Some(failed) = link.tx_failed {
+        report.add_metric(Metric {
+            name: format!("{} tx failed", iface),
+            value: MetricValue::Integer(failed as i64),
+            unit: None,
+            threshold: None,
+        });
+    }
+
+    if let Some(signal) = link.signal.filter(|s| *s < SIGNAL_FAIR) {
+        let severity = match signal {
+            s if s < SIGNAL_UNUSABLE => Severity::Critical,
+            s if s < SIGNAL_WEAK => Severity::Warning,
+            _ => Severity::Info,
+        };
+        report.add_finding(Finding {
+            severity,
+            category: "wifi".into(),
+            message: format!("Weak Wi-Fi signal on {}: {} dBm", iface, signal),
+            details: Some(format!(
+                "Above {} dBm is good; below {} dBm the rate drops sharply and retransmissions make \
+                 the connection feel slow or drop out. Walls, distance and microwave ovens attenuate the signal.",
+                SIGNAL_FAIR, SIGNAL_WEAK
+            )),
+        });
+        report.add_recommendation(Recommendation {
+            priority: if severity == Severity::Info { 3 } else { 1 },
+            action: "Move closer to the access point or add one nearer to this device".into(),
+            command: None,
+            explanation: "Signal strength limits the usable bitrate more than anything else on Wi-Fi.".into(),
+        });
+    }
+    if let Some(snr) = link.snr().filter(|s| *s < SNR_LOW) {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "wifi".into(),
+            message: format!("Low signal-to-noise ratio on {}: {} dB", iface, snr),
+            details: Some("Interference raises the noise floor; high modulation rates need 25 dB or more.".into()),
+        });
+    }
+    if let Some(rate) = link.retry_rate().filter(|r| *r > RETRY_RATE) {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "wifi".into(),
+            message: format!("{:.0}% of frames sent on {} needed a retry", rate * 100.0, iface),
+            details: Some(format!(
+                "{} retries, {} failed outright for {} frames sent. Retries cost airtime and add latency; \
+                 they come from weak signal, interference or a congested channel.",
+                link.tx_retries.unwrap_or(0),
+                link.tx_failed.unwrap_or(0),
+                link.tx_packets.unwrap_or(0)
+            )),
+        });
+    }
+    if let Some(lost) = link.beacon_loss.filter(|b| *b > 0) {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "wifi".into(),
+            message: format!("{} lost {} beacon(s) from the access point", iface, lost),
+            details: Some("Repeated beacon loss leads to disconnects and roaming.".into()),
+        });
+    }
+    if link.power_save == Some(true) {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "wifi".into(),
+            message: format!("Power saving is enabled on {}", iface),
+            details: Some(
+                "The adapter sleeps between beacons, adding up to a few hundred ms of latency and \
+                 jitter to interactive traffic; some drivers also lose throughput."
+                    .into(),
+            ),
+        });
+        report.add_recommendation(Recommendation {
+            priority: 2,
+            action: format!("Disable Wi-Fi power saving on {}", iface),
+            command: Some(format!("sudo iw dev {} set power_save off", iface)),
+            explanation: "Lasts until the next reconnect; with NetworkManager set `wifi.powersave = 2` in \
+                          /etc/NetworkManager/conf.d/ to make it permanent."
+                .into(),
+        });
+    }
+}
+
+/// 2.4 GHz channels overlap unless they are five or more apart.
+pub fn analyze_congestion(report: &mut DiagnosticReport, link: &WifiLink, scan: &[Bss]) {
+    let Some(freq) = link.freq.filter(|f| *f < 2500) else {
+        return;
+    };
+    let Some(own) = channel(freq) else {
+        return;
+    };
+    let overlapping: Vec<&Bss> = scan
+        .iter()
+        .filter(|b| !b.associated && link.bssid.as_deref() != Some(b.bssid.as_str()) && b.freq < 2500)
+        // Networks barely above the noise floor hardly compete for airtime.
+        .filter(|b| !b.signal.is_some_and(|s| s <= f64::from(SIGNAL_UNUSABLE)))
+        .filter(|b| channel(b.freq).is_some_and(|c| c.abs_diff(own) < 5))
+        .collect();
+    let dual_band = link
+        .ssid
+        .as_ref()
+        .is_some_and(|ssid| scan.iter().any(|b| &b.ssid == ssid && b.freq > 5000));
+    if overlapping.len() >= CONGESTED_BSS {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "wifi".into(),
+            message: format!(
+                "2.4 GHz channel {} on {} is shared with {} other network(s)",
+                own,
+                link.iface,
+                overlapping.len()
+            ),
+            details: Some(
+                "Networks on the same or overlapping channels take turns on the air; in apartment \
+                 blocks 2.4 GHz is usually saturated. Only channels 1, 6 and 11 do not overlap."
+                    .into(),
+            ),
+        });
+    }
+    if dual_band {
+        report.add_recommendation(Recommendation {
+            priority: 2,
+            action: format!(
+                "Connect {} to the 5 GHz band of \"{}\"",
+                link.iface,
+                link.ssid.as_deref().unwrap_or_default()
+            ),
+            command: None,
+            explanation: "The same network is visible on 5 GHz, which has more, non-overlapping channels and \
+                          higher rates; enable band steering or use a separate SSID for 5 GHz."
+                .into(),
+        });
+    } else if overlapping.len() >= CONGESTED_BSS {
+        report.add_recommendation(Recommendation {
+            priority: 2,
+            action: "Move the access point to the least used of channels 1, 6 and 11, or to 5 GHz".into(),
+            command: Some(format!("iw dev {} scan dump | grep -E 'freq|SSID'", link.iface)),
+            explanation: "Lists the networks around you and their frequencies.".into(),
+        });
+    }
+}
+
+/// Link quality of every wireless interface; nothing is reported on machines without Wi-Fi.
+pub fn analyze(report: &mut DiagnosticReport) {
+    let ifaces = wireless_interfaces();
+    if ifaces.is_empty() {
+        return;
+    }
+    let mut proc = std::fs::read_to_string("/proc/net/wireless")
+        .map(|c| parse_proc_wireless(&c))
+        .unwrap_or_default();
+    for iface in &ifaces {
+        let link = read_link(iface, proc.remove(iface));
+        analyze_link(report, &link);
+        if link.connected && command_exists("iw") {
+            let scan = run_cmd(&["iw", "dev", iface, "scan", "dump"])
+                .map(|o| parse_scan_dump(&o))
+                .unwrap_or_default();
+            analyze_congestion(report, &link, &scan);
+        }
+    }
+    if !command_exists("iw") {
+        report.add_recommendation(Recommendation {
+            priority: 3,
+            action: "Install iw for Wi-Fi bitrate, retry and channel details".into(),
+            command: Some("sudo apt install iw  # or dnf/pacman".into()),
+            explanation: "Without it only signal and noise from /proc/net/wireless are available.".into(),
+        });
+    }
+}
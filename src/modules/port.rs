@@ -0,0 +1,377 @@
+//! Port ownership (portwhy) - listening sockets with their processes and users, "who holds
+//! port N", and sockets piling up in TIME_WAIT or CLOSE_WAIT.
+
+use super::net::sockets::{
+    self, InetSocket, Proto, TCP_CLOSE_WAIT, TCP_ESTABLISHED, TCP_LISTEN, TCP_TIME_WAIT,
+};
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use crate::core::traits::{DiagnosticModule, ModuleConfig};
+use crate::utils::process_name;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// CLOSE_WAIT sockets per process that indicate a leak rather than a slow close.
+const CLOSE_WAIT_WARN: usize = 10;
+/// TIME_WAIT sockets worth mentioning at all.
+const TIME_WAIT_INFO: usize = 1000;
+
+pub fn module() -> Arc<dyn DiagnosticModule> {
+    Arc::new(PortModule)
+}
+
+struct PortModule;
+
+/// "nginx (PID 812, user www-data)" or the UID alone when the process is not visible.
+fn owner(sock_inode: u64, uid: u32, owners: &HashMap<u64, Vec<u32>>) -> String {
+    let user = sockets::user_name(uid);
+    match owners.get(&sock_inode).map(Vec::as_slice) {
+        Some([pid]) => format!(
+            "{} (PID {}, user {})",
+            process_name(*pid).unwrap_or_else(|_| "?".into()),
+            pid,
+            user
+        ),
+        Some([first, rest @ ..]) => format!(
+            "{} (PIDs {} +{} more, user {})",
+            process_name(*first).unwrap_or_else(|_| "?".into()),
+            first,
+            rest.len(),
+            user
+        ),
+        _ => format!("user {} (process not visible; run as root)", user),
+    }
+}
+
+/// `systemctl stop` for processes in a service cgroup, `kill` otherwise.
+fn stop_command(pid: u32) -> String {
+    let unit = std::fs::read_to_string(format!("/proc/{}/cgroup", pid))
+        .ok()
+        .and_then(|c| {
+            c.lines()
+                .filter_map(|l| l.rsplit('/').next())
+                .find(|u| u.ends_with(".service"))
+                .map(String::from)
+        });
+    match unit {
+        Some(unit) => format!("sudo systemctl stop {}", unit),
+        None => format!("kill {}", pid),
+    }
+}
+
+fn cmdline(pid: u32) -> Option<String> {
+    let raw = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let cmd = String::from_utf8_lossy(&raw).replace('\0', " ").trim().to_string();
+    (!cmd.is_empty()).then_some(cmd)
+}
+
+fn list_listeners(report: &mut DiagnosticReport, socks: &[InetSocket], owners: &HashMap<u64, Vec<u32>>) {
+    let mut listeners: Vec<&InetSocket> = socks.iter().filter(|s| s.is_listener()).collect();
+    listeners.sort_by_key(|s| (s.local.port(), s.proto.name(), s.local.is_ipv6()));
+    for s in listeners {
+        report.add_metric(Metric {
+            name: format!("{} {}", s.proto.name(), s.local),
+            value: MetricValue::Text(owner(s.inode, s.uid, owners)),
+            unit: None,
+            threshold: None,
+        });
+    }
+    let unix: Vec<_> = sockets::read_unix_sockets().into_iter().filter(|u| u.listening).collect();
+    if !unix.is_empty() {
+        report.add_metric(Metric {
+            name: "Listening unix sockets".into(),
+            value: MetricValue::Integer(unix.len() as i64),
+            unit: None,
+            threshold: None,
+        });
+    }
+}
+
+/// Answer "who holds port N": listeners on it, connections using it, or why it is free.
+fn answer_port(report: &mut DiagnosticReport, port: u16, socks: &[InetSocket], owners: &HashMap<u64, Vec<u32>>) {
+    report.add_metric(Metric {
+        name: "Port".into(),
+        value: MetricValue::Integer(i64::from(port)),
+        unit: None,
+        threshold: None,
+    });
+    let on_port: Vec<&InetSocket> = socks.iter().filter(|s| s.local.port() == port).collect();
+    let listeners: Vec<&&InetSocket> = on_port.iter().filter(|s| s.is_listener()).collect();
+    for s in &listeners {
+        let pid = owners.get(&s.inode).and_then(|p| p.first()).copied();
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "port".into(),
+            message: format!(
+                "Port {}/{} is in use by {}, listening on {}",
+                port,
+                s.proto.name(),
+                owner(s.inode, s.uid, owners),
+                s.local.ip()
+            ),
+            details: pid.and_then(cmdline).map(|c| format!("Command: {}", c)),
+        });
+        if let Some(pid) = pid {
+            report.add_recommendation(Recommendation {
+                priority: 2,
+                action: format!(
+                    "Stop {} (PID {}) or choose another port",
+                    process_name(pid).unwrap_or_else(|_| "the process".into()),
+                    pid
+                ),
+                command: Some(stop_command(pid)),
+                explanation: "A listener on 0.0.0.0 or :: also blocks binding the same port on a specific address."
+                    .into(),
+            });
+        }
+    }
+
+    let established = on_port.iter().filter(|s| s.state == TCP_ESTABLISHED).count();
+    let time_wait = on_port.iter().filter(|s| s.state == TCP_TIME_WAIT).count();
+    if !listeners.is_empty() {
+        if established > 0 {
+            report.add_metric(Metric {
+                name: format!("Connections on port {}", port),
+                value: MetricValue::Integer(established as i64),
+                unit: None,
+                threshold: None,
+            });
+        }
+        return;
+    }
+    if time_wait > 0 {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "port".into(),
+            message: format!(
+                "Nothing listens on port {}, but {} connection(s) in TIME_WAIT still use it",
+                port, time_wait
+            ),
+            details: Some(
+                "A restarted server binding this port gets EADDRINUSE for up to 60 s unless it sets \
+                 SO_REUSEADDR before bind()."
+                    .into(),
+            ),
+        });
+    } else if let Some(s) = on_port.first() {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "port".into(),
+            message: format!(
+                "Port {} is not listened on, but used by a {} {} connection of {}",
+                port,
+                s.proto.name(),
+                sockets::state_name(s.state),
+                owner(s.inode, s.uid, owners)
+            ),
+            details: Some("Outgoing connections take local ports from net.ipv4.ip_local_port_range.".into()),
+        });
+    } else {
+        report.add_finding(Finding {
+            severity: Severity::Ok,
+            category: "port".into(),
+            message: format!("Port {} is free (tcp and udp)", port),
+            details: None,
+        });
+    }
+    let unprivileged = std::fs::read_to_string("/proc/sys/net/ipv4/ip_unprivileged_port_start")
+        .ok()
+        .and_then(|s| s.trim().parse::<u16>().ok())
+        .unwrap_or(1024);
+    if port < unprivileged {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "port".into(),
+            message: format!("Binding port {} needs root or CAP_NET_BIND_SERVICE", port),
+            details: Some(format!("Ports below {} are privileged (net.ipv4.ip_unprivileged_port_start).", unprivileged)),
+        });
+    }
+}
+
+/// Answer "who holds this unix socket".
+fn answer_unix(report: &mut DiagnosticReport, path: &str, owners: &HashMap<u64, Vec<u32>>) {
+    let matches: Vec<_> = sockets::read_unix_sockets()
+        .into_iter()
+        .filter(|u| u.path == path && u.listening)
+        .collect();
+    if matches.is_empty() {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "port".into(),
+            message: format!("Nothing listens on unix socket {}", path),
+            details: std::path::Path::new(path)
+                .exists()
+                .then(|| "The socket file exists but is stale; the server that created it is gone.".into()),
+        });
+        return;
+    }
+    for u in matches {
+        let who = match owners.get(&u.inode).and_then(|p| p.first()) {
+            Some(pid) => format!("{} (PID {})", process_name(*pid).unwrap_or_else(|_| "?".into()), pid),
+            None => "a process that is not visible (run as root)".into(),
+        };
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "port".into(),
+            message: format!("{} is served by {}", path, who),
+            details: None,
+        });
+    }
+}
+
+/// CLOSE_WAIT per owning process and TIME_WAIT per server port or remote endpoint.
+fn analyze_states(report: &mut DiagnosticReport, socks: &[InetSocket], owners: &HashMap<u64, Vec<u32>>) {
+    let tcp: Vec<&InetSocket> = socks.iter().filter(|s| s.proto == Proto::Tcp).collect();
+    let count = |state| tcp.iter().filter(|s| s.state == state).count();
+    report.add_metric(Metric {
+        name: "TCP sockets".into(),
+        value: MetricValue::Text(format!(
+            "{} established, {} listening, {} time-wait, {} close-wait",
+            count(TCP_ESTABLISHED),
+            count(TCP_LISTEN),
+            count(TCP_TIME_WAIT),
+            count(TCP_CLOSE_WAIT)
+        )),
+        unit: None,
+        threshold: None,
+    });
+
+    // /proc/net/tcp does not show the listen backlog, so the accept queue comes from `ss`.
+    let queues = sockets::listen_queues();
+    for s in tcp.iter().filter(|s| s.state == TCP_LISTEN) {
+        let Some(&(queued, limit)) = queues.get(&s.local) else {
+            continue;
+        };
+        if limit == 0 || queued < limit {
+            continue;
+        }
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "sockets".into(),
+            message: format!(
+                "Accept queue of {} is full ({} of {}), owned by {}",
+                s.local,
+                queued,
+                limit,
+                owner(s.inode, s.uid, owners)
+            ),
+            details: Some(
+                "The server is not calling accept() fast enough; new connections are dropped and \
+                 clients see timeouts. Add workers or raise the listen() backlog (capped by \
+                 net.core.somaxconn)."
+                    .into(),
+            ),
+        });
+    }
+
+    let mut close_wait: HashMap<Option<u32>, usize> = HashMap::new();
+    for s in tcp.iter().filter(|s| s.state == TCP_CLOSE_WAIT) {
+        *close_wait.entry(owners.get(&s.inode).and_then(|p| p.first()).copied()).or_default() += 1;
+    }
+    let mut close_wait: Vec<_> = close_wait.into_iter().filter(|(_, n)| *n >= CLOSE_WAIT_WARN).collect();
+    close_wait.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+    for (pid, n) in close_wait {
+        let who = match pid {
+            Some(pid) => format!("{} (PID {})", process_name(pid).unwrap_or_else(|_| "?".into()), pid),
+            None => "Processes not visible to this user".into(),
+        };
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "sockets".into(),
+            message: format!("{} holds {} sockets in CLOSE_WAIT", who, n),
+            details: Some(
+                "The remote side closed these connections but the application never called close(). \
+                 That is a descriptor leak: the count only grows until the process hits its open \
+                 file limit (EMFILE) and can no longer accept connections."
+                    .into(),
+            ),
+        });
+    }
+
+    let time_wait: Vec<&&InetSocket> = tcp.iter().filter(|s| s.state == TCP_TIME_WAIT).collect();
+    if time_wait.len() < TIME_WAIT_INFO {
+        return;
+    }
+    // Server side when the local port has a listener, client side otherwise.
+    let listening: HashMap<u16, &InetSocket> = tcp
+        .iter()
+        .filter(|s| s.state == TCP_LISTEN)
+        .map(|s| (s.local.port(), *s))
+        .collect();
+    let mut groups: HashMap<String, usize> = HashMap::new();
+    for s in &time_wait {
+        let key = match listening.get(&s.local.port()) {
+            Some(l) => format!("server port {} ({})", s.local.port(), owner(l.inode, l.uid, owners)),
+            None => format!("client connections to {}", s.remote),
+        };
+        *groups.entry(key).or_default() += 1;
+    }
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    groups.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+    let ephemeral = std::fs::read_to_string("/proc/sys/net/ipv4/ip_local_port_range")
+        .ok()
+        .and_then(|s| {
+            let mut it = s.split_whitespace().filter_map(|n| n.parse::<usize>().ok());
+            Some(it.next_back()? - it.next()? + 1)
+        })
+        .unwrap_or(28232);
+    let worst_client = groups
+        .iter()
+        .filter(|(k, _)| k.starts_with("client"))
+        .map(|(_, n)| *n)
+        .max()
+        .unwrap_or(0);
+    report.add_finding(Finding {
+        severity: if worst_client * 2 > ephemeral { Severity::Warning } else { Severity::Info },
+        category: "sockets".into(),
+        message: format!("{} TCP connections are in TIME_WAIT", time_wait.len()),
+        details: Some(format!(
+            "{}\nThe side that closes first keeps TIME_WAIT for 60 s. Clients opening a new \
+             connection per request to one endpoint run out of its {} ephemeral ports; use \
+             keep-alive or connection pooling.",
+            groups
+                .iter()
+                .take(5)
+                .map(|(k, n)| format!("{}: {}", k, n))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ephemeral
+        )),
+    });
+}
+
+#[async_trait]
+impl DiagnosticModule for PortModule {
+    fn name(&self) -> &'static str {
+        "port"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show which process owns a port and list listening sockets"
+    }
+
+    async fn run(&self, config: &ModuleConfig) -> Result<DiagnosticReport> {
+        let mut report = DiagnosticReport::new("port", "Listening ports and socket ownership");
+        let socks = sockets::read_inet_sockets();
+        let owners = sockets::socket_owners();
+
+        match config.extra_args.get("port").map(String::as_str) {
+            Some(p) if p.parse::<u16>().is_ok() => {
+                let port: u16 = p.parse()?;
+                report.summary = format!("Who uses port {}", port);
+                answer_port(&mut report, port, &socks, &owners);
+            }
+            Some(path) => {
+                report.summary = format!("Who serves {}", path);
+                answer_unix(&mut report, path, &owners);
+            }
+            None => list_listeners(&mut report, &socks, &owners),
+        }
+        analyze_states(&mut report, &socks, &owners);
+
+        report.compute_overall_severity();
+        Ok(report)
+    }
+}
@@ -0,0 +1,535 @@
+//! Software RAID and device-mapper status (raidwhy) - /proc/mdstat, md sysfs, dm-crypt, multipath.
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation};
+use crate::core::severity::Severity;
+use crate::core::traits::{DiagnosticModule, ModuleConfig};
+use crate::utils::{command_exists, format_duration, is_root, list_dir, read_file_optional, read_first_line, run_cmd};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub fn module() -> Arc<dyn DiagnosticModule> {
+    Arc::new(RaidModule)
+}
+
+struct RaidModule;
+
+/// One member of an md array as listed in /proc/mdstat, e.g. `sda1[0](F)`.
+#[derive(Debug, Clone)]
+pub struct MdMember {
+    pub name: String,
+    pub faulty: bool,
+    pub spare: bool,
+}
+
+/// Resync, recovery, check or reshape in progress.
+#[derive(Debug, Clone)]
+pub struct SyncProgress {
+    pub action: String,
+    pub percent: f64,
+    pub eta: Option<Duration>,
+    pub speed_kib: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MdArray {
+    pub name: String,
+    pub active: bool,
+    pub level: String,
+    pub members: Vec<MdMember>,
+    /// `[n/m]`: devices the array should have and devices currently in sync.
+    pub raid_disks: Option<u32>,
+    pub in_sync: Option<u32>,
+    pub sync: Option<SyncProgress>,
+    /// Sync that is queued but not running (`resync=DELAYED` / `PENDING`).
+    pub sync_pending: Option<String>,
+}
+
+impl MdArray {
+    pub fn missing(&self) -> u32 {
+        match (self.raid_disks, self.in_sync) {
+            (Some(total), Some(ok)) => total.saturating_sub(ok),
+            _ => 0,
+        }
+    }
+
+    /// Failures the level survives with all members present.
+    pub fn redundancy(&self) -> u32 {
+        match self.level.as_str() {
+            "raid1" => self.raid_disks.unwrap_or(2).saturating_sub(1),
+            "raid4" | "raid5" | "raid10" => 1,
+            "raid6" => 2,
+            _ => 0,
+        }
+    }
+}
+
+fn parse_member(token: &str) -> Option<MdMember> {
+    let (name, rest) = token.split_once('[')?;
+    Some(MdMember {
+        name: name.to_string(),
+        faulty: rest.contains("(F)"),
+        spare: rest.contains("(S)"),
+    })
+}
+
+/// Parse `recovery = 12.6% (246412928/1953382464) finish=152.3min speed=186688K/sec`.
+fn parse_progress(line: &str) -> Option<SyncProgress> {
+    // The progress bar ("[==>...]") also contains '='; the field separator is spaced.
+    let (before, after) = line.split_once(" = ")?;
+    let action = before.split_whitespace().last()?.to_string();
+    let percent = after.split_whitespace().next()?.trim_end_matches('%').parse().ok()?;
+    let field = |key: &str| {
+        after
+            .split_whitespace()
+            .find_map(|w| w.strip_prefix(key))
+            .map(str::to_string)
+    };
+    let eta = field("finish=")
+        .and_then(|s| s.trim_end_matches("min").parse::<f64>().ok())
+        .map(|min| Duration::from_secs((min * 60.0) as u64));
+    let speed_kib = field("speed=").and_then(|s| s.trim_end_matches("K/sec").parse().ok());
+    Some(SyncProgress {
+        action,
+        percent,
+        eta,
+        speed_kib,
+    })
+}
+
+/// Parse /proc/mdstat into arrays.
+pub fn parse_mdstat(content: &str) -> Vec<MdArray> {
+    let mut arrays: Vec<MdArray> = Vec::new();
+    for line in content.lines() {
+        if let Some((name, rest)) = line.split_once(" : ") {
+            if !name.starts_with("md") {
+                continue;
+            }
+            let mut words = rest.split_whitespace();
+            let state = words.next().unwrap_or("");
+            let active = state == "active";
+            // "(auto-read-only)" / "(read-only)" may follow the state.
+            let mut words = words.skip_while(|w| w.starts_with('('));
+            let level = if active {
+                words.next().unwrap_or("").to_string()
+            } else {
+                String::new()
+            };
+            let members = words.filter_map(parse_member).collect();
+            arrays.push(MdArray {
+                name: name.trim().to_string(),
+                active,
+                level,
+                members,
+                raid_disks: None,
+                in_sync: None,
+                sync: None,
+                sync_pending: None,
+            });
+            continue;
+        }
+        let Some(array) = arrays.last_mut() else {
+            continue;
+        };
+        let trimmed = line.trim();
+        if trimmed.contains("blocks") {
+            // "... [2/1] [U_]"
+            if let Some(counts) = trimmed
+                .split_whitespace()
+                .find(|w| w.starts_with('[') && w.contains('/'))
+            {
+                let counts = counts.trim_matches(|c| c == '[' || c == ']');
+                if let Some((total, ok)) = counts.split_once('/') {
+                    array.raid_disks = total.parse().ok();
+                    array.in_sync = ok.parse().ok();
+                }
+            }
+        } else if trimmed.contains('%') && trimmed.contains('=') {
+            array.sync = parse_progress(trimmed);
+        } else if let Some(pending) = ["resync=", "recovery="]
+            .iter()
+            .find_map(|key| trimmed.split_whitespace().find_map(|w| w.strip_prefix(key)))
+        {
+            array.sync_pending = Some(pending.to_string());
+        }
+    }
+    arrays
+}
+
+fn md_sysfs(array: &str, attr: &str) -> Option<String> {
+    read_first_line(&Path::new("/sys/block").join(array).join("md").join(attr))
+        .ok()
+        .flatten()
+        .map(|s| s.trim().to_string())
+}
+
+pub fn analyze_md(report: &mut DiagnosticReport, array: &MdArray) {
+    let dev = format!("/dev/{}", array.name);
+    let members = array
+        .members
+        .iter()
+        .map(|m| {
+            let flag = if m.faulty {
+                " (failed)"
+            } else if m.spare {
+                " (spare)"
+            } else {
+                ""
+            };
+            format!("{}{}", m.name, flag)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if !array.active {
+        report.add_finding(Finding {
+            severity: Severity::Critical,
+            category: "md".into(),
+            message: format!("{} is inactive", dev),
+            details: Some(format!(
+                "Members: {}. An array that could not be assembled (missing members or \
+                 conflicting superblocks) is not usable.",
+                members
+            )),
+        });
+        report.add_recommendation(Recommendation {
+            priority: 1,
+            action: format!("Inspect the member superblocks of {} before reassembling", dev),
+            command: Some(format!(
+                "sudo mdadm --examine {}",
+                array
+                    .members
+                    .iter()
+                    .map(|m| format!("/dev/{}", m.name))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )),
+            explanation: "Compare event counts; `mdadm --assemble --scan` is safe once the members agree."
+                .into(),
+        });
+        return;
+    }
+
+    if let (Some(total), Some(ok)) = (array.raid_disks, array.in_sync) {
+        report.add_metric(Metric {
+            name: format!("{} members in sync", dev),
+            value: MetricValue::Text(format!("{}/{} ({})", ok, total, array.level)),
+            unit: None,
+            threshold: None,
+        });
+    }
+
+    let missing = array.missing();
+    let failed: Vec<&MdMember> = array.members.iter().filter(|m| m.faulty).collect();
+    if missing > 0 || !failed.is_empty() {
+        let left = array.redundancy().saturating_sub(missing);
+        let recovering = array.sync.as_ref().filter(|s| s.action == "recovery");
+        let mut details = vec![format!("Members: {}.", members)];
+        details.push(if left == 0 {
+            "No redundancy left: one more disk failure loses the array.".into()
+        } else {
+            format!("It can still survive {} more failure(s).", left)
+        });
+        if let Some(sync) = recovering {
+            details.push(format!("Rebuild {:.1}% done{}.", sync.percent, eta_text(sync)));
+        }
+        report.add_finding(Finding {
+            severity: if left == 0 { Severity::Critical } else { Severity::Warning },
+            category: "md".into(),
+            message: format!(
+                "{} ({}) is degraded: {} of {} member(s) missing",
+                dev,
+                array.level,
+                missing,
+                array.raid_disks.unwrap_or(0)
+            ),
+            details: Some(details.join("\n")),
+        });
+        if recovering.is_none() {
+            let failed_name = failed.first().map(|m| m.name.as_str()).unwrap_or("sdX");
+            report.add_recommendation(Recommendation {
+                priority: 1,
+                action: format!("Replace the failed member of {} and start the rebuild", dev),
+                command: Some(format!(
+                    "sudo mdadm --manage {} --remove /dev/{} && sudo mdadm --manage {} --add /dev/<new>",
+                    dev, failed_name, dev
+                )),
+                explanation: "Check the failed disk with `rustwhy smart` first; back up before \
+                              rebuilding an array without redundancy."
+                    .into(),
+            });
+        }
+    }
+
+    if let Some(sync) = &array.sync {
+        report.add_metric(Metric {
+            name: format!("{} {} progress", dev, sync.action),
+            value: MetricValue::Float(sync.percent),
+            unit: Some("%".into()),
+            threshold: None,
+        });
+        if sync.action != "recovery" {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "md".into(),
+                message: format!("{} {} {:.1}% done{}", dev, sync.action, sync.percent, eta_text(sync)),
+                details: Some(
+                    "Array I/O is slower until it finishes; the rate is bounded by \
+                     /proc/sys/dev/raid/speed_limit_min and speed_limit_max."
+                        .into(),
+                ),
+            });
+        }
+    } else if let Some(pending) = &array.sync_pending {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "md".into(),
+            message: format!("{} has a sync queued ({})", dev, pending),
+            details: Some("Usually waiting for another array on the same disks to finish.".into()),
+        });
+    }
+
+    let mismatches: u64 = md_sysfs(&array.name, "mismatch_cnt")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+    let idle = md_sysfs(&array.name, "sync_action").filter(|a| a != "idle").is_none();
+    if mismatches > 0 && idle {
+        // RAID1/10 can legitimately diverge on swap or in-flight O_DIRECT writes.
+        let parity = matches!(array.level.as_str(), "raid4" | "raid5" | "raid6");
+        report.add_finding(Finding {
+            severity: if parity { Severity::Warning } else { Severity::Info },
+            category: "md".into(),
+            message: format!("{} has {} mismatched sectors from the last check", dev, mismatches),
+            details: Some(if parity {
+                "Parity does not match the data; a disk may be returning bad data silently.".into()
+            } else {
+                "Mirrors may differ harmlessly for swap or aborted writes; repeated growth is suspicious."
+                    .into()
+            }),
+        });
+        report.add_recommendation(Recommendation {
+            priority: if parity { 2 } else { 4 },
+            action: format!("Rewrite inconsistent stripes on {}", dev),
+            command: Some(format!("echo repair | sudo tee /sys/block/{}/md/sync_action", array.name)),
+            explanation: "A repair pass recomputes parity or copies the first mirror over the others."
+                .into(),
+        });
+    }
+}
+
+fn eta_text(sync: &SyncProgress) -> String {
+    let mut text = String::new();
+    if let Some(eta) = sync.eta {
+        text.push_str(&format!(", about {} left", format_duration(eta)));
+    }
+    if let Some(speed) = sync.speed_kib {
+        text.push_str(&format!(" at {} MiB/s", speed / 1024));
+    }
+    text
+}
+
+/// A device-mapper device from /sys/block/dm-*.
+#[derive(Debug, Clone)]
+struct DmDevice {
+    kernel_name: String,
+    name: String,
+    uuid: String,
+    suspended: bool,
+    slaves: Vec<String>,
+}
+
+fn dm_devices() -> Vec<DmDevice> {
+    list_dir(Path::new("/sys/block"))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|p| p.join("dm").is_dir())
+        .map(|p: PathBuf| {
+            let attr = |name: &str| {
+                read_first_line(&p.join("dm").join(name))
+                    .ok()
+                    .flatten()
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or_default()
+            };
+            DmDevice {
+                kernel_name: p.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+                name: attr("name"),
+                uuid: attr("uuid"),
+                suspended: attr("suspended") == "1",
+                slaves: list_dir(&p.join("slaves"))
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|s| s.file_name().map(|n| n.to_string_lossy().into_owned()))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// SCSI device state of a multipath member; anything but "running" does not carry I/O.
+fn path_state(dev: &str) -> String {
+    read_first_line(&Path::new("/sys/block").join(dev).join("device/state"))
+        .ok()
+        .flatten()
+        .map(|s| s.trim().to_string())
+        .unwrap_or_else(|| "unknown".into())
+}
+
+/// `dmsetup table` cipher of a crypt target (needs root), e.g. `aes-xts-plain64`.
+fn crypt_cipher(name: &str) -> Option<String> {
+    if !is_root() || !command_exists("dmsetup") {
+        return None;
+    }
+    let table = run_cmd(&["dmsetup", "table", name]).ok()?;
+    let mut words = table.split_whitespace();
+    words.find(|w| *w == "crypt")?;
+    words.next().map(str::to_string)
+}
+
+/// Names listed in /etc/crypttab.
+fn crypttab_names() -> Vec<String> {
+    read_file_optional(Path::new("/etc/crypttab"))
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| l.split_whitespace().next().map(str::to_string))
+        .collect()
+}
+
+fn analyze_dm(report: &mut DiagnosticReport, devices: &[DmDevice]) {
+    for dev in devices.iter().filter(|d| d.suspended) {
+        report.add_finding(Finding {
+            severity: Severity::Warning,
+            category: "dm".into(),
+            message: format!("Device-mapper device {} ({}) is suspended", dev.name, dev.kernel_name),
+            details: Some("All I/O to a suspended device blocks until it is resumed.".into()),
+        });
+        report.add_recommendation(Recommendation {
+            priority: 2,
+            action: format!("Resume {} if no LVM or cryptsetup operation is in progress", dev.name),
+            command: Some(format!("sudo dmsetup resume {}", dev.name)),
+            explanation: "Devices stay suspended when a tool dies in the middle of a table reload.".into(),
+        });
+    }
+
+    let crypt: Vec<&DmDevice> = devices.iter().filter(|d| d.uuid.starts_with("CRYPT-")).collect();
+    if !crypt.is_empty() {
+        report.add_metric(Metric {
+            name: "dm-crypt devices".into(),
+            value: MetricValue::List(
+                crypt
+                    .iter()
+                    .map(|d| format!("{} on {}", d.name, d.slaves.join(",")))
+                    .collect(),
+            ),
+            unit: None,
+            threshold: None,
+        });
+    }
+    for dev in &crypt {
+        let Some(cipher) = crypt_cipher(&dev.name) else {
+            continue;
+        };
+        // ECB and CBC with a plain IV leak patterns of the plaintext (watermarking attacks).
+        if cipher.contains("ecb") || cipher.ends_with("-plain") && cipher.contains("cbc") {
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "dm-crypt".into(),
+                message: format!("{} uses the legacy cipher mode {}", dev.name, cipher),
+                details: Some("Current cryptsetup defaults to aes-xts-plain64.".into()),
+            });
+        }
+    }
+    let open: Vec<&str> = crypt.iter().map(|d| d.name.as_str()).collect();
+    for name in crypttab_names().iter().filter(|n| !open.contains(&n.as_str())) {
+        report.add_finding(Finding {
+            severity: Severity::Info,
+            category: "dm-crypt".into(),
+            message: format!("crypttab entry {} is not open", name),
+            details: Some("Filesystems on it will fail to mount until it is unlocked.".into()),
+        });
+        report.add_recommendation(Recommendation {
+            priority: 3,
+            action: format!("Unlock {}", name),
+            command: Some(format!("sudo systemctl start systemd-cryptsetup@{}.service", name)),
+            explanation: "Uses the same options as boot-time unlocking from /etc/crypttab.".into(),
+        });
+    }
+
+    for dev in devices.iter().filter(|d| d.uuid.starts_with("mpath-")) {
+        let states: Vec<(String, String)> = dev.slaves.iter().map(|s| (s.clone(), path_state(s))).collect();
+        let healthy = states.iter().filter(|(_, st)| st == "running").count();
+        report.add_metric(Metric {
+            name: format!("{} active paths", dev.name),
+            value: MetricValue::Text(format!("{}/{}", healthy, states.len())),
+            unit: None,
+            threshold: None,
+        });
+        if healthy == states.len() {
+            continue;
+        }
+        report.add_finding(Finding {
+            severity: if healthy == 0 { Severity::Critical } else { Severity::Warning },
+            category: "multipath".into(),
+            message: format!("Multipath device {} has {} of {} paths down", dev.name, states.len() - healthy, states.len()),
+            details: Some(
+                states
+                    .iter()
+                    .map(|(path, state)| format!("{}: {}", path, state))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        });
+        report.add_recommendation(Recommendation {
+            priority: if healthy == 0 { 1 } else { 2 },
+            action: format!("Check cabling, HBA and SAN zoning for {}", dev.name),
+            command: Some(format!("sudo multipath -ll {}", dev.name)),
+            explanation: "With paths missing, I/O has no failover and may queue indefinitely.".into(),
+        });
+    }
+}
+
+#[async_trait]
+impl DiagnosticModule for RaidModule {
+    fn name(&self) -> &'static str {
+        "raid"
+    }
+
+    fn description(&self) -> &'static str {
+        "Check software RAID arrays and device-mapper targets"
+    }
+
+    async fn run(&self, _config: &ModuleConfig) -> Result<DiagnosticReport> {
+        let mut report = DiagnosticReport::new("raid", "RAID and device-mapper status");
+
+        let arrays = read_file_optional(Path::new("/proc/mdstat"))
+            .ok()
+            .flatten()
+            .map(|c| parse_mdstat(&c))
+            .unwrap_or_default();
+        for array in &arrays {
+            analyze_md(&mut report, array);
+        }
+
+        let dm = dm_devices();
+        analyze_dm(&mut report, &dm);
+
+        if arrays.is_empty() && dm.is_empty() {
+            report.summary = "No md arrays or device-mapper devices found.".into();
+        } else if report.findings.iter().all(|f| f.severity <= Severity::Info) {
+            report.summary = format!(
+                "{} md array(s) and {} device-mapper device(s) healthy",
+                arrays.len(),
+                dm.len()
+            );
+        }
+
+        report.compute_overall_severity();
+        Ok(report)
+    }
+}
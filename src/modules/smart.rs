@@ -0,0 +1,614 @@
+//! Drive health (smartwhy) - ATA SMART attributes and NVMe health logs.
+//!
+//! Uses `smartctl --json` when smartmontools is installed. NVMe controllers it did not cover
+//! are read directly via the admin "Get Log Page" ioctl, with sysfs for model and temperature.
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation, Threshold};
+use crate::core::severity::Severity;
+use crate::core::traits::{DiagnosticModule, ModuleConfig};
+use crate::utils::{command_exists, is_root, list_dir, read_first_line};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::fs::File;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+
+pub fn module() -> Arc<dyn DiagnosticModule> {
+    Arc::new(SmartModule)
+}
+
+struct SmartModule;
+
+/// Percentage of rated endurance used (NVMe) that triggers a warning.
+const WEAR_WARNING_PCT: u64 = 90;
+/// Hours after which a drive is reported as aged (about five years powered on).
+const AGED_POWER_ON_HOURS: u64 = 5 * 8766;
+/// Spinning disks run cooler than flash; both get their own temperature limits (°C).
+const HDD_TEMP_WARNING: i64 = 55;
+const HDD_TEMP_CRITICAL: i64 = 65;
+const SSD_TEMP_WARNING: i64 = 70;
+const SSD_TEMP_CRITICAL: i64 = 80;
+
+/// ATA attributes whose raw value stays at zero on a healthy drive:
+/// (id, description, count at which the finding becomes critical).
+const ATA_ERROR_ATTRIBUTES: &[(u64, &str, u64)] = &[
+    (5, "reallocated sectors", 100),
+    (187, "reported uncorrectable errors", 10),
+    (197, "sectors pending reallocation", 10),
+    (198, "offline uncorrectable sectors", 10),
+];
+/// UDMA CRC errors point at the cable or connector, not the drive.
+const ATA_CRC_ERRORS: u64 = 199;
+
+/// NVMe "critical warning" bits.
+const NVME_CRITICAL_WARNINGS: &[(u8, &str)] = &[
+    (0x01, "available spare below threshold"),
+    (0x02, "temperature outside the allowed range"),
+    (0x04, "reliability degraded by media errors"),
+    (0x08, "media placed in read-only mode"),
+    (0x10, "volatile memory backup failed"),
+    (0x20, "persistent memory region read-only"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Protocol {
+    Ata,
+    Nvme,
+    Other(String),
+}
+
+/// NVMe SMART / health information log (log page 0x02).
+#[derive(Debug, Clone, Default)]
+pub struct NvmeLog {
+    pub critical_warning: u8,
+    pub available_spare: u64,
+    pub spare_threshold: u64,
+    pub percentage_used: u64,
+    pub media_errors: u64,
+    pub error_log_entries: u64,
+    pub unsafe_shutdowns: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AtaAttribute {
+    pub id: u64,
+    pub name: String,
+    pub raw: u64,
+    /// "now" or "past" when the normalized value crossed the vendor threshold.
+    pub when_failed: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DriveHealth {
+    pub device: String,
+    pub model: Option<String>,
+    pub protocol: Protocol,
+    pub rotational: bool,
+    /// Overall self-assessment; `None` when the drive doesn't report one.
+    pub passed: Option<bool>,
+    pub temperature: Option<i64>,
+    pub power_on_hours: Option<u64>,
+    pub nvme: Option<NvmeLog>,
+    pub attributes: Vec<AtaAttribute>,
+}
+
+impl DriveHealth {
+    fn label(&self) -> String {
+        match &self.model {
+            Some(model) => format!("{} ({})", self.device, model),
+            None => self.device.clone(),
+        }
+    }
+
+    fn temp_limits(&self) -> (i64, i64) {
+        if self.rotational {
+            (HDD_TEMP_WARNING, HDD_TEMP_CRITICAL)
+        } else {
+            (SSD_TEMP_WARNING, SSD_TEMP_CRITICAL)
+        }
+    }
+}
+
+/// Run smartctl and parse its JSON output. Non-zero exit codes are a bitmask that also flags
+/// drive problems, so stdout is used regardless of the status.
+fn smartctl_json(args: &[&str]) -> Option<Value> {
+    let output = Command::new("smartctl").args(args).output().ok()?;
+    serde_json::from_slice(&output.stdout).ok()
+}
+
+/// Decode the output of `smartctl -a --json` for `device`.
+pub fn parse_smartctl(device: &str, v: &Value) -> DriveHealth {
+    let protocol = match v["device"]["protocol"].as_str() {
+        Some("ATA") => Protocol::Ata,
+        Some("NVMe") => Protocol::Nvme,
+        Some(other) => Protocol::Other(other.to_string()),
+        None => Protocol::Other("unknown".into()),
+    };
+    let nvme = v["nvme_smart_health_information_log"].as_object().map(|log| {
+        let get = |key: &str| log.get(key).and_then(Value::as_u64).unwrap_or(0);
+        NvmeLog {
+            critical_warning: get("critical_warning") as u8,
+            available_spare: get("available_spare"),
+            spare_threshold: get("available_spare_threshold"),
+            percentage_used: get("percentage_used"),
+            media_errors: get("media_errors"),
+            error_log_entries: get("num_err_log_entries"),
+            unsafe_shutdowns: get("unsafe_shutdowns"),
+        }
+    });
+    let attributes = v["ata_smart_attributes"]["table"]
+        .as_array()
+        .map(|table| {
+            table
+                .iter()
+                .filter_map(|attr| {
+                    // Some vendors pack several counters into the raw value; the first number
+                    // of the decoded string is the one that matters.
+                    let raw = attr["raw"]["string"]
+                        .as_str()
+                        .and_then(|s| s.split_whitespace().next())
+                        .and_then(|s| s.parse().ok())
+                        .or_else(|| attr["raw"]["value"].as_u64())?;
+                    Some(AtaAttribute {
+                        id: attr["id"].as_u64()?,
+                        name: attr["name"].as_str().unwrap_or("").to_string(),
+                        raw,
+                        when_failed: attr["when_failed"].as_str().unwrap_or("").to_string(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    DriveHealth {
+        device: device.to_string(),
+        model: v["model_name"].as_str().map(str::to_string),
+        rotational: protocol == Protocol::Ata && v["rotation_rate"].as_u64().is_some_and(|r| r > 0),
+        protocol,
+        passed: v["smart_status"]["passed"].as_bool(),
+        temperature: v["temperature"]["current"].as_i64(),
+        power_on_hours: v["power_on_time"]["hours"].as_u64(),
+        nvme,
+        attributes,
+    }
+}
+
+/// Query every device smartctl can find. Returns readable drives and devices it could not open.
+fn read_smartctl() -> (Vec<DriveHealth>, Vec<String>) {
+    let mut drives = Vec::new();
+    let mut unreadable = Vec::new();
+    let Some(scan) = smartctl_json(&["--scan-open", "--json=c"]) else {
+        return (drives, unreadable);
+    };
+    for dev in scan["devices"].as_array().into_iter().flatten() {
+        let Some(name) = dev["name"].as_str() else {
+            continue;
+        };
+        let dev_type = dev["type"].as_str().unwrap_or("auto");
+        let Some(v) = smartctl_json(&["-a", "--json=c", "-d", dev_type, name]) else {
+            unreadable.push(name.to_string());
+            continue;
+        };
+        // Bits 0-1: command line error or device could not be opened.
+        if v["smartctl"]["exit_status"].as_u64().unwrap_or(0) & 0x3 != 0 {
+            unreadable.push(name.to_string());
+            continue;
+        }
+        drives.push(parse_smartctl(name, &v));
+    }
+    (drives, unreadable)
+}
+
+/// `struct nvme_admin_cmd` from <linux/nvme_ioctl.h>.
+#[repr(C)]
+#[derive(Default)]
+struct NvmeAdminCmd {
+    opcode: u8,
+    flags: u8,
+    rsvd1: u16,
+    nsid: u32,
+    cdw2: u32,
+    cdw3: u32,
+    metadata: u64,
+    addr: u64,
+    metadata_len: u32,
+    data_len: u32,
+    cdw10: u32,
+    cdw11: u32,
+    cdw12: u32,
+    cdw13: u32,
+    cdw14: u32,
+    cdw15: u32,
+    timeout_ms: u32,
+    result: u32,
+}
+
+nix::ioctl_readwrite!(nvme_admin_ioctl, b'N', 0x41, NvmeAdminCmd);
+
+fn le_u64(buf: &[u8], offset: usize) -> u64 {
+    // The 128-bit counters never exceed 64 bits in practice; read the low half.
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Decode the 512-byte health log; also returns temperature (°C, `None` when the controller
+/// reports 0 K) and power-on hours.
+pub fn parse_nvme_log(buf: &[u8; 512]) -> (NvmeLog, Option<i64>, u64) {
+    let kelvin = u16::from_le_bytes([buf[1], buf[2]]) as i64;
+    let log = NvmeLog {
+        critical_warning: buf[0],
+        available_spare: buf[3] as u64,
+        spare_threshold: buf[4] as u64,
+        percentage_used: buf[5] as u64,
+        unsafe_shutdowns: le_u64(buf, 144),
+        media_errors: le_u64(buf, 160),
+        error_log_entries: le_u64(buf, 176),
+    };
+    let celsius = (kelvin > 0).then(|| kelvin - 273);
+    (log, celsius, le_u64(buf, 128))
+}
+
+/// Fetch log page 0x02 from an NVMe controller character device (needs root).
+fn read_nvme_log(ctrl: &Path) -> Option<(NvmeLog, Option<i64>, u64)> {
+    let file = File::open(ctrl).ok()?;
+    let mut buf = [0u8; 512];
+    let mut cmd = NvmeAdminCmd {
+        opcode: 0x02,
+        nsid: 0xFFFF_FFFF,
+        addr: buf.as_mut_ptr() as u64,
+        data_len: buf.len() as u32,
+        // Number of dwords minus one in the upper half, log page id in the lower.
+        cdw10: ((buf.len() as u32 / 4 - 1) << 16) | 0x02,
+        ..Default::default()
+    };
+    // SAFETY: `cmd` is a properly laid out nvme_admin_cmd and `addr` points to a live buffer of
+    // exactly `data_len` bytes for the duration of the call.
+    unsafe { nvme_admin_ioctl(file.as_raw_fd(), &mut cmd) }.ok()?;
+    Some(parse_nvme_log(&buf))
+}
+
+/// NVMe controllers not already covered by smartctl, from sysfs and the ioctl.
+fn read_nvme_sysfs(known: &[DriveHealth]) -> Vec<DriveHealth> {
+    let mut out = Vec::new();
+    for ctrl in list_dir(Path::new("/sys/class/nvme")).unwrap_or_default() {
+        let Some(name) = ctrl.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        let device = format!("/dev/{}", name);
+        if known.iter().any(|d| d.device == device) {
+            continue;
+        }
+        let model = read_first_line(&ctrl.join("model"))
+            .ok()
+            .flatten()
+            .map(|s| s.trim().to_string());
+        let hwmon_temp = list_dir(&ctrl)
+            .unwrap_or_default()
+            .into_iter()
+            .chain(list_dir(&ctrl.join("device/hwmon")).unwrap_or_default())
+            .filter(|p| p.file_name().is_some_and(|n| n.to_string_lossy().starts_with("hwmon")))
+            .find_map(|p| read_first_line(&p.join("temp1_input")).ok().flatten())
+            .and_then(|s| s.trim().parse::<i64>().ok())
+            .map(|millideg| millideg / 1000);
+        let log = read_nvme_log(Path::new(&device));
+        out.push(DriveHealth {
+            device,
+            model,
+            protocol: Protocol::Nvme,
+            rotational: false,
+            passed: log.as_ref().map(|(l, _, _)| l.critical_warning == 0),
+            temperature: log.as_ref().and_then(|(_, t, _)| *t).or(hwmon_temp),
+            power_on_hours: log.as_ref().map(|(_, _, h)| *h),
+            nvme: log.map(|(l, _, _)| l),
+            attributes: Vec::new(),
+        });
+    }
+    out
+}
+
+/// SATA/SAS/IDE disks; their SMART data is only reachable through smartctl.
+fn ata_block_devices() -> Vec<String> {
+    list_dir(Path::new("/sys/block"))
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .filter(|n| n.starts_with("sd") || n.starts_with("hd"))
+        .map(|n| format!("/dev/{}", n))
+        .collect()
+}
+
+fn add_finding(
+    report: &mut DiagnosticReport,
+    worst: &mut Severity,
+    severity: Severity,
+    message: String,
+    details: Option<String>,
+) {
+    *worst = (*worst).max(severity);
+    report.add_finding(Finding {
+        severity,
+        category: "smart".into(),
+        message,
+        details,
+    });
+}
+
+/// Add metrics and findings for one drive; returns its worst severity.
+pub fn assess(report: &mut DiagnosticReport, drive: &DriveHealth) -> Severity {
+    let label = drive.label();
+    let dev = &drive.device;
+    let mut worst = Severity::Ok;
+
+    if drive.passed == Some(false) {
+        add_finding(
+            report,
+            &mut worst,
+            Severity::Critical,
+            format!("{} failed its SMART self-assessment", label),
+            Some("The drive itself predicts failure; treat its data as at risk.".into()),
+        );
+    }
+
+    if let Some(temp) = drive.temperature {
+        let (warning, critical) = drive.temp_limits();
+        report.add_metric(Metric {
+            name: format!("{} temperature", dev),
+            value: MetricValue::Integer(temp),
+            unit: Some("°C".into()),
+            threshold: Some(Threshold {
+                warning: warning as f64,
+                critical: critical as f64,
+            }),
+        });
+        if temp >= warning {
+            add_finding(
+                report,
+                &mut worst,
+                if temp >= critical { Severity::Critical } else { Severity::Warning },
+                format!("{} is running at {}°C", label, temp),
+                Some("Sustained heat shortens drive life; check airflow around the drive bay.".into()),
+            );
+        }
+    }
+
+    if let Some(hours) = drive.power_on_hours {
+        report.add_metric(Metric {
+            name: format!("{} power-on hours", dev),
+            value: MetricValue::Integer(hours as i64),
+            unit: Some(" h".into()),
+            threshold: None,
+        });
+        if hours >= AGED_POWER_ON_HOURS {
+            add_finding(
+                report,
+                &mut worst,
+                Severity::Info,
+                format!("{} has been powered on for {:.1} years", label, hours as f64 / 8766.0),
+                None,
+            );
+        }
+    }
+
+    if let Some(log) = &drive.nvme {
+        report.add_metric(Metric {
+            name: format!("{} endurance used", dev),
+            value: MetricValue::Integer(log.percentage_used as i64),
+            unit: Some("%".into()),
+            threshold: Some(Threshold {
+                warning: WEAR_WARNING_PCT as f64,
+                critical: 100.0,
+            }),
+        });
+        report.add_metric(Metric {
+            name: format!("{} available spare", dev),
+            value: MetricValue::Integer(log.available_spare as i64),
+            unit: Some("%".into()),
+            threshold: None,
+        });
+        report.add_metric(Metric {
+            name: format!("{} media errors", dev),
+            value: MetricValue::Integer(log.media_errors as i64),
+            unit: None,
+            threshold: None,
+        });
+        let warnings: Vec<&str> = NVME_CRITICAL_WARNINGS
+            .iter()
+            .filter(|(bit, _)| log.critical_warning & bit != 0)
+            .map(|(_, text)| *text)
+            .collect();
+        if !warnings.is_empty() {
+            add_finding(
+                report,
+                &mut worst,
+                Severity::Critical,
+                format!("{} reports a critical warning: {}", label, warnings.join(", ")),
+                Some(format!(
+                    "Available spare {}% (threshold {}%).",
+                    log.available_spare, log.spare_threshold
+                )),
+            );
+        }
+        if log.percentage_used >= WEAR_WARNING_PCT {
+            add_finding(
+                report,
+                &mut worst,
+                if log.percentage_used >= 100 { Severity::Critical } else { Severity::Warning },
+                format!("{} has used {}% of its rated write endurance", label, log.percentage_used),
+                Some("Past 100% the drive may switch to read-only at any time.".into()),
+            );
+        }
+        if log.media_errors > 0 {
+            add_finding(
+                report,
+                &mut worst,
+                Severity::Warning,
+                format!("{} logged {} media error(s)", label, log.media_errors),
+                Some(format!(
+                    "{} entries in the error log, {} unsafe shutdowns.",
+                    log.error_log_entries, log.unsafe_shutdowns
+                )),
+            );
+        }
+    }
+
+    for attr in &drive.attributes {
+        if let Some((_, what, critical_at)) = ATA_ERROR_ATTRIBUTES.iter().find(|(id, _, _)| *id == attr.id) {
+            report.add_metric(Metric {
+                name: format!("{} {}", dev, what),
+                value: MetricValue::Integer(attr.raw as i64),
+                unit: None,
+                threshold: Some(Threshold {
+                    warning: 1.0,
+                    critical: *critical_at as f64,
+                }),
+            });
+            if attr.raw > 0 {
+                add_finding(
+                    report,
+                    &mut worst,
+                    if attr.raw >= *critical_at { Severity::Critical } else { Severity::Warning },
+                    format!("{} has {} {}", label, attr.raw, what),
+                    Some(format!(
+                        "{} (attribute {}) should stay at 0; a growing count means the surface is failing.",
+                        attr.name, attr.id
+                    )),
+                );
+            }
+        } else if attr.id == ATA_CRC_ERRORS && attr.raw > 0 {
+            add_finding(
+                report,
+                &mut worst,
+                Severity::Info,
+                format!("{} has {} interface CRC errors", label, attr.raw),
+                Some("Usually a loose or damaged SATA cable, not the drive; the counter never resets.".into()),
+            );
+        }
+        if attr.when_failed == "now" {
+            add_finding(
+                report,
+                &mut worst,
+                Severity::Critical,
+                format!("{} attribute {} is below the vendor failure threshold", label, attr.name),
+                None,
+            );
+        }
+    }
+
+    worst
+}
+
+#[async_trait]
+impl DiagnosticModule for SmartModule {
+    fn name(&self) -> &'static str {
+        "smart"
+    }
+
+    fn description(&self) -> &'static str {
+        "Check drive health from SMART and NVMe health logs"
+    }
+
+    async fn run(&self, config: &ModuleConfig) -> Result<DiagnosticReport> {
+        let mut report = DiagnosticReport::new("smart", "Drive health analysis");
+        let device_filter = config.extra_args.get("device").map(|d| d.trim_start_matches("/dev/").to_string());
+        let has_smartctl = command_exists("smartctl");
+
+        let (mut drives, mut unreadable) = if has_smartctl {
+            tokio::task::spawn_blocking(read_smartctl).await?
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        drives.extend(read_nvme_sysfs(&drives));
+        if let Some(filter) = &device_filter {
+            let matches = |dev: &String| dev.trim_start_matches("/dev/") == filter;
+            drives.retain(|d| matches(&d.device));
+            unreadable.retain(matches);
+        }
+
+        for drive in &drives {
+            let worst = assess(&mut report, drive);
+            if worst == Severity::Critical {
+                report.add_recommendation(Recommendation {
+                    priority: 1,
+                    action: format!("Back up {} now and plan its replacement", drive.label()),
+                    command: Some(format!("sudo smartctl -x {}", drive.device)),
+                    explanation: "Failing drives often degrade quickly once errors appear; the full \
+                                  report includes the drive's error and self-test logs."
+                        .into(),
+                });
+            } else if worst == Severity::Warning && drive.protocol == Protocol::Ata {
+                report.add_recommendation(Recommendation {
+                    priority: 2,
+                    action: format!("Run an extended self-test on {}", drive.device),
+                    command: Some(format!("sudo smartctl -t long {}", drive.device)),
+                    explanation: "The long test reads the whole surface; check the result later with \
+                                  smartctl -l selftest."
+                        .into(),
+                });
+            }
+        }
+
+        let missing_data: Vec<String> = drives
+            .iter()
+            .filter(|d| d.nvme.is_none() && d.attributes.is_empty() && d.passed.is_none())
+            .map(|d| d.device.clone())
+            .chain(unreadable)
+            .collect();
+        let all_readable = missing_data.is_empty();
+        if !missing_data.is_empty() {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "smart".into(),
+                message: format!("No health data readable for {}", missing_data.join(", ")),
+                details: Some(if is_root() {
+                    "The device does not expose SMART data (virtual disk or USB bridge?).".into()
+                } else {
+                    "Reading SMART logs needs root.".into()
+                }),
+            });
+            if !is_root() {
+                report.add_recommendation(Recommendation {
+                    priority: 3,
+                    action: "Re-run with root privileges".into(),
+                    command: Some("sudo rustwhy smart".into()),
+                    explanation: "SMART and NVMe log pages are only readable by root.".into(),
+                });
+            }
+        }
+
+        if !has_smartctl && !ata_block_devices().is_empty() {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "smart".into(),
+                message: "smartctl not found; SATA/SAS drives cannot be checked".into(),
+                details: Some(format!("Unchecked: {}", ata_block_devices().join(", "))),
+            });
+            report.add_recommendation(Recommendation {
+                priority: 3,
+                action: "Install smartmontools".into(),
+                command: Some("sudo apt install smartmontools  # or dnf/pacman".into()),
+                explanation: "smartctl reads ATA SMART attributes and self-test logs.".into(),
+            });
+        }
+
+        if drives.is_empty() && report.findings.is_empty() {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "smart".into(),
+                message: match &device_filter {
+                    Some(dev) => format!("No SMART-capable drive named {} found", dev),
+                    None => "No SMART-capable drives found".into(),
+                },
+                details: None,
+            });
+        } else if report.overall_severity <= Severity::Info && all_readable {
+            report.summary = format!("{} drive(s) healthy", drives.len());
+        }
+
+        report.compute_overall_severity();
+        Ok(report)
+    }
+}
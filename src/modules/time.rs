@@ -0,0 +1,508 @@
+//! Clock synchronisation (timewhy) - which NTP daemon is active (systemd-timesyncd, chrony,
+//! ntpd), its offset, stratum and last sync, the kernel's view via adjtimex(2), and whether
+//! the RTC keeps local time.
+
+use crate::core::report::{DiagnosticReport, Finding, Metric, MetricValue, Recommendation, Threshold};
+use crate::core::severity::Severity;
+use crate::core::traits::{DiagnosticModule, ModuleConfig};
+use crate::utils::{command_exists, list_dir, read_first_line, run_cmd};
+use anyhow::Result;
+use async_trait::async_trait;
+use nix::libc;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Offsets (ms) that break things: TLS validity checks tolerate little, Kerberos allows 5 min.
+const OFFSET_WARN_MS: f64 = 100.0;
+const OFFSET_CRIT_MS: f64 = 1000.0;
+/// Seconds since the last successful sync before it counts as stale.
+const STALE_SYNC_SECS: u64 = 24 * 3600;
+/// RTC and system clock difference worth mentioning.
+const RTC_DRIFT_SECS: i64 = 60;
+/// Seconds between the NTP era (1900) and the Unix epoch.
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+pub fn module() -> Arc<dyn DiagnosticModule> {
+    Arc::new(TimeModule)
+}
+
+struct TimeModule;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Daemon {
+    Timesyncd,
+    Chrony,
+    Ntpd,
+    OpenNtpd,
+}
+
+impl Daemon {
+    fn name(self) -> &'static str {
+        match self {
+            Daemon::Timesyncd => "systemd-timesyncd",
+            Daemon::Chrony => "chronyd",
+            Daemon::Ntpd => "ntpd",
+            Daemon::OpenNtpd => "openntpd",
+        }
+    }
+
+    /// Match /proc/<pid>/comm (truncated to 15 characters by the kernel). OpenNTPD's process
+    /// is also called ntpd; only OpenNTPD ships ntpctl.
+    fn from_comm(comm: &str) -> Option<Self> {
+        match comm {
+            "systemd-timesyn" => Some(Daemon::Timesyncd),
+            "chronyd" => Some(Daemon::Chrony),
+            "ntpd" if command_exists("ntpctl") => Some(Daemon::OpenNtpd),
+            "ntpd" => Some(Daemon::Ntpd),
+            _ => None,
+        }
+    }
+}
+
+/// Sync daemons with a running process.
+fn running_daemons() -> Vec<Daemon> {
+    let mut found = Vec::new();
+    for dir in list_dir(Path::new("/proc")).unwrap_or_default() {
+        let is_pid = dir.file_name().is_some_and(|n| n.to_string_lossy().bytes().all(|b| b.is_ascii_digit()));
+        if !is_pid {
+            continue;
+        }
+        let comm = read_first_line(&dir.join("comm")).ok().flatten().unwrap_or_default();
+        if let Some(d) = Daemon::from_comm(comm.trim()) {
+            if !found.contains(&d) {
+                found.push(d);
+            }
+        }
+    }
+    found
+}
+
+/// What the active daemon reports about its last exchange.
+#[derive(Debug, Clone, Default)]
+pub struct SyncStatus {
+    pub synchronized: Option<bool>,
+    pub server: Option<String>,
+    /// Local clock minus reference, in milliseconds (positive: this clock is ahead).
+    pub offset_ms: Option<f64>,
+    pub stratum: Option<u32>,
+    /// Unix time of the last successful update.
+    pub last_sync: Option<u64>,
+}
+
+/// `key=value` lines, as printed by `timedatectl show` and `show-timesync`.
+pub fn parse_key_values(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect()
+}
+
+/// `timedatectl show-timesync --all`. Newer systemd prints the NTPMessage timestamps in µs;
+/// then ((T2 - T1) + (T3 - T4)) / 2 is how far the server is ahead. Older versions format
+/// them as dates, and the offset has to come from `timesync-status` instead.
+pub fn parse_timesync(output: &str) -> SyncStatus {
+    let kv = parse_key_values(output);
+    let message = output
+        .lines()
+        .find_map(|l| l.strip_prefix("NTPMessage="))
+        .map(|m| {
+            m.trim_matches(|c| c == '{' || c == '}' || c == ' ')
+                .split(", ")
+                .filter_map(|p| p.split_once('='))
+                .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+                .collect::<HashMap<_, _>>()
+        })
+        .unwrap_or_default();
+    let ts = |k: &str| message.get(k).and_then(|v| v.parse::<i64>().ok());
+    let offset_ms = match (ts("OriginateTimestamp"), ts("ReceiveTimestamp"), ts("TransmitTimestamp"), ts("DestinationTimestamp")) {
+        (Some(t1), Some(t2), Some(t3), Some(t4)) if t1 > 0 && t4 > 0 => {
+            Some(-(((t2 - t1) + (t3 - t4)) as f64 / 2.0) / 1000.0)
+        }
+        _ => None,
+    };
+    SyncStatus {
+        synchronized: None,
+        server: match (kv.get("ServerName"), kv.get("ServerAddress")) {
+            (Some(name), Some(addr)) if !name.is_empty() && name != addr => Some(format!("{} ({})", name, addr)),
+            (_, Some(addr)) if !addr.is_empty() => Some(addr.clone()),
+            _ => None,
+        },
+        offset_ms,
+        stratum: message.get("Stratum").and_then(|s| s.parse().ok()).filter(|s| *s > 0),
+        last_sync: ts("DestinationTimestamp").filter(|t| *t > 0).map(|t| t as u64 / 1_000_000),
+    }
+}
+
+/// The "Offset: +1.234ms" line of `timedatectl timesync-status`, in milliseconds.
+pub fn parse_timesync_offset(output: &str) -> Option<f64> {
+    let value = output.lines().find_map(|l| l.trim().strip_prefix("Offset:"))?.trim();
+    let split = value.find(|c: char| c.is_ascii_alphabetic() || c == 'µ')?;
+    let (num, unit) = value.split_at(split);
+    let factor = match unit {
+        "us" | "µs" => 0.001,
+        "ms" => 1.0,
+        "s" => 1000.0,
+        // Compound values such as "+2min 3s".
+        _ => {
+            let sign = if value.starts_with('-') { -1.0 } else { 1.0 };
+            let d = humantime::parse_duration(value.trim_start_matches(['+', '-'])).ok()?;
+            return Some(sign * d.as_secs_f64() * 1000.0);
+        }
+    };
+    Some(num.parse::<f64>().ok()? * factor)
+}
+
+/// timesyncd touches this file after every successful sync.
+fn timesyncd_last_sync() -> Option<u64> {
+    let modified = std::fs::metadata("/var/lib/systemd/timesync/clock").ok()?.modified().ok()?;
+    modified.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+/// `chronyc -c tracking`: RefID, name, stratum, ref time, system time offset, last offset,
+/// RMS offset, frequency, residual freq, skew, root delay, root dispersion, update interval,
+/// leap status. The system time field is how far the clock is from NTP time, positive when
+/// slow.
+pub fn parse_chrony_tracking(output: &str) -> SyncStatus {
+    let f: Vec<&str> = output.trim().split(',').collect();
+    if f.len() < 14 {
+        return SyncStatus::default();
+    }
+    let stratum = f[2].parse::<u32>().ok();
+    let leap = f[13].trim();
+    SyncStatus {
+        synchronized: Some(leap != "Not synchronised" && stratum.is_some_and(|s| s > 0 && s < 16)),
+        server: Some(f[1].to_string()).filter(|s| !s.is_empty()),
+        offset_ms: f[4].parse::<f64>().ok().map(|s| -s * 1000.0),
+        stratum: stratum.filter(|s| *s > 0),
+        last_sync: f[3].parse::<f64>().ok().filter(|t| *t > 0.0).map(|t| t as u64),
+    }
+}
+
+/// `ntpq -c rv`: comma separated `key=value` pairs; offset in ms (server minus local), reftime
+/// as an NTP timestamp.
+pub fn parse_ntpq_rv(output: &str) -> SyncStatus {
+    let kv: HashMap<&str, &str> = output
+        .split(',')
+        .filter_map(|p| p.trim().split_once('='))
+        .map(|(k, v)| (k.trim(), v.trim().trim_matches('"')))
+        .collect();
+    let stratum = kv.get("stratum").and_then(|s| s.parse::<u32>().ok());
+    SyncStatus {
+        synchronized: kv.get("leap").map(|l| *l != "11" && *l != "3" && stratum.is_some_and(|s| s < 16)),
+        server: kv.get("refid").map(|s| s.to_string()),
+        offset_ms: kv.get("offset").and_then(|s| s.parse::<f64>().ok()).map(|o| -o),
+        stratum: stratum.filter(|s| *s > 0 && *s < 16),
+        last_sync: kv
+            .get("reftime")
+            .and_then(|r| r.split('.').next())
+            .and_then(|h| u64::from_str_radix(h, 16).ok())
+            .filter(|s| *s > NTP_UNIX_OFFSET)
+            .map(|s| s - NTP_UNIX_OFFSET),
+    }
+}
+
+/// The kernel's clock discipline state from adjtimex(2).
+#[derive(Debug, Clone, Copy)]
+pub struct KernelClock {
+    pub synchronized: bool,
+    pub max_error_us: i64,
+    pub est_error_us: i64,
+}
+
+fn kernel_clock() -> Option<KernelClock> {
+    // SAFETY: timex is plain data; modes = 0 only reads the state.
+    let mut tx: libc::timex = unsafe { std::mem::zeroed() };
+    let state = unsafe { libc::adjtimex(&mut tx) };
+    if state < 0 {
+        return None;
+    }
+    Some(KernelClock {
+        synchronized: state != libc::TIME_ERROR && tx.status & libc::STA_UNSYNC == 0,
+        max_error_us: tx.maxerror as i64,
+        est_error_us: tx.esterror as i64,
+    })
+}
+
+fn daemon_status(daemon: Daemon) -> Option<SyncStatus> {
+    match daemon {
+        Daemon::Timesyncd if command_exists("timedatectl") => {
+            let mut status = run_cmd(&["timedatectl", "show-timesync", "--all"]).ok().map(|o| parse_timesync(&o))?;
+            if status.offset_ms.is_none() {
+                status.offset_ms = run_cmd(&["timedatectl", "timesync-status"])
+                    .ok()
+                    .and_then(|o| parse_timesync_offset(&o));
+            }
+            status.last_sync = status.last_sync.or_else(timesyncd_last_sync);
+            Some(status)
+        }
+        Daemon::Chrony if command_exists("chronyc") => {
+            run_cmd(&["chronyc", "-c", "tracking"]).ok().map(|o| parse_chrony_tracking(&o))
+        }
+        Daemon::Ntpd if command_exists("ntpq") => run_cmd(&["ntpq", "-c", "rv"]).ok().map(|o| parse_ntpq_rv(&o)),
+        _ => None,
+    }
+}
+
+/// RTC mode from `timedatectl show` (LocalRTC) or the third line of /etc/adjtime.
+fn rtc_local(timedatectl: &HashMap<String, String>) -> Option<bool> {
+    if let Some(v) = timedatectl.get("LocalRTC") {
+        return Some(v == "yes");
+    }
+    let adjtime = std::fs::read_to_string("/etc/adjtime").ok()?;
+    adjtime.lines().nth(2).map(|l| l.trim() == "LOCAL")
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn metric(name: &str, value: MetricValue, unit: Option<&str>) -> Metric {
+    Metric { name: name.into(), value, unit: unit.map(String::from), threshold: None }
+}
+
+#[async_trait]
+impl DiagnosticModule for TimeModule {
+    fn name(&self) -> &'static str {
+        "time"
+    }
+
+    fn description(&self) -> &'static str {
+        "Check clock synchronisation: NTP daemon, offset, stratum, RTC"
+    }
+
+    async fn run(&self, _config: &ModuleConfig) -> Result<DiagnosticReport> {
+        let mut report = DiagnosticReport::new("time", "Clock synchronisation");
+
+        let timedatectl = if command_exists("timedatectl") {
+            run_cmd(&["timedatectl", "show"]).map(|o| parse_key_values(&o)).unwrap_or_default()
+        } else {
+            HashMap::new()
+        };
+        if let Some(tz) = timedatectl.get("Timezone").filter(|t| !t.is_empty()) {
+            report.add_metric(metric("Time zone", MetricValue::Text(tz.clone()), None));
+        }
+
+        let daemons = running_daemons();
+        report.add_metric(metric(
+            "Sync daemon",
+            MetricValue::Text(if daemons.is_empty() {
+                "none".into()
+            } else {
+                daemons.iter().map(|d| d.name()).collect::<Vec<_>>().join(", ")
+            }),
+            None,
+        ));
+        let status = daemons.first().and_then(|d| daemon_status(*d)).unwrap_or_default();
+        let kernel = kernel_clock();
+
+        // The daemon knows best; timedatectl and the kernel flag are the fallbacks.
+        let synchronized = status
+            .synchronized
+            .or_else(|| timedatectl.get("NTPSynchronized").map(|v| v == "yes"))
+            .or(kernel.map(|k| k.synchronized));
+        if let Some(synced) = synchronized {
+            report.add_metric(metric("Synchronized", MetricValue::Boolean(synced), None));
+        }
+        if let Some(server) = &status.server {
+            report.add_metric(metric("Time source", MetricValue::Text(server.clone()), None));
+        }
+        if let Some(stratum) = status.stratum {
+            report.add_metric(metric("Stratum", MetricValue::Integer(i64::from(stratum)), None));
+        }
+        if let Some(offset) = status.offset_ms {
+            report.add_metric(Metric {
+                name: "Clock offset".into(),
+                value: MetricValue::Float(offset),
+                unit: Some("ms".into()),
+                threshold: Some(Threshold { warning: OFFSET_WARN_MS, critical: OFFSET_CRIT_MS }),
+            });
+        }
+        let since_sync = status.last_sync.map(|t| now_secs().saturating_sub(t));
+        if let Some(ago) = since_sync {
+            report.add_metric(metric(
+                "Last sync",
+                MetricValue::Text(format!("{} ago", humantime::format_duration(std::time::Duration::from_secs(ago)))),
+                None,
+            ));
+        }
+        if let Some(k) = kernel.filter(|k| k.synchronized) {
+            report.add_metric(Metric {
+                name: "Kernel estimated error".into(),
+                value: MetricValue::Float(k.est_error_us as f64 / 1000.0),
+                unit: Some("ms".into()),
+                threshold: None,
+            });
+            report.add_metric(Metric {
+                name: "Kernel maximum error".into(),
+                value: MetricValue::Float(k.max_error_us as f64 / 1000.0),
+                unit: Some("ms".into()),
+                threshold: None,
+            });
+        }
+        let local_rtc = rtc_local(&timedatectl);
+        if let Some(local) = local_rtc {
+            report.add_metric(metric("RTC in local time", MetricValue::Boolean(local), None));
+        }
+
+        if daemons.is_empty() {
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "time".into(),
+                message: "No time synchronisation daemon is running".into(),
+                details: Some(
+                    "Without NTP the clock drifts by seconds per day. In containers the host keeps time; \
+                     there this is expected."
+                        .into(),
+                ),
+            });
+            report.add_recommendation(Recommendation {
+                priority: 1,
+                action: "Enable network time synchronisation".into(),
+                command: Some("sudo timedatectl set-ntp true".into()),
+                explanation: "Starts systemd-timesyncd; on servers chrony is the more accurate choice.".into(),
+            });
+        } else if daemons.len() > 1 {
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "time".into(),
+                message: format!(
+                    "Several time daemons are running: {}",
+                    daemons.iter().map(|d| d.name()).collect::<Vec<_>>().join(", ")
+                ),
+                details: Some("They adjust the same clock against each other; keep exactly one.".into()),
+            });
+        }
+        if synchronized == Some(false) && !daemons.is_empty() {
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "time".into(),
+                message: format!("The clock is not synchronised although {} is running", daemons[0].name()),
+                details: Some(
+                    "The daemon cannot reach any server: check that UDP port 123 is allowed outbound and \
+                     that the configured servers resolve."
+                        .into(),
+                ),
+            });
+            let command = match daemons[0] {
+                Daemon::Chrony => "chronyc sources -v",
+                Daemon::Ntpd => "ntpq -p",
+                Daemon::OpenNtpd => "ntpctl -s all",
+                Daemon::Timesyncd => "timedatectl timesync-status",
+            };
+            report.add_recommendation(Recommendation {
+                priority: 1,
+                action: "List the time sources and their reachability".into(),
+                command: Some(command.into()),
+                explanation: "Shows which servers answer and which were rejected.".into(),
+            });
+        }
+        if let Some(offset) = status.offset_ms.filter(|o| o.abs() >= OFFSET_WARN_MS) {
+            let critical = offset.abs() >= OFFSET_CRIT_MS;
+            report.add_finding(Finding {
+                severity: if critical { Severity::Critical } else { Severity::Warning },
+                category: "time".into(),
+                message: format!(
+                    "Clock is {:.0} ms {} of {}",
+                    offset.abs(),
+                    if offset > 0.0 { "ahead" } else { "behind" },
+                    status.server.as_deref().unwrap_or("the time source")
+                ),
+                details: Some(
+                    "Large offsets make TLS certificates look not-yet-valid or expired, break Kerberos \
+                     (5 minute limit) and TOTP codes, and confuse make and other timestamp-based builds."
+                        .into(),
+                ),
+            });
+            if critical {
+                let (command, explanation) = match daemons.first() {
+                    Some(Daemon::Chrony) => ("sudo chronyc makestep", ""),
+                    // A running ntpd refuses large steps; -gq sets the clock once and exits.
+                    Some(Daemon::Ntpd) => (
+                        "sudo systemctl stop ntp && sudo ntpd -gq && sudo systemctl start ntp",
+                        " The service is called ntpd on Fedora and RHEL.",
+                    ),
+                    Some(Daemon::OpenNtpd) => (
+                        "sudo systemctl restart openntpd",
+                        " OpenNTPD sets the clock at startup when the offset is large; versions before 6.0 \
+                         need the -s flag for that.",
+                    ),
+                    _ => ("sudo systemctl restart systemd-timesyncd", ""),
+                };
+                report.add_recommendation(Recommendation {
+                    priority: 1,
+                    action: "Step the clock to the correct time now".into(),
+                    command: Some(command.into()),
+                    explanation: format!(
+                        "Slewing a large offset takes hours; stepping corrects it at once.{}",
+                        explanation
+                    ),
+                });
+            }
+        }
+        if let Some(ago) = since_sync.filter(|a| *a > STALE_SYNC_SECS) {
+            report.add_finding(Finding {
+                severity: Severity::Warning,
+                category: "time".into(),
+                message: format!(
+                    "Last successful time sync was {} ago",
+                    humantime::format_duration(std::time::Duration::from_secs(ago))
+                ),
+                details: Some("The daemon normally polls every few minutes to hours.".into()),
+            });
+        }
+        if local_rtc == Some(true) {
+            report.add_finding(Finding {
+                severity: Severity::Info,
+                category: "time".into(),
+                message: "The hardware clock (RTC) keeps local time".into(),
+                details: Some(
+                    "Linux expects UTC; with local time the clock jumps after daylight saving changes until \
+                     the next sync. Only needed when dual-booting Windows without its RealTimeIsUniversal \
+                     setting."
+                        .into(),
+                ),
+            });
+            report.add_recommendation(Recommendation {
+                priority: 3,
+                action: "Keep the RTC in UTC".into(),
+                command: Some("sudo timedatectl set-local-rtc 0".into()),
+                explanation: "Writes the current system time to the RTC as UTC.".into(),
+            });
+        }
+        // With a UTC RTC the two clocks should agree; local-time RTCs are off by the zone offset.
+        if local_rtc != Some(true) {
+            let rtc = read_first_line(Path::new("/sys/class/rtc/rtc0/since_epoch"))
+                .ok()
+                .flatten()
+                .and_then(|s| s.trim().parse::<i64>().ok());
+            if let Some(drift) = rtc.map(|r| r - now_secs() as i64).filter(|d| d.abs() > RTC_DRIFT_SECS) {
+                report.add_finding(Finding {
+                    severity: Severity::Info,
+                    category: "time".into(),
+                    message: format!("The RTC is {} s {} of the system clock", drift.abs(), if drift > 0 { "ahead" } else { "behind" }),
+                    details: Some("After a reboot the clock starts from the RTC until the first sync.".into()),
+                });
+                report.add_recommendation(Recommendation {
+                    priority: 3,
+                    action: "Write the synchronised system time to the RTC".into(),
+                    command: Some("sudo hwclock --systohc --utc".into()),
+                    explanation: "NTP daemons only do this periodically (every 11 minutes when synchronised).".into(),
+                });
+            }
+        }
+
+        if report.findings.is_empty() {
+            report.add_finding(Finding {
+                severity: Severity::Ok,
+                category: "time".into(),
+                message: "Clock is synchronised".into(),
+                details: None,
+            });
+        }
+        report.compute_overall_severity();
+        Ok(report)
+    }
+}
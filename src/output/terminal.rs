@@ -39,11 +39,15 @@ pub fn write_report<W: Write>(w: &mut W, report: &DiagnosticReport, use_color: b
             let line1 = format!("   ┌─ Finding: {}", f.message);
             let _ = writeln!(w, "{}", line1);
             if let Some(ref d) = f.details {
-                let line2 = format!("   │  → {}", d);
-                if use_color {
-                    let _ = writeln!(w, "{}", line2.dimmed());
-                } else {
-                    let _ = writeln!(w, "{}", line2);
+                // Multi-line details (e.g. directory trees) stay inside the finding box.
+                for (i, detail_line) in d.lines().enumerate() {
+                    let marker = if i == 0 { "→ " } else { "  " };
+                    let line2 = format!("   │  {}{}", marker, detail_line);
+                    if use_color {
+                        let _ = writeln!(w, "{}", line2.dimmed());
+                    } else {
+                        let _ = writeln!(w, "{}", line2);
+                    }
                 }
             }
             let _ = writeln!(w, "   └─ {}", icon);
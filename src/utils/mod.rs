@@ -2,6 +2,7 @@
 
 pub mod files;
 pub mod format;
+pub mod mounts;
 pub mod parse;
 pub mod permissions;
 pub mod process;
@@ -9,7 +10,8 @@ pub mod system;
 
 pub use files::{list_dir, read_file_optional, read_first_line};
 pub use format::{format_bytes, format_duration, format_percent};
+pub use mounts::{mount_for_path, parse_mounts, read_mounts, MountEntry};
 pub use parse::{parse_f64, parse_key_value, parse_key_value_as, parse_size_human, parse_u64};
 pub use permissions::{can_read_proc, can_read_sys, has_all_permissions, has_permission, is_root};
 pub use process::{parse_status, process_name, process_user};
-pub use system::{command_exists, run_cmd, run_cmd_timeout};
+pub use system::{command_exists, run_cmd, run_cmd_timeout, shell_quote};
@@ -0,0 +1,111 @@
+//! Mount table helpers (/proc/self/mounts).
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// Filesystem types that never hold user data and should be skipped for capacity checks.
+pub const PSEUDO_FILESYSTEMS: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "proc",
+    "pstore",
+    "ramfs",
+    "rpc_pipefs",
+    "securityfs",
+    "selinuxfs",
+    "squashfs",
+    "sysfs",
+    "tracefs",
+];
+
+/// tmpfs mounts that hold no user data: the legacy cgroup v1 directory and systemd's
+/// per-service credential stores (read-only and always exactly full).
+pub const SKIPPED_TMPFS_MOUNTS: &[&str] = &["/sys/fs/cgroup", "/run/credentials"];
+
+/// One line of the mount table.
+#[derive(Debug, Clone)]
+pub struct MountEntry {
+    pub device: String,
+    pub mountpoint: PathBuf,
+    pub fstype: String,
+    pub options: Vec<String>,
+}
+
+impl MountEntry {
+    /// True if mounted read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.options.iter().any(|o| o == "ro")
+    }
+
+    /// True for kernel pseudo filesystems. tmpfs counts as real (a full /run or /dev/shm
+    /// causes ENOSPC like any disk), except for the mounts in [`SKIPPED_TMPFS_MOUNTS`].
+    pub fn is_pseudo(&self) -> bool {
+        PSEUDO_FILESYSTEMS.contains(&self.fstype.as_str())
+            || (self.fstype == "tmpfs" && SKIPPED_TMPFS_MOUNTS.iter().any(|m| self.mountpoint.starts_with(m)))
+    }
+}
+
+/// Decode the octal escapes (`\040` for space, etc.) used in /proc/mounts fields.
+pub fn unescape_mount_field(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 4 <= bytes.len() {
+            let digits = std::str::from_utf8(&bytes[i + 1..i + 4]).unwrap_or("");
+            if let Ok(v) = u8::from_str_radix(digits, 8) {
+                out.push(v);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Parse mount table content in /proc/mounts format.
+pub fn parse_mounts(content: &str) -> Vec<MountEntry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 4 {
+                return None;
+            }
+            Some(MountEntry {
+                device: unescape_mount_field(parts[0]),
+                mountpoint: PathBuf::from(unescape_mount_field(parts[1])),
+                fstype: parts[2].to_string(),
+                options: parts[3].split(',').map(String::from).collect(),
+            })
+        })
+        .collect()
+}
+
+/// Read the current mount table.
+pub fn read_mounts() -> Result<Vec<MountEntry>> {
+    let content = std::fs::read_to_string("/proc/self/mounts")?;
+    Ok(parse_mounts(&content))
+}
+
+/// Find the mount that contains `path` (longest matching mountpoint wins).
+pub fn mount_for_path<'a>(mounts: &'a [MountEntry], path: &Path) -> Option<&'a MountEntry> {
+    mounts
+        .iter()
+        .filter(|m| path.starts_with(&m.mountpoint))
+        .max_by_key(|m| m.mountpoint.as_os_str().len())
+}
@@ -26,6 +26,17 @@ pub fn run_cmd_timeout(args: &[&str], _timeout: Duration) -> Result<String> {
     run_cmd(args)
 }
 
+/// Quote `s` for a POSIX shell, for paths pasted into suggested commands. Strings made only
+/// of safe characters are returned as they are.
+pub fn shell_quote(s: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "/._-+:,@%=".contains(c);
+    if !s.is_empty() && s.chars().all(safe) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
 /// Check if a command is available in PATH.
 pub fn command_exists(name: &str) -> bool {
     which::which(name).is_ok()
@@ -42,6 +42,18 @@ fn all_subcommand_runs() {
         .success();
 }
 
+#[test]
+fn all_subcommand_writes_no_state() {
+    let state = tempfile::tempdir().unwrap();
+    Command::cargo_bin("rustwhy")
+        .unwrap()
+        .env("RUSTWHY_STATE_DIR", state.path())
+        .arg("all")
+        .assert()
+        .success();
+    assert_eq!(std::fs::read_dir(state.path()).unwrap().count(), 0);
+}
+
 #[test]
 fn completions_bash_prints() {
     Command::cargo_bin("rustwhy")
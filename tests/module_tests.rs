@@ -1,6 +1,6 @@
 //! Integration tests for diagnostic modules (via lib).
 
-use rustwhy::core::{run_module, ModuleConfig};
+use rustwhy::core::{run_module, MetricValue, ModuleConfig};
 use rustwhy::modules::get_module;
 use std::collections::HashMap;
 
@@ -11,7 +11,11 @@ fn default_config() -> ModuleConfig {
         interval: 2,
         top_n: 5,
         json_output: false,
-        extra_args: HashMap::new(),
+        // Keep disk usage history and snapshots out of the developer's home.
+        extra_args: HashMap::from([(
+            "state_dir".to_string(),
+            format!("{}/state", env!("CARGO_TARGET_TMPDIR")),
+        )]),
     }
 }
 
@@ -32,7 +36,534 @@ async fn boot_module_returns_report() {
     assert_eq!(report.module, "boot");
 }
 
+#[tokio::test]
+async fn smart_module_reports_unknown_device() {
+    let module = get_module("smart").expect("smart module exists");
+    let mut config = default_config();
+    config.extra_args.insert("device".into(), "/dev/does-not-exist".into());
+    let report = run_module(module, &config).await.expect("run succeeds");
+    assert_eq!(report.module, "smart");
+    assert!(report
+        .findings
+        .iter()
+        .any(|f| f.message.contains("No SMART-capable drive named does-not-exist")));
+}
+
+#[tokio::test]
+async fn raid_module_is_part_of_all() {
+    assert!(rustwhy::modules::all_modules().iter().any(|m| m.name() == "raid"));
+    let module = get_module("raid").expect("raid module exists");
+    let report = run_module(module, &default_config()).await.expect("run succeeds");
+    assert_eq!(report.module, "raid");
+    assert!(!report.summary.is_empty());
+}
+
+#[tokio::test]
+async fn io_module_reports_dirty_page_state() {
+    let module = get_module("io").expect("io module exists");
+    let report = run_module(module, &default_config()).await.expect("run succeeds");
+    assert!(report.metrics.iter().any(|m| m.name == "Dirty pages"));
+    assert!(report
+        .metrics
+        .iter()
+        .any(|m| m.name == "Dirty limit (background / throttle)"));
+}
+
+#[tokio::test]
+async fn io_module_attributes_process_io_to_open_files() {
+    use std::io::Write;
+
+    let dir = tempfile::tempdir_in(env!("CARGO_TARGET_TMPDIR")).expect("tempdir");
+    let file_path = dir.path().join("busy.dat");
+    let mut file = std::fs::File::create(&file_path).unwrap();
+    file.write_all(&vec![b'x'; 20 * 1024 * 1024]).unwrap();
+    file.sync_all().unwrap();
+
+    let module = get_module("io").expect("io module exists");
+    let mut config = default_config();
+    config.top_n = usize::MAX;
+    let report = run_module(module, &config).await.expect("run succeeds");
+    let pid = format!("(PID {})", std::process::id());
+    let finding = report
+        .findings
+        .iter()
+        .find(|f| f.category == "process" && f.message.contains(&pid))
+        .expect("finding for the test process");
+    let details = finding.details.as_deref().unwrap_or("");
+    assert!(details.contains(&file_path.display().to_string()));
+    assert!(details.contains("rw, at 20.0 MiB"));
+    drop(file);
+}
+
+#[tokio::test]
+async fn io_module_summarizes_block_queues() {
+    let disks: Vec<String> = std::fs::read_dir("/sys/block")
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .filter(|n| !n.starts_with("loop") && !n.starts_with("ram") && !n.starts_with("zram"))
+                .collect()
+        })
+        .unwrap_or_default();
+    let Some(disk) = disks.iter().find(|d| !d.starts_with("dm-") && !d.starts_with("md")) else {
+        return;
+    };
+
+    let module = get_module("io").expect("io module exists");
+    let mut config = default_config();
+    config.extra_args.insert("device".into(), disk.clone());
+    let report = run_module(module, &config).await.expect("run succeeds");
+    let queue = report
+        .metrics
+        .iter()
+        .find(|m| m.name == format!("{} queue", disk))
+        .expect("queue summary for the disk");
+    assert!(matches!(&queue.value, rustwhy::MetricValue::Text(t) if t.starts_with("scheduler ")));
+}
+
 #[tokio::test]
 async fn get_module_unknown_returns_none() {
     assert!(get_module("unknown").is_none());
 }
+
+#[tokio::test]
+async fn disk_module_rolls_up_nested_sizes() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let deep = dir.path().join("top/a/b/c/d/e/f/g");
+    std::fs::create_dir_all(&deep).unwrap();
+    std::fs::write(deep.join("blob"), vec![1u8; 1024 * 1024]).unwrap();
+
+    let module = get_module("disk").expect("disk module exists");
+    let mut config = default_config();
+    config.extra_args.insert("path".into(), dir.path().display().to_string());
+    config.extra_args.insert("depth".into(), "1".into());
+    let report = run_module(module, &config).await.expect("run succeeds");
+
+    let tree = &report.raw_data.expect("tree in raw data")["tree"];
+    let top = &tree["children"][0];
+    assert!(top["path"].as_str().unwrap().ends_with("top"));
+    assert!(top["size"].as_u64().unwrap() >= 1024 * 1024);
+    assert!(top["children"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn disk_module_without_path_only_checks_capacity() {
+    let module = get_module("disk").expect("disk module exists");
+    let report = run_module(module, &default_config()).await.expect("run succeeds");
+    assert!(report.metrics.iter().any(|m| m.name == "/ used"));
+    assert!(!report.metrics.iter().any(|m| m.name == "Path analyzed"));
+}
+
+#[tokio::test]
+async fn disk_module_classifies_cargo_target_dirs() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let project = dir.path().join("project");
+    std::fs::create_dir_all(project.join("target/debug")).unwrap();
+    std::fs::write(project.join("Cargo.toml"), "[package]\n").unwrap();
+    std::fs::write(project.join("target/debug/app"), vec![7u8; 12 * 1024 * 1024]).unwrap();
+
+    let module = get_module("disk").expect("disk module exists");
+    let mut config = default_config();
+    config.extra_args.insert("path".into(), dir.path().display().to_string());
+    let report = run_module(module, &config).await.expect("run succeeds");
+
+    assert!(report
+        .findings
+        .iter()
+        .any(|f| f.category == "cleanup" && f.message.contains("Cargo build output")));
+    assert!(report
+        .recommendations
+        .iter()
+        .any(|r| r.command.as_deref().is_some_and(|c| c.starts_with("cargo clean"))));
+}
+
+#[tokio::test]
+async fn disk_module_reports_deleted_but_open_files() {
+    use std::io::Write;
+
+    let dir = tempfile::tempdir_in(env!("CARGO_TARGET_TMPDIR")).expect("tempdir");
+    let file_path = dir.path().join("rotated.log");
+    let mut file = std::fs::File::create(&file_path).unwrap();
+    file.write_all(&vec![b'x'; 2 * 1024 * 1024]).unwrap();
+    file.sync_all().unwrap();
+    std::fs::remove_file(&file_path).unwrap();
+
+    let module = get_module("disk").expect("disk module exists");
+    let report = run_module(module, &default_config()).await.expect("run succeeds");
+    let pid = format!("(PID {})", std::process::id());
+    assert!(report
+        .findings
+        .iter()
+        .any(|f| f.category == "deleted-open" && f.message.contains(&pid)));
+    // A few MiB are not worth destroying data for.
+    let fd_link = format!("/proc/{}/fd/", std::process::id());
+    assert!(!report
+        .recommendations
+        .iter()
+        .any(|r| r.command.as_deref().is_some_and(|c| c.contains("truncate") && c.contains(&fd_link))));
+    drop(file);
+}
+
+#[tokio::test]
+async fn disk_module_finds_duplicates_and_ignores_hardlinks() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    std::fs::write(dir.path().join("original"), &content).unwrap();
+    std::fs::write(dir.path().join("copy"), &content).unwrap();
+    std::fs::hard_link(dir.path().join("original"), dir.path().join("link")).unwrap();
+    let mut different = content.clone();
+    different[99_999] ^= 0xff;
+    std::fs::write(dir.path().join("almost"), &different).unwrap();
+
+    let module = get_module("disk").expect("disk module exists");
+    let mut config = default_config();
+    config.extra_args.insert("path".into(), dir.path().display().to_string());
+    config.extra_args.insert("duplicates".into(), "true".into());
+    let report = run_module(module, &config).await.expect("run succeeds");
+
+    let groups: Vec<_> = report.findings.iter().filter(|f| f.category == "duplicates").collect();
+    assert_eq!(groups.len(), 1);
+    assert!(groups[0].message.starts_with("2 copies"));
+}
+
+#[tokio::test]
+async fn disk_module_honors_exclude_patterns() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    for name in ["keep", "node_modules"] {
+        std::fs::create_dir_all(dir.path().join(name)).unwrap();
+        std::fs::write(dir.path().join(name).join("data"), vec![0u8; 512 * 1024]).unwrap();
+    }
+
+    let module = get_module("disk").expect("disk module exists");
+    let mut config = default_config();
+    config.extra_args.insert("path".into(), dir.path().display().to_string());
+    config.extra_args.insert("exclude".into(), "node_modules".into());
+    config.json_output = true;
+    let report = run_module(module, &config).await.expect("run succeeds");
+
+    let tree = &report.raw_data.expect("tree in raw data")["tree"];
+    let children = tree["children"].as_array().unwrap();
+    assert_eq!(children.len(), 1);
+    assert!(children[0]["path"].as_str().unwrap().ends_with("keep"));
+}
+
+#[tokio::test]
+async fn disk_module_predicts_time_to_full_from_history() {
+    let state = tempfile::tempdir().expect("tempdir");
+    let two_days_ago = chrono::Utc::now() - chrono::Duration::days(2);
+    let history = serde_json::json!([
+        { "timestamp": two_days_ago, "mountpoint": "/", "used": 0, "total": 0 }
+    ]);
+    std::fs::write(state.path().join("disk-usage.json"), history.to_string()).unwrap();
+
+    let module = get_module("disk").expect("disk module exists");
+    let mut config = default_config();
+    config.extra_args.insert("state_dir".into(), state.path().display().to_string());
+    let report = run_module(module.clone(), &config).await.expect("run succeeds");
+
+    assert!(report.metrics.iter().any(|m| m.name == "/ growth"));
+    assert!(report.metrics.iter().any(|m| m.name == "/ full in"));
+    // Without --record the history is only read.
+    let saved = std::fs::read_to_string(state.path().join("disk-usage.json")).unwrap();
+    assert_eq!(serde_json::from_str::<Vec<serde_json::Value>>(&saved).unwrap().len(), 1);
+
+    config.extra_args.insert("record".into(), "true".into());
+    run_module(module, &config).await.expect("run succeeds");
+    let saved = std::fs::read_to_string(state.path().join("disk-usage.json")).unwrap();
+    let samples: Vec<serde_json::Value> = serde_json::from_str(&saved).unwrap();
+    assert_eq!(samples.iter().filter(|s| s["mountpoint"] == "/").count(), 2);
+}
+
+#[tokio::test]
+async fn disk_module_reports_directories_grown_since_last_scan() {
+    let state = tempfile::tempdir().expect("tempdir");
+    let dir = tempfile::tempdir().expect("tempdir");
+    for name in ["steady", "growing"] {
+        std::fs::create_dir_all(dir.path().join(name)).unwrap();
+        std::fs::write(dir.path().join(name).join("data"), vec![0u8; 256 * 1024]).unwrap();
+    }
+
+    let module = get_module("disk").expect("disk module exists");
+    let mut config = default_config();
+    config.extra_args.insert("path".into(), dir.path().display().to_string());
+    config.extra_args.insert("state_dir".into(), state.path().display().to_string());
+    config.extra_args.insert("record".into(), "true".into());
+    config.json_output = true;
+    let first = run_module(module.clone(), &config).await.expect("run succeeds");
+    assert!(!first.findings.iter().any(|f| f.category == "growth" && f.message.contains("grew")));
+
+    std::fs::write(dir.path().join("growing/more"), vec![1u8; 2 * 1024 * 1024]).unwrap();
+    let second = run_module(module, &config).await.expect("run succeeds");
+    let grown = second
+        .findings
+        .iter()
+        .find(|f| f.category == "growth" && f.message.contains("grew the most"))
+        .expect("growth finding");
+    assert!(grown.message.contains("growing"));
+    assert!(!grown.details.as_deref().unwrap_or("").contains("steady"));
+}
+
+#[tokio::test]
+async fn net_module_pings_loopback_with_requested_count() {
+    let module = get_module("net").expect("net module exists");
+    let mut config = default_config();
+    config.extra_args.insert("host".into(), "127.0.0.1".into());
+    config.extra_args.insert("count".into(), "2".into());
+    let report = run_module(module, &config).await.expect("run succeeds");
+    // Sandboxes without root or ping_group_range cannot open ICMP sockets at all.
+    if report.findings.iter().any(|f| f.message.starts_with("Could not ping")) {
+        return;
+    }
+    let sent = report
+        .metrics
+        .iter()
+        .find(|m| m.name == "Packets sent/received")
+        .expect("packet counts");
+    assert!(matches!(&sent.value, MetricValue::Text(t) if t == "2/2"));
+    assert!(report.metrics.iter().any(|m| m.name == "Ping latency (avg)"));
+}
+
+#[tokio::test]
+async fn net_dns_only_skips_ping_and_reports_resolver() {
+    let module = get_module("net").expect("net module exists");
+    let mut config = default_config();
+    config.extra_args.insert("host".into(), "localhost".into());
+    config.extra_args.insert("dns_only".into(), "true".into());
+    let report = run_module(module, &config).await.expect("run succeeds");
+    assert!(!report.metrics.iter().any(|m| m.name.starts_with("Ping")));
+    assert!(report.findings.iter().all(|f| f.category == "dns"));
+    if std::path::Path::new("/etc/resolv.conf").exists() {
+        assert!(report.metrics.iter().any(|m| m.name == "Resolver"));
+    }
+}
+
+#[tokio::test]
+async fn net_module_reports_default_gateway_from_routing_table() {
+    let module = get_module("net").expect("net module exists");
+    let mut config = default_config();
+    config.extra_args.insert("host".into(), "127.0.0.1".into());
+    config.extra_args.insert("count".into(), "1".into());
+    let report = run_module(module, &config).await.expect("run succeeds");
+    // Loopback never needs a gateway, so no hop may be blamed.
+    assert!(!report.findings.iter().any(|f| f.category == "routing" && f.message.starts_with("Hop")));
+    let has_default = std::fs::read_to_string("/proc/net/route")
+        .map(|c| c.lines().skip(1).any(|l| l.split_whitespace().nth(1) == Some("00000000")))
+        .unwrap_or(false);
+    if has_default {
+        assert!(report.metrics.iter().any(|m| m.name == "Default gateway (IPv4)"));
+    }
+}
+
+#[tokio::test]
+async fn net_interfaces_flag_reports_link_and_error_counters() {
+    let module = get_module("net").expect("net module exists");
+    let mut config = default_config();
+    config.extra_args.insert("host".into(), "127.0.0.1".into());
+    config.extra_args.insert("count".into(), "1".into());
+    config.extra_args.insert("interfaces".into(), "true".into());
+    let report = run_module(module, &config).await.expect("run succeeds");
+    let content = std::fs::read_to_string("/proc/net/dev").unwrap_or_default();
+    for name in content.lines().skip(2).filter_map(|l| l.split(':').next()).map(str::trim) {
+        if name == "lo" {
+            continue;
+        }
+        assert!(report.metrics.iter().any(|m| m.name == format!("{} link", name)), "{} link", name);
+        assert!(report.metrics.iter().any(|m| m.name == format!("{} rx errors", name)));
+    }
+}
+
+#[tokio::test]
+async fn net_module_reads_tcp_stack_counters() {
+    let snmp = std::fs::read_to_string("/proc/net/snmp").unwrap_or_default();
+    let tcp: Vec<&str> = snmp.lines().filter(|l| l.starts_with("Tcp:")).collect();
+    let Some(retrans_before) = tcp.first().and_then(|header| {
+        let idx = header.split_whitespace().position(|w| w == "RetransSegs")?;
+        tcp.get(1)?.split_whitespace().nth(idx)?.parse::<i64>().ok()
+    }) else {
+        return;
+    };
+
+    let module = get_module("net").expect("net module exists");
+    let mut config = default_config();
+    config.extra_args.insert("host".into(), "127.0.0.1".into());
+    config.extra_args.insert("count".into(), "1".into());
+    let report = run_module(module, &config).await.expect("run succeeds");
+    let metric = report
+        .metrics
+        .iter()
+        .find(|m| m.name == "TCP retransmits since boot")
+        .expect("retransmit counter");
+    assert!(matches!(metric.value, MetricValue::Integer(n) if n >= retrans_before));
+    assert!(report.metrics.iter().any(|m| m.name == "Listen overflows since boot"));
+}
+
+#[tokio::test]
+async fn port_module_names_the_owner_of_a_port() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+    let port = listener.local_addr().unwrap().port();
+
+    let module = get_module("port").expect("port module exists");
+    let mut config = default_config();
+    config.extra_args.insert("port".into(), port.to_string());
+    let report = run_module(module, &config).await.expect("run succeeds");
+    let finding = report
+        .findings
+        .iter()
+        .find(|f| f.message.starts_with(&format!("Port {}/tcp is in use", port)))
+        .expect("listener finding");
+    assert!(finding.message.contains(&format!("PID {}", std::process::id())));
+    assert!(rustwhy::modules::all_modules().iter().any(|m| m.name() == "port"));
+}
+
+#[tokio::test]
+async fn net_full_times_http_and_detects_captive_portal() {
+    use std::io::{Read, Write};
+
+    // Stands in for both the probed site and a portal that rewrites the 204 check page.
+    let server = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+    let url = format!("http://{}/generate_204", server.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for mut stream in server.incoming().flatten() {
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf);
+            let body = "<html><title>Guest WiFi login</title></html>";
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+    });
+
+    let module = get_module("net").expect("net module exists");
+    let mut config = default_config();
+    config.extra_args.insert("host".into(), "127.0.0.1".into());
+    config.extra_args.insert("count".into(), "1".into());
+    config.extra_args.insert("full".into(), "true".into());
+    config.extra_args.insert("url".into(), url.clone());
+    config.extra_args.insert("portal_url".into(), url);
+    let report = run_module(module, &config).await.expect("run succeeds");
+
+    let status = report.metrics.iter().find(|m| m.name == "HTTP status").expect("status metric");
+    assert!(matches!(&status.value, MetricValue::Text(s) if s.starts_with("200 ")));
+    assert!(report.metrics.iter().any(|m| m.name == "Time to first byte"));
+    let portal = report
+        .findings
+        .iter()
+        .find(|f| f.message.starts_with("Captive portal or filtering proxy detected"))
+        .expect("portal finding");
+    assert!(portal.message.contains("Guest WiFi login"));
+}
+
+#[tokio::test]
+async fn net_module_probes_path_mtu_to_reachable_host() {
+    // The host's own primary address: routed via a real interface, answered locally.
+    let Ok(socket) = std::net::UdpSocket::bind("0.0.0.0:0") else {
+        return;
+    };
+    if socket.connect("198.51.100.1:9").is_err() {
+        return;
+    }
+    let local = socket.local_addr().unwrap().ip();
+
+    let module = get_module("net").expect("net module exists");
+    let mut config = default_config();
+    config.extra_args.insert("host".into(), local.to_string());
+    config.extra_args.insert("count".into(), "1".into());
+    let report = run_module(module, &config).await.expect("run succeeds");
+    if !report.metrics.iter().any(|m| m.name == "Packets sent/received" && matches!(&m.value, MetricValue::Text(s) if s == "1/1")) {
+        return;
+    }
+    let path = report
+        .metrics
+        .iter()
+        .find(|m| m.name == format!("Path MTU to {}", local))
+        .expect("path MTU metric");
+    assert!(matches!(&path.value, MetricValue::Text(s) if s.ends_with(" bytes")));
+    assert!(report.metrics.iter().any(|m| m.name.starts_with("Interface MTU (")));
+}
+
+#[tokio::test]
+async fn net_bandwidth_attributes_tcp_traffic_to_this_process() {
+    use std::io::{Read, Write};
+
+    // Loopback traffic is ignored, so talk to the host's own primary address.
+    let Ok(probe) = std::net::UdpSocket::bind("0.0.0.0:0") else {
+        return;
+    };
+    if probe.connect("198.51.100.1:9").is_err() || which::which("ss").is_err() {
+        return;
+    }
+    let local = probe.local_addr().unwrap().ip();
+    let server = std::net::TcpListener::bind((local, 0)).expect("bind");
+    let addr = server.local_addr().unwrap();
+    std::thread::spawn(move || {
+        if let Ok((mut conn, _)) = server.accept() {
+            let mut buf = [0u8; 65536];
+            while matches!(conn.read(&mut buf), Ok(n) if n > 0) {}
+        }
+    });
+    let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let sender = {
+        let stop = stop.clone();
+        std::thread::spawn(move || {
+            let mut conn = std::net::TcpStream::connect(addr).expect("connect");
+            let chunk = [0u8; 65536];
+            while !stop.load(std::sync::atomic::Ordering::Relaxed) {
+                if conn.write_all(&chunk).is_err() {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(2));
+            }
+        })
+    };
+
+    let module = get_module("net").expect("net module exists");
+    let mut config = default_config();
+    config.interval = 1;
+    config.extra_args.insert("host".into(), "127.0.0.1".into());
+    config.extra_args.insert("count".into(), "1".into());
+    config.extra_args.insert("bandwidth".into(), "true".into());
+    let report = run_module(module, &config).await.expect("run succeeds");
+    stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    sender.join().unwrap();
+
+    let me = format!("(PID {})", std::process::id());
+    let metric = report
+        .metrics
+        .iter()
+        .find(|m| m.name.starts_with("Bandwidth ") && m.name.ends_with(&me))
+        .expect("bandwidth metric for this process");
+    assert!(matches!(&metric.value, MetricValue::Text(s) if s.starts_with("rx ")));
+}
+
+#[tokio::test]
+async fn time_module_reports_sync_state() {
+    let module = get_module("time").expect("time module exists");
+    let report = run_module(module, &default_config()).await.expect("run succeeds");
+    assert!(report.metrics.iter().any(|m| m.name == "Sync daemon"));
+    // adjtimex(2) is always available, so the sync state is known even without a daemon.
+    assert!(report
+        .metrics
+        .iter()
+        .any(|m| m.name == "Synchronized" && matches!(m.value, MetricValue::Boolean(_))));
+    assert!(rustwhy::modules::all_modules().iter().any(|m| m.name() == "time"));
+}
+
+#[tokio::test]
+async fn firewall_module_reports_conntrack_usage() {
+    let module = get_module("firewall").expect("firewall module exists");
+    let mut config = default_config();
+    config.extra_args.insert("port".into(), "8080".into());
+    let report = run_module(module, &config).await.expect("run succeeds");
+    assert!(!report.findings.is_empty());
+    if std::path::Path::new("/proc/sys/net/netfilter/nf_conntrack_max").exists() {
+        assert!(report
+            .metrics
+            .iter()
+            .any(|m| m.name == "Conntrack usage" && matches!(m.value, MetricValue::Float(_))));
+    }
+    assert!(rustwhy::modules::all_modules().iter().any(|m| m.name() == "firewall"));
+}
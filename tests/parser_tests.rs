@@ -0,0 +1,599 @@
+//! Fixture tests for the parsers of external tool output used by the modules.
+
+use rustwhy::modules::disk::volumes::{
+    parse_btrfs_qgroups, parse_btrfs_snapshot_list, parse_lvs, parse_zfs_datasets, parse_zfs_snapshots,
+    parse_zpool_list,
+};
+use rustwhy::modules::firewall::{
+    evaluate, iptables, nft, Backend, Family, Match, Packet, Ruleset, Verdict,
+};
+use rustwhy::modules::net::wifi::{
+    analyze_congestion, band, channel, parse_iw_fields, parse_proc_wireless, parse_scan_dump, WifiLink,
+};
+use rustwhy::modules::raid::{analyze_md, parse_mdstat};
+use rustwhy::modules::smart::{assess, parse_nvme_log, parse_smartctl};
+use rustwhy::modules::time::{parse_chrony_tracking, parse_ntpq_rv, parse_timesync};
+use rustwhy::{DiagnosticReport, Severity};
+
+#[test]
+fn btrfs_snapshot_list_keeps_ids_and_top_level_paths() {
+    let output = "ID 257 gen 1042 cgen 980 top level 5 otime 2024-05-01 10:00:01 path @snapshots/1/snapshot\n\
+                  ID 258 gen 1043 cgen 1010 top level 5 otime 2024-05-02 10:00:01 path @snapshots/2/snap shot\n\
+                  garbage line\n";
+    let snapshots = parse_btrfs_snapshot_list(output);
+    assert_eq!(
+        snapshots,
+        vec![(257, "@snapshots/1/snapshot".to_string()), (258, "@snapshots/2/snap shot".to_string())]
+    );
+}
+
+#[test]
+fn btrfs_qgroups_map_level_zero_ids_to_exclusive_bytes() {
+    let output = "qgroupid         rfer         excl     path\n\
+                  --------         ----         ----     ----\n\
+                  0/5          16384        16384     <toplevel>\n\
+                  0/257   1073741824    536870912     @snapshots/1/snapshot\n\
+                  1/100   2147483648   1073741824     <squota space holder>\n";
+    let excl = parse_btrfs_qgroups(output);
+    assert_eq!(excl.len(), 2);
+    assert_eq!(excl[&5], 16384);
+    assert_eq!(excl[&257], 536_870_912);
+}
+
+#[test]
+fn zpool_list_parses_capacity_and_missing_fragmentation() {
+    let output = "tank\t1000000000\t850000000\t85\t12\nboot\t500000\t1000\t0\t-\n";
+    let pools = parse_zpool_list(output);
+    assert_eq!(pools.len(), 2);
+    assert_eq!(pools[0].name, "tank");
+    assert_eq!(pools[0].alloc, 850_000_000);
+    assert_eq!(pools[0].cap, 85.0);
+    assert_eq!(pools[0].frag, Some(12.0));
+    assert_eq!(pools[1].frag, None);
+}
+
+#[test]
+fn zfs_datasets_and_snapshots_parse_tab_separated_rows() {
+    let datasets = parse_zfs_datasets("tank/home\t5000\t1000\t2000\nbad\trow\n");
+    assert_eq!(datasets.len(), 1);
+    assert_eq!(datasets[0].name, "tank/home");
+    assert_eq!((datasets[0].used, datasets[0].avail, datasets[0].used_by_snapshots), (5000, 1000, 2000));
+
+    let snapshots = parse_zfs_snapshots("tank/home@daily-1\t100\ntank/home@daily-2\t4096\n");
+    assert_eq!(snapshots, vec![("tank/home@daily-1".into(), 100), ("tank/home@daily-2".into(), 4096)]);
+}
+
+#[test]
+fn lvs_marks_thin_pools_and_their_volumes() {
+    let output = "  vg0;pool0;twi-aotz--;107374182400;85.50;12.00;\n\
+                  \x20 vg0;thin1;Vwi-aotz--;53687091200;40.00;;pool0\n\
+                  \x20 vg0;root;-wi-ao----;21474836480;;;\n";
+    let lvs = parse_lvs(output);
+    assert_eq!(lvs.len(), 3);
+    assert!(lvs[0].is_thin_pool());
+    assert_eq!(lvs[0].data_pct, Some(85.5));
+    assert_eq!(lvs[0].metadata_pct, Some(12.0));
+    assert!(!lvs[1].is_thin_pool());
+    assert_eq!(lvs[1].pool, "pool0");
+    assert_eq!(lvs[1].metadata_pct, None);
+    assert_eq!(lvs[2].size, 21_474_836_480);
+}
+
+fn ata_attribute(id: u64, name: &str, raw: u64) -> serde_json::Value {
+    serde_json::json!({ "id": id, "name": name, "when_failed": "", "raw": { "value": raw, "string": raw.to_string() } })
+}
+
+#[test]
+fn smartctl_ata_thresholds_for_reallocated_and_pending_sectors() {
+    let healthy = serde_json::json!({
+        "device": { "protocol": "ATA" },
+        "model_name": "WDC WD40EFRX",
+        "rotation_rate": 5400,
+        "smart_status": { "passed": true },
+        "temperature": { "current": 38 },
+        "power_on_time": { "hours": 1200 },
+        "ata_smart_attributes": { "table": [
+            ata_attribute(5, "Reallocated_Sector_Ct", 0),
+            ata_attribute(197, "Current_Pending_Sector", 0),
+        ] }
+    });
+    let drive = parse_smartctl("/dev/sda", &healthy);
+    assert!(drive.rotational);
+    assert_eq!(drive.temperature, Some(38));
+    let mut report = DiagnosticReport::new("smart", "test");
+    assert_eq!(assess(&mut report, &drive), Severity::Ok);
+
+    let mut failing = healthy.clone();
+    failing["ata_smart_attributes"]["table"] = serde_json::json!([
+        ata_attribute(5, "Reallocated_Sector_Ct", 8),
+        // Packed raw value: only the first number counts.
+        { "id": 197, "name": "Current_Pending_Sector", "when_failed": "",
+          "raw": { "value": 4_294_967_308_u64, "string": "12 (0 4)" } },
+        ata_attribute(199, "UDMA_CRC_Error_Count", 3),
+    ]);
+    let drive = parse_smartctl("/dev/sda", &failing);
+    let mut report = DiagnosticReport::new("smart", "test");
+    assert_eq!(assess(&mut report, &drive), Severity::Critical);
+    let severity_of = |needle: &str| report.findings.iter().find(|f| f.message.contains(needle)).map(|f| f.severity);
+    assert_eq!(severity_of("8 reallocated sectors"), Some(Severity::Warning));
+    assert_eq!(severity_of("12 sectors pending reallocation"), Some(Severity::Critical));
+    assert_eq!(severity_of("interface CRC errors"), Some(Severity::Info));
+}
+
+#[test]
+fn smartctl_nvme_wear_and_media_errors() {
+    let v = serde_json::json!({
+        "device": { "protocol": "NVMe" },
+        "model_name": "Samsung SSD 970 EVO",
+        "smart_status": { "passed": true },
+        "nvme_smart_health_information_log": {
+            "critical_warning": 0, "available_spare": 100, "available_spare_threshold": 10,
+            "percentage_used": 93, "media_errors": 2, "num_err_log_entries": 5, "unsafe_shutdowns": 40
+        }
+    });
+    let drive = parse_smartctl("/dev/nvme0", &v);
+    assert!(!drive.rotational);
+    let mut report = DiagnosticReport::new("smart", "test");
+    assert_eq!(assess(&mut report, &drive), Severity::Warning);
+    assert!(report.findings.iter().any(|f| f.message.contains("93% of its rated write endurance")));
+    assert!(report.findings.iter().any(|f| f.message.contains("2 media error(s)")));
+
+    let mut worn = v.clone();
+    worn["nvme_smart_health_information_log"]["percentage_used"] = 100.into();
+    worn["nvme_smart_health_information_log"]["critical_warning"] = 0x04.into();
+    let mut report = DiagnosticReport::new("smart", "test");
+    assert_eq!(assess(&mut report, &parse_smartctl("/dev/nvme0", &worn)), Severity::Critical);
+    assert!(report.findings.iter().any(|f| f.message.contains("reliability degraded by media errors")));
+}
+
+#[test]
+fn nvme_health_log_decodes_counters_and_unknown_temperature() {
+    let mut buf = [0u8; 512];
+    buf[0] = 0x01;
+    buf[1..3].copy_from_slice(&310u16.to_le_bytes());
+    buf[3] = 4;
+    buf[4] = 10;
+    buf[5] = 42;
+    buf[128..136].copy_from_slice(&9000u64.to_le_bytes());
+    buf[144..152].copy_from_slice(&17u64.to_le_bytes());
+    buf[160..168].copy_from_slice(&3u64.to_le_bytes());
+    buf[176..184].copy_from_slice(&25u64.to_le_bytes());
+    let (log, temp, hours) = parse_nvme_log(&buf);
+    assert_eq!(temp, Some(37));
+    assert_eq!(hours, 9000);
+    assert_eq!(log.critical_warning, 0x01);
+    assert_eq!((log.available_spare, log.spare_threshold, log.percentage_used), (4, 10, 42));
+    assert_eq!((log.unsafe_shutdowns, log.media_errors, log.error_log_entries), (17, 3, 25));
+
+    buf[1..3].copy_from_slice(&0u16.to_le_bytes());
+    assert_eq!(parse_nvme_log(&buf).1, None);
+}
+
+const DEGRADED_MDSTAT: &str = "\
+Personalities : [raid1] [raid6] [raid5] [raid4]
+md0 : active raid1 sdb1[1](F) sda1[0]
+      976630464 blocks super 1.2 [2/1] [U_]
+      bitmap: 3/8 pages [12KB], 65536KB chunk
+
+md1 : active raid5 sde1[3] sdd1[1] sdc1[0]
+      1953260544 blocks super 1.2 level 5, 512k chunk, algorithm 2 [3/2] [UU_]
+      [===>.................]  recovery = 18.4% (179856000/976630272) finish=81.3min speed=163320K/sec
+
+unused devices: <none>
+";
+
+#[test]
+fn mdstat_degraded_raid1_without_redundancy_is_critical() {
+    let arrays = parse_mdstat(DEGRADED_MDSTAT);
+    assert_eq!(arrays.len(), 2);
+    let md0 = &arrays[0];
+    assert_eq!((md0.name.as_str(), md0.level.as_str()), ("md0", "raid1"));
+    assert!(md0.active);
+    assert_eq!((md0.raid_disks, md0.in_sync), (Some(2), Some(1)));
+    assert_eq!(md0.missing(), 1);
+    assert!(md0.members.iter().any(|m| m.name == "sdb1" && m.faulty));
+
+    let mut report = DiagnosticReport::new("raid", "test");
+    analyze_md(&mut report, md0);
+    let degraded = report
+        .findings
+        .iter()
+        .find(|f| f.message.contains("is degraded"))
+        .expect("degraded finding");
+    assert_eq!(degraded.severity, Severity::Critical);
+    assert!(degraded.message.contains("1 of 2 member(s) missing"));
+    assert!(degraded.details.as_deref().unwrap_or_default().contains("No redundancy left"));
+    assert!(report
+        .recommendations
+        .iter()
+        .any(|r| r.command.as_deref().unwrap_or_default().contains("--remove /dev/sdb1")));
+}
+
+#[test]
+fn mdstat_recovering_raid5_reports_progress_instead_of_replacement() {
+    let arrays = parse_mdstat(DEGRADED_MDSTAT);
+    let md1 = &arrays[1];
+    let sync = md1.sync.as_ref().expect("recovery progress");
+    assert_eq!(sync.action, "recovery");
+    assert_eq!(sync.percent, 18.4);
+    assert_eq!(sync.speed_kib, Some(163_320));
+
+    let mut report = DiagnosticReport::new("raid", "test");
+    analyze_md(&mut report, md1);
+    let degraded = report.findings.iter().find(|f| f.message.contains("is degraded")).unwrap();
+    assert_eq!(degraded.severity, Severity::Critical);
+    assert!(degraded.details.as_deref().unwrap_or_default().contains("Rebuild 18.4% done"));
+    assert!(report.recommendations.is_empty());
+}
+
+#[test]
+fn proc_wireless_parses_dbm_and_unsigned_levels() {
+    let content = "Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE\n \
+                   face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22\n\
+                   wlan0: 0000   54.  -56.  -256        0      0      0     12      0        3\n\
+                   wlan1: 0000   40.  200.  161.        0      0      0      0      0        0\n";
+    let stats = parse_proc_wireless(content);
+    let wlan0 = &stats["wlan0"];
+    assert_eq!(wlan0.quality, Some(54.0));
+    assert_eq!(wlan0.level, Some(-56));
+    assert_eq!(wlan0.noise, None);
+    assert_eq!((wlan0.discarded_retry, wlan0.missed_beacons), (12, 3));
+    // Old drivers report 256 + dBm.
+    assert_eq!((stats["wlan1"].level, stats["wlan1"].noise), (Some(-56), Some(-95)));
+}
+
+#[test]
+fn iw_link_and_station_fields_keep_the_first_value() {
+    let link = "Connected to aa:bb:cc:dd:ee:ff (on wlan0)\n\
+                \tSSID: home\n\
+                \tfreq: 2437\n\
+                \tsignal: -67 dBm\n\
+                \ttx bitrate: 72.2 MBit/s MCS 7 short GI\n\
+                \tbss flags:\tshort-slot-time\n";
+    let fields = parse_iw_fields(link);
+    assert_eq!(fields["SSID"], "home");
+    assert_eq!(fields["freq"], "2437");
+    assert_eq!(fields["signal"], "-67 dBm");
+    assert_eq!(fields["tx bitrate"], "72.2 MBit/s MCS 7 short GI");
+    assert!(!fields.contains_key("Connected to aa"));
+}
+
+#[test]
+fn wifi_channels_and_bands() {
+    assert_eq!(channel(2412), Some(1));
+    assert_eq!(channel(2437), Some(6));
+    assert_eq!(channel(2484), Some(14));
+    assert_eq!(channel(5180), Some(36));
+    assert_eq!(channel(5955), Some(1));
+    assert_eq!(channel(3000), None);
+    assert_eq!((band(2437), band(5180), band(5955)), ("2.4 GHz", "5 GHz", "6 GHz"));
+}
+
+fn scan_entry(bssid: &str, ssid: &str, freq: u32, signal: f64) -> String {
+    format!(
+        "BSS {}(on wlan0)\n\tfreq: {}\n\tsignal: {:.2} dBm\n\tSSID: {}\n\
+         \tRSN:\t * Version: 1\n\t\t * Group cipher: CCMP\n",
+        bssid, freq, signal, ssid
+    )
+}
+
+#[test]
+fn scan_dump_and_24ghz_congestion_count() {
+    let mut dump = String::from(
+        "BSS aa:bb:cc:dd:ee:ff(on wlan0) -- associated\n\tfreq: 2437\n\tsignal: -60.00 dBm\n\tSSID: home\n",
+    );
+    // Five overlapping networks, one too weak to matter, one on a non-overlapping channel.
+    for (i, freq) in [2427, 2437, 2442, 2447, 2452].iter().enumerate() {
+        dump += &scan_entry(&format!("02:00:00:00:00:0{}", i), &format!("neighbour{}", i), *freq, -70.0);
+    }
+    dump += &scan_entry("02:00:00:00:00:10", "faint", 2437, -90.0);
+    dump += &scan_entry("02:00:00:00:00:11", "far", 2412, -50.0);
+
+    let scan = parse_scan_dump(&dump);
+    assert_eq!(scan.len(), 8);
+    assert!(scan[0].associated);
+    assert_eq!((scan[0].ssid.as_str(), scan[0].freq, scan[0].signal), ("home", 2437, Some(-60.0)));
+    assert_eq!(scan[1].bssid, "02:00:00:00:00:00");
+
+    let link = WifiLink {
+        iface: "wlan0".into(),
+        connected: true,
+        ssid: Some("home".into()),
+        bssid: Some("aa:bb:cc:dd:ee:ff".into()),
+        freq: Some(2437),
+        ..Default::default()
+    };
+    let mut report = DiagnosticReport::new("net", "test");
+    analyze_congestion(&mut report, &link, &scan);
+    assert!(report.findings.is_empty(), "five overlapping networks are below the threshold");
+
+    dump += &scan_entry("02:00:00:00:00:12", "neighbour5", 2432, -75.0);
+    let scan = parse_scan_dump(&dump);
+    let mut report = DiagnosticReport::new("net", "test");
+    analyze_congestion(&mut report, &link, &scan);
+    assert_eq!(report.findings.len(), 1);
+    assert!(report.findings[0].message.contains("channel 6 on wlan0 is shared with 6 other network(s)"));
+    assert_eq!(report.findings[0].severity, Severity::Warning);
+}
+
+// Offsets are local clock minus reference: positive means this clock is ahead.
+
+#[test]
+fn timesync_offset_is_positive_when_the_local_clock_is_ahead() {
+    // Server 2 s behind this host, 5 ms each way, 1 ms processing; timestamps in µs.
+    let t1: i64 = 1_700_000_000_000_000;
+    let t2 = t1 + 5_000 - 2_000_000;
+    let t3 = t2 + 1_000;
+    let t4 = t1 + 11_000;
+    let output = format!(
+        "ServerName=pool.example.org\nServerAddress=192.0.2.123\n\
+         NTPMessage={{ Leap=0, Version=4, Mode=4, Stratum=2, Precision=-23, \
+         OriginateTimestamp={}, ReceiveTimestamp={}, TransmitTimestamp={}, DestinationTimestamp={} }}\n",
+        t1, t2, t3, t4
+    );
+    let status = parse_timesync(&output);
+    assert_eq!(status.offset_ms, Some(2000.0));
+    assert_eq!(status.stratum, Some(2));
+    assert_eq!(status.server.as_deref(), Some("pool.example.org (192.0.2.123)"));
+    assert_eq!(status.last_sync, Some(1_700_000_000));
+
+    // Server 2 s ahead: the local clock is behind.
+    let output = output
+        .replace(&t2.to_string(), &(t2 + 4_000_000).to_string())
+        .replace(&t3.to_string(), &(t3 + 4_000_000).to_string());
+    assert_eq!(parse_timesync(&output).offset_ms, Some(-2000.0));
+}
+
+#[test]
+fn chrony_tracking_system_time_is_positive_when_slow() {
+    let fast = "C0000201,192.0.2.1,3,1700000000.123456,-0.250000000,-0.000012,0.000050,\
+                -12.345,-0.001,0.020,0.001,0.002,64.5,Normal\n";
+    let status = parse_chrony_tracking(fast);
+    assert_eq!(status.offset_ms, Some(250.0));
+    assert_eq!(status.synchronized, Some(true));
+    assert_eq!(status.stratum, Some(3));
+    assert_eq!(status.last_sync, Some(1_700_000_000));
+
+    let slow = fast.replace("-0.250000000", "0.100000000").replace("Normal", "Not synchronised");
+    let status = parse_chrony_tracking(&slow);
+    assert_eq!(status.offset_ms, Some(-100.0));
+    assert_eq!(status.synchronized, Some(false));
+}
+
+#[test]
+fn ntpq_offset_is_server_minus_local() {
+    let output = "associd=0 status=0615 leap_none, sync_ntp, 1 event, clock_sync,\n\
+                  version=\"ntpd 4.2.8p15@1.3728-o\", processor=\"x86_64\",\n\
+                  leap=00, stratum=3, precision=-24, rootdelay=1.234, rootdisp=5.678,\n\
+                  refid=192.0.2.1,\n\
+                  reftime=e9d5b2c0.12345678  Fri, Apr 26 2024  4:57:36.071,\n\
+                  clock=e9d5b2f0.00000000  Fri, Apr 26 2024  4:58:24.000, peer=1234, tc=10,\n\
+                  mintc=3, offset=-12.345, frequency=-3.210, sys_jitter=0.456\n";
+    let status = parse_ntpq_rv(output);
+    assert_eq!(status.offset_ms, Some(12.345));
+    assert_eq!(status.synchronized, Some(true));
+    assert_eq!(status.stratum, Some(3));
+    assert_eq!(status.server.as_deref(), Some("192.0.2.1"));
+    assert_eq!(status.last_sync, Some(1_714_107_456));
+
+    let behind = output.replace("offset=-12.345", "offset=+40.5").replace("leap=00", "leap=11");
+    let status = parse_ntpq_rv(&behind);
+    assert_eq!(status.offset_ms, Some(-40.5));
+    assert_eq!(status.synchronized, Some(false));
+}
+
+fn packet(proto: &str, dport: u16, iface: Option<&str>) -> Packet {
+    Packet { proto: proto.into(), dport: Some(dport), iface: iface.map(String::from) }
+}
+
+/// The verdict of the single base chain that sees `p`, and the rule that made it.
+fn verdict(rs: &Ruleset, p: &Packet) -> (Verdict, String) {
+    let decisions = evaluate(rs, Family::V4, "input", p);
+    let last = decisions.last().expect("an input base chain");
+    (last.verdict.clone(), last.by.clone())
+}
+
+const NFT_RULESET: &str = r#"{"nftables": [
+  {"metainfo": {"version": "1.0.6", "json_schema_version": 1}},
+  {"table": {"family": "inet", "name": "filter", "handle": 1}},
+  {"chain": {"family": "inet", "table": "filter", "name": "input", "handle": 1,
+             "type": "filter", "hook": "input", "prio": 0, "policy": "drop"}},
+  {"chain": {"family": "inet", "table": "filter", "name": "services", "handle": 2}},
+  {"chain": {"family": "inet", "table": "filter", "name": "web", "handle": 3}},
+  {"rule": {"family": "inet", "table": "filter", "chain": "input", "expr": [
+    {"match": {"op": "in", "left": {"ct": {"key": "state"}}, "right": ["established", "related"]}},
+    {"accept": null}]}},
+  {"rule": {"family": "inet", "table": "filter", "chain": "input", "expr": [
+    {"match": {"op": "==", "left": {"meta": {"key": "iifname"}}, "right": "lo"}},
+    {"accept": null}]}},
+  {"rule": {"family": "inet", "table": "filter", "chain": "input", "expr": [
+    {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}}, "right": 22}},
+    {"counter": {"packets": 0, "bytes": 0}},
+    {"accept": null}]}},
+  {"rule": {"family": "inet", "table": "filter", "chain": "input", "expr": [
+    {"jump": {"target": "services"}}]}},
+  {"rule": {"family": "inet", "table": "filter", "chain": "input", "expr": [
+    {"match": {"op": "!=", "left": {"meta": {"key": "iifname"}}, "right": "eth0"}},
+    {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}}, "right": 8200}},
+    {"accept": null}]}},
+  {"rule": {"family": "inet", "table": "filter", "chain": "input", "expr": [
+    {"match": {"op": "==", "left": {"payload": {"protocol": "ip", "field": "saddr"}},
+               "right": {"prefix": {"addr": "10.0.0.0", "len": 8}}}},
+    {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}}, "right": 5432}},
+    {"accept": null}]}},
+  {"rule": {"family": "inet", "table": "filter", "chain": "services", "expr": [
+    {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}},
+               "right": {"set": [80, 443]}}},
+    {"accept": null}]}},
+  {"rule": {"family": "inet", "table": "filter", "chain": "services", "expr": [
+    {"match": {"op": "==", "left": {"payload": {"protocol": "udp", "field": "dport"}}, "right": 53}},
+    {"return": null}]}},
+  {"rule": {"family": "inet", "table": "filter", "chain": "services", "expr": [
+    {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}},
+               "right": {"range": [8000, 8100]}}},
+    {"goto": {"target": "web"}}]}},
+  {"rule": {"family": "inet", "table": "filter", "chain": "services", "expr": [
+    {"match": {"op": "==", "left": {"payload": {"protocol": "th", "field": "dport"}}, "right": 9000}},
+    {"drop": null}]}},
+  {"rule": {"family": "inet", "table": "filter", "chain": "web", "expr": [
+    {"match": {"op": "==", "left": {"meta": {"key": "iifname"}}, "right": "eth0"}},
+    {"accept": null}]}},
+  {"table": {"family": "ip", "name": "early", "handle": 2}},
+  {"chain": {"family": "ip", "table": "early", "name": "input", "handle": 1,
+             "type": "filter", "hook": "input", "prio": -10, "policy": "accept"}},
+  {"chain": {"family": "ip", "table": "early", "name": "nat_pre", "handle": 2,
+             "type": "nat", "hook": "prerouting", "prio": -100, "policy": "accept"}}
+]}"#;
+
+#[test]
+fn nft_json_parses_chains_and_reduced_matches() {
+    let rs = nft::parse(NFT_RULESET).unwrap();
+    assert_eq!(rs.backend, Backend::Nft);
+    assert_eq!(rs.chains.len(), 5);
+    let input = &rs.chains[0];
+    assert_eq!((input.hook.as_deref(), input.policy.clone()), (Some("input"), Some(Verdict::Drop)));
+    assert_eq!(input.rules.len(), 6);
+    assert!(matches!(&input.rules[0].matches[..], [Match::CtState { negate: false, .. }]));
+    assert!(matches!(
+        &input.rules[2].matches[..],
+        [Match::DPort { ranges, proto: Some(p), negate: false }] if ranges == &[(22, 22)] && p == "tcp"
+    ));
+    assert_eq!(input.rules[2].text, "tcp dport 22 accept");
+    assert_eq!(input.rules[3].verdict, Some(Verdict::Jump("services".into())));
+    assert!(matches!(&input.rules[4].matches[0], Match::InIface { negate: true, .. }));
+    assert!(matches!(&input.rules[5].matches[0], Match::Other(s) if s == "ip saddr 10.0.0.0/8"));
+    let services = &rs.chains[1];
+    assert_eq!((services.hook.as_deref(), services.policy.clone()), (None, None));
+    assert!(matches!(
+        &services.rules[0].matches[0],
+        Match::DPort { ranges, .. } if ranges == &[(80, 80), (443, 443)]
+    ));
+    assert!(matches!(
+        &services.rules[2].matches[0],
+        Match::DPort { ranges, .. } if ranges == &[(8000, 8100)]
+    ));
+    assert!(matches!(&services.rules[3].matches[0], Match::DPort { proto: None, .. }));
+    assert!(nft::parse("{}").is_err());
+}
+
+#[test]
+fn nft_evaluation_follows_jumps_gotos_and_returns() {
+    let rs = nft::parse(NFT_RULESET).unwrap();
+    let eth0 = Some("eth0");
+    let wlan0 = Some("wlan0");
+
+    let (v, by) = verdict(&rs, &packet("tcp", 22, eth0));
+    assert_eq!(v, Verdict::Accept);
+    assert!(by.starts_with("rule 3 in inet filter input"), "{}", by);
+    let (v, by) = verdict(&rs, &packet("tcp", 443, eth0));
+    assert_eq!(v, Verdict::Accept);
+    assert!(by.starts_with("rule 1 in inet filter services"), "{}", by);
+    // "return" resumes the calling chain, which ends in its drop policy.
+    let (v, by) = verdict(&rs, &packet("udp", 53, eth0));
+    assert_eq!(v, Verdict::Drop);
+    assert_eq!(by, "the default policy of inet filter input");
+    // "goto web": web accepts eth0; otherwise it falls off and returns past services.
+    assert_eq!(verdict(&rs, &packet("tcp", 8050, eth0)).0, Verdict::Accept);
+    assert!(verdict(&rs, &packet("tcp", 8050, wlan0)).1.contains("default policy"));
+    // A th dport rule matches udp as well as tcp.
+    assert_eq!(verdict(&rs, &packet("udp", 9000, eth0)).0, Verdict::Drop);
+    let by = verdict(&rs, &packet("udp", 9000, eth0)).1;
+    assert!(by.starts_with("rule 4 in inet filter services"));
+    // Negated interface match.
+    assert_eq!(verdict(&rs, &packet("tcp", 8200, wlan0)).0, Verdict::Accept);
+    assert!(verdict(&rs, &packet("tcp", 8200, eth0)).1.contains("default policy"));
+}
+
+#[test]
+fn nft_evaluation_propagates_maybe_and_orders_base_chains() {
+    let rs = nft::parse(NFT_RULESET).unwrap();
+    // The source address is unknown, so the saddr rule might accept.
+    let decisions = evaluate(&rs, Family::V4, "input", &packet("tcp", 5432, Some("eth0")));
+    assert_eq!(decisions.len(), 2);
+    assert_eq!(decisions[0].by, "the default policy of ip early input");
+    assert_eq!(decisions[1].verdict, Verdict::Drop);
+    assert_eq!(decisions[1].uncertain.len(), 1);
+    assert!(decisions[1].uncertain[0].contains("ip saddr 10.0.0.0/8"));
+
+    // Without an interface the iifname rules are undecided but do not stop the walk.
+    let no_iface = Packet { proto: "tcp".into(), dport: Some(22), iface: None };
+    let decisions = evaluate(&rs, Family::V6, "input", &no_iface);
+    assert_eq!(decisions.len(), 1, "ip tables do not see IPv6");
+    assert_eq!(decisions[0].verdict, Verdict::Accept);
+    assert!(decisions[0].uncertain.iter().any(|r| r.contains("iifname lo")));
+}
+
+const IPTABLES_SAVE: &str = r#"# Generated by iptables-save v1.8.9 (legacy)
+*nat
+:PREROUTING ACCEPT [0:0]
+:INPUT ACCEPT [0:0]
+-A PREROUTING -p tcp -m tcp --dport 80 -j REDIRECT --to-ports 8080
+COMMIT
+*filter
+:INPUT DROP [12:3456]
+:FORWARD DROP [0:0]
+:OUTPUT ACCEPT [100:20000]
+:SERVICES - [0:0]
+-A INPUT -m conntrack --ctstate RELATED,ESTABLISHED -j ACCEPT
+-A INPUT -i lo -j ACCEPT
+-A INPUT -p tcp -m tcp --dport 22 -m comment --comment "allow ssh" -j ACCEPT
+-A INPUT ! -i eth0 -p udp -m multiport --dports 60000:61000 -j ACCEPT
+-A INPUT -s 10.0.0.0/8 -p tcp -m tcp --dport 5432 -j ACCEPT
+-A INPUT -p tcp -m tcp --dport 23 -j LOG --log-prefix "telnet "
+-A INPUT -j SERVICES
+-A INPUT -p icmp -j ACCEPT
+-A SERVICES -p tcp -m tcp --dport 443 -j ACCEPT
+-A SERVICES -p tcp -m tcp --dport 25 -j REJECT --reject-with tcp-reset
+-A SERVICES -j RETURN
+COMMIT
+"#;
+
+#[test]
+fn iptables_save_parses_the_filter_table_only() {
+    let chains = iptables::parse(IPTABLES_SAVE, "ip");
+    let names: Vec<&str> = chains.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["INPUT", "FORWARD", "OUTPUT", "SERVICES"]);
+    let input = &chains[0];
+    assert_eq!((input.family.as_str(), input.table.as_str()), ("ip", "filter"));
+    assert_eq!((input.hook.as_deref(), input.policy.clone()), (Some("input"), Some(Verdict::Drop)));
+    assert_eq!(input.rules.len(), 8);
+    assert_eq!((chains[3].hook.as_deref(), chains[3].policy.clone()), (None, None));
+
+    assert!(matches!(&input.rules[0].matches[..], [Match::CtState { states, negate: false }]
+        if states == &["related", "established"]));
+    assert_eq!(
+        input.rules[2].text,
+        "-p tcp -m tcp --dport 22 -m comment --comment allow ssh -j ACCEPT"
+    );
+    assert!(matches!(&input.rules[3].matches[..], [
+        Match::InIface { negate: true, .. },
+        Match::Proto { negate: false, .. },
+        Match::DPort { ranges, negate: false, .. },
+    ] if ranges == &[(60000, 61000)]));
+    assert!(matches!(&input.rules[4].matches[0], Match::Other(s) if s == "-s 10.0.0.0/8"));
+    assert_eq!(input.rules[5].verdict, Some(Verdict::Jump("LOG".into())));
+    assert_eq!(chains[3].rules[1].verdict, Some(Verdict::Reject));
+}
+
+#[test]
+fn iptables_evaluation_of_user_chains_and_targets() {
+    let rs = Ruleset { backend: Backend::Iptables, chains: iptables::parse(IPTABLES_SAVE, "ip") };
+    assert_eq!(verdict(&rs, &packet("tcp", 22, Some("eth0"))).0, Verdict::Accept);
+    assert_eq!(verdict(&rs, &packet("tcp", 443, Some("eth0"))).0, Verdict::Accept);
+    let (v, by) = verdict(&rs, &packet("tcp", 25, Some("eth0")));
+    assert_eq!(v, Verdict::Reject);
+    assert!(by.starts_with("rule 2 in ip filter SERVICES"), "{}", by);
+    // LOG is a non-terminating target and RETURN resumes INPUT, which ends in its policy.
+    let (v, by) = verdict(&rs, &packet("tcp", 23, Some("eth0")));
+    assert_eq!(v, Verdict::Drop);
+    assert_eq!(by, "the default policy of ip filter INPUT");
+    assert!(verdict(&rs, &packet("tcp", 80, Some("eth0"))).1.contains("default policy"));
+    // "! -i eth0" with a port range.
+    assert_eq!(verdict(&rs, &packet("udp", 60500, Some("wg0"))).0, Verdict::Accept);
+    assert_eq!(verdict(&rs, &packet("udp", 60500, Some("eth0"))).0, Verdict::Drop);
+
+    let decisions = evaluate(&rs, Family::V4, "input", &packet("tcp", 5432, Some("eth0")));
+    assert_eq!(decisions[0].verdict, Verdict::Drop);
+    assert!(decisions[0].uncertain[0].contains("-s 10.0.0.0/8"));
+    assert!(evaluate(&rs, Family::V6, "input", &packet("tcp", 22, None)).is_empty());
+}
@@ -0,0 +1,42 @@
+//! Tests for shared parsing utilities.
+
+use rustwhy::utils::{mount_for_path, parse_mounts};
+use std::path::Path;
+
+#[test]
+fn parse_mounts_decodes_escapes_and_finds_longest_match() {
+    let content = "/dev/sda1 / ext4 rw,relatime 0 0\n\
+                   /dev/sdb1 /mnt/my\\040disk xfs ro,noatime 0 0\n\
+                   proc /proc proc rw 0 0\n";
+    let mounts = parse_mounts(content);
+    assert_eq!(mounts.len(), 3);
+    assert_eq!(mounts[1].mountpoint, Path::new("/mnt/my disk"));
+    assert!(mounts[1].is_read_only());
+    assert!(mounts[2].is_pseudo());
+
+    let m = mount_for_path(&mounts, Path::new("/mnt/my disk/photos")).unwrap();
+    assert_eq!(m.device, "/dev/sdb1");
+    let m = mount_for_path(&mounts, Path::new("/home")).unwrap();
+    assert_eq!(m.device, "/dev/sda1");
+}
+
+#[test]
+fn tmpfs_is_real_except_for_data_less_mounts() {
+    let content = "tmpfs /run tmpfs rw,nosuid,size=1617000k,mode=755 0 0\n\
+                   tmpfs /dev/shm tmpfs rw,nosuid,nodev 0 0\n\
+                   tmpfs /run/user/1000 tmpfs rw,nosuid,size=808500k,mode=700 0 0\n\
+                   tmpfs /sys/fs/cgroup tmpfs ro,nosuid,mode=755 0 0\n\
+                   tmpfs /run/credentials/systemd-journald.service tmpfs ro,nosuid,size=1024k 0 0\n";
+    let mounts = parse_mounts(content);
+    assert!(mounts[..3].iter().all(|m| !m.is_pseudo()));
+    assert!(mounts[3..].iter().all(|m| m.is_pseudo()));
+}
+
+#[test]
+fn shell_quote_protects_spaces_and_quotes() {
+    use rustwhy::utils::shell_quote;
+    assert_eq!(shell_quote("/home/me/.cache"), "/home/me/.cache");
+    assert_eq!(shell_quote("/home/me/My Cache"), "'/home/me/My Cache'");
+    assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    assert_eq!(shell_quote(""), "''");
+}